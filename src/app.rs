@@ -0,0 +1,1010 @@
+// The player session: the track that's playing, what plays after it,
+// and the state of the interface around them
+use std::collections::HashMap;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+use termion::event::Key;
+
+use crate::artwork;
+use crate::bookmarks;
+use crate::comments;
+use crate::config;
+use crate::control;
+use crate::history;
+use crate::keys;
+use crate::level;
+use crate::output::{self, Output};
+use crate::picker::Picker;
+use crate::player::{Player, PlayerEvent, PlayerState};
+use crate::playlist;
+use crate::queue;
+use crate::radio;
+use crate::record;
+use crate::resume;
+use crate::scrobble;
+use crate::soundcloud;
+use crate::spectrum;
+use crate::status;
+use crate::tap;
+use crate::theme::Theme;
+use crate::ui;
+
+// how long feedback messages stay on screen
+const MESSAGE_TIMEOUT: Duration = Duration::from_secs(4);
+
+// how often the playback position is saved, in case of a crash
+const SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+// Everything loaded for the track that's playing
+struct Current {
+    track: soundcloud::Track,
+    wave: soundcloud::Wave,
+    artwork: Option<artwork::Artwork>,
+    player: Player,
+    comments: comments::Comments,
+    liked: Option<bool>,
+    started_at: DateTime<Local>,
+    // played before the last seek, in ms
+    listened: u32,
+}
+
+// What the text being written is for
+#[derive(Clone, Copy)]
+enum Prompt {
+    // a comment pinned to a position in the track
+    Comment(u32),
+    // tags for a bookmark, by index
+    Tags(usize),
+    // file to export the queue to
+    Export,
+}
+
+// A list being browsed in place of the waveform
+enum Browsing {
+    // newest first
+    History(Picker<history::Entry>),
+    Library(Picker<bookmarks::Bookmark>),
+    // output devices, by name
+    Devices(Picker<String>),
+}
+
+impl Browsing {
+    fn up(&mut self) {
+        match self {
+            Browsing::History(picker) => picker.up(),
+            Browsing::Library(picker) => picker.up(),
+            Browsing::Devices(picker) => picker.up(),
+        }
+    }
+
+    fn down(&mut self) {
+        match self {
+            Browsing::History(picker) => picker.down(),
+            Browsing::Library(picker) => picker.down(),
+            Browsing::Devices(picker) => picker.down(),
+        }
+    }
+
+    // Url of the selected track, and where to start playing it
+    fn selected(&self) -> Option<(String, u32)> {
+        match self {
+            Browsing::History(picker) => picker.selected().map(|e| (e.url.clone(), 0)),
+            Browsing::Library(picker) => picker
+                .selected()
+                .map(|b| (b.url.clone(), b.position.unwrap_or(0))),
+            Browsing::Devices(_) => None,
+        }
+    }
+
+    fn pane(&self) -> ui::Pane {
+        match self {
+            Browsing::History(picker) => ui::Pane {
+                title: "History  enter: play".to_string(),
+                items: picker.items().iter().map(|e| e.summary()).collect(),
+                selected: picker.index(),
+            },
+            Browsing::Library(picker) => ui::Pane {
+                title: "Library  enter: play  t: tag  d: delete".to_string(),
+                items: picker.items().iter().map(|b| b.summary()).collect(),
+                selected: picker.index(),
+            },
+            Browsing::Devices(picker) => ui::Pane {
+                title: "Output devices  enter: play on".to_string(),
+                items: picker.items().to_vec(),
+                selected: picker.index(),
+            },
+        }
+    }
+}
+
+// What to do with a url's tracks, once they're resolved
+enum Resolve {
+    // the first one, straight away, this many ms in
+    Play(u32),
+    Enqueue,
+}
+
+// Tracks for a url, resolved in the background since a user or a long
+// playlist can take a while
+struct Resolved {
+    resolve: Resolve,
+    tracks: Result<Vec<soundcloud::Track>, String>,
+}
+
+/// How to start playing.
+pub struct Options {
+    /// Keep playing related tracks once the queue runs out
+    pub autoplay: bool,
+    /// Position to start the first track at, in ms, instead of offering to resume
+    pub start: Option<u32>,
+    /// Positions to start tracks at by id, in ms, e.g. from an imported playlist
+    pub starts: HashMap<u64, u32>,
+    pub volume: u8,
+    /// Playback speed, from 0.5 to 3
+    pub speed: f32,
+    pub theme: Theme,
+    pub scrobble: config::Scrobble,
+    pub output: Output,
+}
+
+pub struct App {
+    client: soundcloud::Client,
+    bindings: keys::Bindings,
+    current: Current,
+    queue: queue::Queue<soundcloud::Track>,
+    autoplay: bool,
+    theme: Theme,
+    recent: radio::Recent,
+    samples: tap::Buffer,
+    analyzer: spectrum::Analyzer,
+    meter: level::Meter,
+    view: ui::View,
+    show_info: bool,
+    show_help: bool,
+    // feedback from the last action, shown in place of the comment ticker
+    message: Option<(String, Instant)>,
+    // text being written, and what it's for
+    prompt: Option<(Prompt, String)>,
+    positions: resume::Positions,
+    saved_at: Instant,
+    // saved position offered to resume from
+    resume: Option<u32>,
+    // positions from the command line, each used the first time its track plays
+    starts: HashMap<u64, u32>,
+    history: history::History,
+    bookmarks: bookmarks::Bookmarks,
+    browsing: Option<Browsing>,
+    // tracks heard at all, and tracks that wouldn't load
+    played: usize,
+    failed: usize,
+    // urls being resolved, and where their tracks turn up
+    resolving: mpsc::Sender<Resolved>,
+    resolved: mpsc::Receiver<Resolved>,
+    scrobbler: scrobble::Scrobbler,
+    output: Output,
+    recorder: record::Recorder,
+}
+
+impl App {
+    /// Start playing the current track in the queue.
+    pub fn new(
+        client: soundcloud::Client,
+        bindings: keys::Bindings,
+        queue: queue::Queue<soundcloud::Track>,
+        options: Options,
+    ) -> Result<App, failure::Error> {
+        let track = queue
+            .current()
+            .cloned()
+            .ok_or_else(|| failure::format_err!("nothing to play"))?;
+        let mut starts = options.starts;
+        let start = options.start.or_else(|| starts.remove(&track.id));
+
+        let samples = tap::Buffer::new();
+        let recorder = record::Recorder::default();
+        let mut current = Current::load(
+            &client,
+            track,
+            samples.clone(),
+            recorder.clone(),
+            options.volume,
+            start.unwrap_or(0),
+            &options.output,
+        )?;
+        current.player.update(PlayerEvent::Speed(options.speed));
+
+        let mut recent = radio::Recent::default();
+        recent.push(current.track.id);
+
+        let scrobbler = scrobble::Scrobbler::start(options.scrobble);
+        scrobbler.now_playing(&current.track);
+
+        let positions = resume::Positions::load();
+        let (resolving, resolved) = mpsc::channel();
+        let (bookmarks, broken) = bookmarks::Bookmarks::load_or_empty();
+        let message = broken.map(|e| (format!("Bookmarks not loaded: {}", e), Instant::now()));
+        let resume = match start {
+            Some(_) => None,
+            None => positions.get(current.track.id),
+        };
+
+        Ok(App {
+            client,
+            bindings,
+            current,
+            queue,
+            autoplay: options.autoplay,
+            theme: options.theme,
+            recent,
+            analyzer: spectrum::Analyzer::new(samples.clone()),
+            samples,
+            meter: level::Meter::new(),
+            view: ui::View::Wave,
+            show_info: false,
+            show_help: false,
+            message,
+            prompt: None,
+            positions,
+            saved_at: Instant::now(),
+            resume,
+            starts,
+            history: history::History::open(),
+            bookmarks,
+            browsing: None,
+            played: 0,
+            failed: 0,
+            resolving,
+            resolved,
+            scrobbler,
+            output: options.output,
+            recorder,
+        })
+    }
+
+    /// Advance playback, returns false once there's nothing left to play.
+    pub fn tick(&mut self) -> bool {
+        while let Ok(resolved) = self.resolved.try_recv() {
+            self.enqueue(resolved);
+        }
+
+        let current = &mut self.current;
+        current.player.update(PlayerEvent::Tick);
+        current.comments.poll();
+        self.meter.update(&self.samples);
+
+        // the output's broken and wouldn't take the next track either,
+        // what went wrong comes from finishing it
+        if current.player.error().is_some() {
+            return false;
+        }
+        if current.player.state() == PlayerState::Stopped {
+            return self.advance();
+        }
+
+        if self.saved_at.elapsed() >= SAVE_INTERVAL {
+            // not worth interrupting playback over, the next save may work
+            let _ = self.save();
+        }
+
+        true
+    }
+
+    /// Number of tracks that played at all, counted as they finish.
+    pub fn played(&self) -> usize {
+        self.played
+    }
+
+    /// Number of tracks that failed to load.
+    pub fn failed(&self) -> usize {
+        self.failed
+    }
+
+    /// Record the track that's playing and where it got to, before quitting.
+    pub fn quit(&mut self) -> Result<(), failure::Error> {
+        self.record()?;
+        self.save()?;
+        if let Some(recording) = self.recorder.stop() {
+            recording?;
+        }
+        self.output.finish()
+    }
+
+    // Save the playback position
+    fn save(&mut self) -> Result<(), failure::Error> {
+        // until resuming is answered, the saved position still stands
+        let current = &self.current;
+        if self.resume.is_none() {
+            self.positions.set(&current.track, current.player.elapsed());
+        }
+        self.saved_at = Instant::now();
+        self.positions.save()
+    }
+
+    /// The action a key is bound to, if any.
+    pub fn action(&self, key: Key) -> Option<keys::Action> {
+        self.bindings.action(key)
+    }
+
+    /// Handle a key press, returns false when it's time to quit.
+    pub fn input(&mut self, input: Key) -> bool {
+        let action = self.bindings.action(input);
+
+        // while writing, keys are text
+        if let Some((prompt, ref mut text)) = self.prompt {
+            match input {
+                Key::Esc => self.prompt = None,
+                Key::Backspace => {
+                    text.pop();
+                }
+                Key::Char('\n') => {
+                    let text = text.trim().to_string();
+                    self.prompt = None;
+                    self.submit(prompt, text);
+                }
+                Key::Char(c) => text.push(c),
+                _ => {}
+            }
+            return true;
+        }
+
+        // resume is a yes or no question
+        if let Some(position) = self.resume {
+            match input {
+                Key::Char('y') | Key::Char('\n') => {
+                    self.resume = None;
+                    self.seek(position);
+                }
+                Key::Char('n') | Key::Esc => self.resume = None,
+                _ if action == Some(keys::Action::Quit) => return false,
+                _ => {}
+            }
+            return true;
+        }
+
+        // browsing a list, arrows pick a track to play
+        if let Some(ref mut browsing) = self.browsing {
+            match input {
+                Key::Up => browsing.up(),
+                Key::Down => browsing.down(),
+                Key::Char('\n') => {
+                    if let Browsing::Devices(picker) = browsing {
+                        let device = picker.selected().cloned();
+                        self.browsing = None;
+                        if let Some(device) = device {
+                            self.switch_device(device);
+                        }
+                    } else if let Some((url, start)) = browsing.selected() {
+                        self.browsing = None;
+                        self.resolve(url, Resolve::Play(start));
+                    }
+                }
+                Key::Char('t') => self.edit_tags(),
+                Key::Char('d') | Key::Delete => self.delete_bookmark(),
+                Key::Esc => self.browsing = None,
+                _ => match action {
+                    Some(keys::Action::History)
+                    | Some(keys::Action::Library)
+                    | Some(keys::Action::Devices) => self.browsing = None,
+                    Some(keys::Action::Quit) => return false,
+                    _ => {}
+                },
+            }
+            return true;
+        }
+
+        // help is modal, only closing it (or quitting) does anything
+        if self.show_help {
+            match action {
+                Some(keys::Action::Help) => self.show_help = false,
+                Some(keys::Action::Quit) => return false,
+                _ if input == Key::Esc => self.show_help = false,
+                _ => {}
+            }
+            return true;
+        }
+
+        match action {
+            Some(action) => self.act(action),
+            None => true,
+        }
+    }
+
+    // Do what a key is bound to, returns false when it's time to quit
+    fn act(&mut self, action: keys::Action) -> bool {
+        let player = &mut self.current.player;
+        match action {
+            keys::Action::Quit => {
+                return false;
+            }
+            keys::Action::PlayPause => {
+                player.update(PlayerEvent::PlayPause);
+            }
+            keys::Action::VolumeUp => {
+                player.update(PlayerEvent::VolumeUp);
+            }
+            keys::Action::VolumeDown => {
+                player.update(PlayerEvent::VolumeDown);
+            }
+            keys::Action::Slower => {
+                player.update(PlayerEvent::Slower);
+            }
+            keys::Action::Faster => {
+                player.update(PlayerEvent::Faster);
+            }
+            keys::Action::View => {
+                self.view = self.view.next();
+            }
+            keys::Action::Info => {
+                self.show_info = !self.show_info;
+            }
+            keys::Action::Like => {
+                let current = &mut self.current;
+                let result = if current.liked == Some(true) {
+                    self.client
+                        .unlike(current.track.id)
+                        .map(|_| current.liked = Some(false))
+                } else {
+                    self.client
+                        .like(current.track.id)
+                        .map(|_| current.liked = Some(true))
+                };
+                let done = if current.liked == Some(true) {
+                    "Liked"
+                } else {
+                    "Unliked"
+                };
+                self.notify(outcome(result, done));
+            }
+            keys::Action::Repost => {
+                let result = self.client.repost(self.current.track.id);
+                self.notify(outcome(result, "Reposted"));
+            }
+            keys::Action::Comment => {
+                self.prompt = Some((Prompt::Comment(player.elapsed()), String::new()));
+            }
+            keys::Action::Autoplay => {
+                self.autoplay = !self.autoplay;
+                let done = if self.autoplay {
+                    "Autoplay on"
+                } else {
+                    "Autoplay off"
+                };
+                self.notify(done.to_string());
+            }
+            keys::Action::Shuffle => {
+                let shuffle = !self.queue.shuffle();
+                self.queue.set_shuffle(shuffle);
+                let done = if shuffle { "Shuffle on" } else { "Shuffle off" };
+                self.notify(done.to_string());
+            }
+            keys::Action::Repeat => {
+                let repeat = self.queue.repeat().next();
+                self.queue.set_repeat(repeat);
+                let done = match repeat {
+                    queue::Repeat::Off => "Repeat off",
+                    queue::Repeat::All => "Repeat all",
+                    queue::Repeat::One => "Repeat one",
+                };
+                self.notify(done.to_string());
+            }
+            keys::Action::History => match self.history.entries() {
+                Ok(mut entries) => {
+                    entries.reverse();
+                    self.browsing = Some(Browsing::History(Picker::new(entries)));
+                }
+                Err(e) => self.notify(format!("Error: {}", e)),
+            },
+            keys::Action::Bookmark => {
+                let position = player.elapsed();
+                let bookmark = bookmarks::Bookmark::new(&self.current.track, Some(position));
+                let result = self.bookmarks.add(bookmark);
+                let done = format!("Bookmarked at {}", status::Clock::format(position));
+                self.notify(outcome(result, &done));
+            }
+            keys::Action::BookmarkTrack => {
+                let bookmark = bookmarks::Bookmark::new(&self.current.track, None);
+                let result = self.bookmarks.add(bookmark);
+                self.notify(outcome(result, "Bookmarked"));
+            }
+            keys::Action::Library => {
+                self.browsing = Some(self.library(0));
+            }
+            keys::Action::Export => {
+                self.prompt = Some((Prompt::Export, "queue.m3u8".to_string()));
+            }
+            keys::Action::Devices => match output::devices() {
+                Ok(devices) => {
+                    // starting from the one playing
+                    let playing = self.output.device().map(String::from);
+                    let playing = playing.or_else(output::default_device);
+                    let index = devices.iter().position(|d| Some(d) == playing.as_ref());
+                    let mut picker = Picker::new(devices);
+                    picker.select(index.unwrap_or(0));
+                    self.browsing = Some(Browsing::Devices(picker));
+                }
+                Err(e) => self.notify(format!("Error: {}", e)),
+            },
+            keys::Action::Record => {
+                if self.recorder.is_recording() {
+                    self.stop_recording();
+                } else {
+                    self.start_recording();
+                }
+            }
+            keys::Action::Help => {
+                self.show_help = true;
+            }
+        }
+
+        true
+    }
+
+    /// Act on a command from the control socket.
+    pub fn control(&mut self, command: control::Command) -> control::Reply {
+        let player = &mut self.current.player;
+        let playing = player.state() == PlayerState::Playing;
+        match command {
+            control::Command::Play if !playing => player.update(PlayerEvent::PlayPause),
+            control::Command::Pause if playing => player.update(PlayerEvent::PlayPause),
+            control::Command::Play | control::Command::Pause => {}
+            control::Command::Toggle => player.update(PlayerEvent::PlayPause),
+            control::Command::Seek { position } => {
+                self.resume = None;
+                self.seek(position.min(self.current.track.duration));
+            }
+            control::Command::Volume { volume } => player.update(PlayerEvent::Volume(volume)),
+            control::Command::Speed { speed } => player.update(PlayerEvent::Speed(speed)),
+            control::Command::Next => {
+                if !self.advance() {
+                    return control::Reply::error("nothing left to play".to_string());
+                }
+            }
+            control::Command::Open { url } => self.resolve(url, Resolve::Play(0)),
+            control::Command::Shuffle { shuffle } => self.queue.set_shuffle(shuffle),
+            control::Command::Repeat { repeat } => self.queue.set_repeat(repeat),
+            control::Command::Enqueue { url } => self.resolve(url, Resolve::Enqueue),
+            control::Command::Status => {}
+            control::Command::Action { action } => {
+                match keys::Action::all().iter().find(|a| a.name() == action) {
+                    // quitting is left to whoever is in front of the player
+                    Some(keys::Action::Quit) | None => {
+                        return control::Reply::error(format!("unknown action '{}'", action));
+                    }
+                    Some(&action) => {
+                        self.act(action);
+                    }
+                }
+            }
+        }
+
+        control::Reply {
+            status: Some(self.status()),
+            ..control::Reply::ok()
+        }
+    }
+
+    /// What's playing, for the control socket and remote.
+    pub fn status(&self) -> control::Status {
+        let current = &self.current;
+        let state = current.player.state();
+        control::Status {
+            track_id: current.track.id,
+            title: current.track.title.clone(),
+            username: current.track.user.username.clone(),
+            url: current.track.permalink_url.clone(),
+            artwork_url: current.track.artwork_url.clone(),
+            playing: state == PlayerState::Playing,
+            state: match state {
+                PlayerState::Playing => "playing",
+                PlayerState::Paused => "paused",
+                PlayerState::Stopped => "stopped",
+            }
+            .to_string(),
+            position_ms: current.player.elapsed(),
+            duration_ms: current.track.duration,
+            volume: current.player.volume(),
+            speed: current.player.speed(),
+            queue: self.queue.items().len(),
+            shuffle: self.queue.shuffle(),
+            repeat: self.queue.repeat(),
+        }
+    }
+
+    /// The current track's artwork, when it has any.
+    #[cfg_attr(not(feature = "notifications"), allow(dead_code))]
+    pub fn artwork(&self) -> Option<&artwork::Artwork> {
+        self.current.artwork.as_ref()
+    }
+
+    /// Everything needed to draw the current frame.
+    pub fn screen(&self) -> ui::Screen {
+        let current = &self.current;
+        let player = &current.player;
+        ui::Screen {
+            track: &current.track,
+            wave: &current.wave,
+            view: self.view,
+            progress: player.progress(),
+            markers: current.comments.markers(current.track.duration),
+            comment: current.comments.nearest(player.elapsed()),
+            bands: self.analyzer.bands(),
+            is_playing: player.state() == PlayerState::Playing,
+            volume: player.volume(),
+            speed: player.speed(),
+            elapsed_ms: player.elapsed(),
+            levels: self.meter.reading(),
+            liked: current.liked,
+            autoplay: self.autoplay,
+            shuffle: self.queue.shuffle(),
+            repeat: self.queue.repeat(),
+            recording: self.recorder.is_recording(),
+            message: self
+                .message
+                .as_ref()
+                .filter(|m| m.1.elapsed() < MESSAGE_TIMEOUT)
+                .map(|m| m.0.as_str()),
+            info: self.show_info,
+            artwork: current.artwork.as_ref(),
+            help: if self.show_help {
+                Some(self.bindings.help())
+            } else {
+                None
+            },
+            prompt: self.prompt.as_ref().map(|(prompt, text)| {
+                let title = match prompt {
+                    Prompt::Comment(timestamp) => {
+                        format!("Comment at {}", status::Clock::format(*timestamp))
+                    }
+                    Prompt::Tags(_) => "Tags".to_string(),
+                    Prompt::Export => "Export queue to (.m3u8, .pls, .json)".to_string(),
+                };
+                (title, text.clone())
+            }),
+            resume: self.resume,
+            pane: self.browsing.as_ref().map(Browsing::pane),
+            theme: self.theme,
+        }
+    }
+
+    // Move on to the next track in the queue, or a related one when the
+    // queue is finished and autoplay is on. Tracks that fail to load are
+    // skipped, even on repeat, giving up after a whole queue of them.
+    fn advance(&mut self) -> bool {
+        let mut attempts = self.queue.items().len().max(1);
+        let mut skip = false;
+        loop {
+            let track = match self.following(skip) {
+                Some(track) => track,
+                None => return false,
+            };
+            match self.load(track, 0) {
+                Ok(()) => return true,
+                Err(e) => self.notify(format!("Error: {}", e)),
+            }
+
+            attempts -= 1;
+            if attempts == 0 {
+                return false;
+            }
+            skip = true;
+        }
+    }
+
+    // The next track to play, or None when there's nothing left
+    fn following(&mut self, skip: bool) -> Option<soundcloud::Track> {
+        let next = if skip {
+            self.queue.skip()
+        } else {
+            self.queue.advance()
+        };
+        if let Some(track) = next {
+            return Some(track.clone());
+        }
+        if !self.autoplay {
+            return None;
+        }
+
+        match self.related() {
+            Ok(Some(track)) => {
+                self.queue.push(track.clone());
+                self.queue.advance();
+                Some(track)
+            }
+            Ok(None) => None,
+            Err(e) => {
+                self.notify(format!("Error: {}", e));
+                None
+            }
+        }
+    }
+
+    // Act on text that's been written
+    fn submit(&mut self, prompt: Prompt, text: String) {
+        match prompt {
+            Prompt::Comment(timestamp) => {
+                if text.is_empty() {
+                    return;
+                }
+
+                let current = &mut self.current;
+                let result = self
+                    .client
+                    .comment(current.track.id, &text, timestamp)
+                    .map(|comment| current.comments.add(comment));
+                self.notify(outcome(result, "Comment posted"));
+            }
+            Prompt::Tags(index) => {
+                let result = self.bookmarks.tag(index, bookmarks::parse_tags(&text));
+                self.browsing = Some(self.library(index));
+                self.notify(outcome(result, "Tagged"));
+            }
+            Prompt::Export => {
+                if text.is_empty() {
+                    return;
+                }
+
+                let entries: Vec<playlist::Entry> = self
+                    .queue
+                    .items()
+                    .into_iter()
+                    .map(playlist::Entry::from)
+                    .collect();
+                let result = playlist::export(Path::new(&text), &entries);
+                self.notify(outcome(result, &format!("Exported to {}", text)));
+            }
+        }
+    }
+
+    // Bookmarks pane, with the bookmark at `index` selected
+    fn library(&self, index: usize) -> Browsing {
+        let mut picker = Picker::new(self.bookmarks.all().to_vec());
+        picker.select(index);
+        Browsing::Library(picker)
+    }
+
+    fn edit_tags(&mut self) {
+        if let Some(Browsing::Library(ref picker)) = self.browsing {
+            if let Some(bookmark) = picker.selected() {
+                let text = bookmark.tags.join(" ");
+                self.prompt = Some((Prompt::Tags(picker.index()), text));
+            }
+        }
+    }
+
+    fn delete_bookmark(&mut self) {
+        let index = match self.browsing {
+            Some(Browsing::Library(ref picker)) if picker.selected().is_some() => picker.index(),
+            _ => return,
+        };
+
+        let result = self.bookmarks.remove(index);
+        self.browsing = Some(self.library(index));
+        self.notify(outcome(result, "Deleted"));
+    }
+
+    // Look up a url's tracks on another thread, they're picked up on a tick
+    fn resolve(&self, url: String, resolve: Resolve) {
+        let client = self.client.clone();
+        let resolving = self.resolving.clone();
+        thread::spawn(move || {
+            let tracks = client.tracks(url).map_err(|e| e.to_string());
+            let _ = resolving.send(Resolved { resolve, tracks });
+        });
+    }
+
+    // Play the first track now, the rest of the queue follows, or add
+    // them all to the end
+    fn enqueue(&mut self, resolved: Resolved) {
+        let tracks = match resolved.tracks {
+            Ok(tracks) => tracks,
+            Err(e) => return self.notify(format!("Error: {}", e)),
+        };
+
+        match resolved.resolve {
+            Resolve::Play(start) => {
+                if let Some(track) = tracks.into_iter().next() {
+                    self.queue.play(track.clone());
+                    if let Err(e) = self.load(track, start) {
+                        self.notify(format!("Error: {}", e));
+                    }
+                }
+            }
+            Resolve::Enqueue => {
+                let count = tracks.len();
+                for track in tracks {
+                    self.queue.push(track);
+                }
+                self.notify(format!("Queued {} tracks", count));
+            }
+        }
+    }
+
+    // Switch to another track, remembering the outgoing one. When it fails
+    // to load, the outgoing one stays, stopped.
+    fn load(&mut self, track: soundcloud::Track, start: u32) -> Result<(), failure::Error> {
+        let _ = self.record();
+        let _ = self.save();
+        // a recording is of one track
+        self.stop_recording();
+
+        let volume = self.current.player.volume();
+        let speed = self.current.player.speed();
+        let start = match start {
+            0 => self.starts.remove(&track.id).unwrap_or(0),
+            start => start,
+        };
+        let mut current = match Current::load(
+            &self.client,
+            track,
+            self.samples.clone(),
+            self.recorder.clone(),
+            volume,
+            start,
+            &self.output,
+        ) {
+            Ok(current) => current,
+            Err(e) => {
+                self.failed += 1;
+                return Err(e);
+            }
+        };
+        current.player.update(PlayerEvent::Speed(speed));
+        self.recent.push(current.track.id);
+        self.resume = match start {
+            0 => self.positions.get(current.track.id),
+            _ => None,
+        };
+        self.scrobbler.now_playing(&current.track);
+        self.current = current;
+        self.prompt = None;
+        Ok(())
+    }
+
+    // Add the track that's playing to the history
+    fn record(&mut self) -> Result<(), failure::Error> {
+        let current = &self.current;
+        let listened = current.listened + current.player.played();
+        if listened == 0 {
+            return Ok(());
+        }
+
+        self.played += 1;
+        if scrobble::counts(current.track.duration, listened) {
+            let scrobble = scrobble::Scrobble::new(&current.track, current.started_at);
+            self.scrobbler.scrobble(scrobble);
+        }
+        let entry = history::Entry::new(&current.track, current.started_at, listened);
+        self.history.append(&entry)?;
+
+        // only recorded once, even if loading the next track fails
+        self.current.listened = 0;
+        self.current.started_at = Local::now();
+        Ok(())
+    }
+
+    // Restart the current track from `position` ms
+    fn seek(&mut self, position: u32) {
+        // a recording is of one stretch of the track, not bits spliced together
+        self.stop_recording();
+        if let Err(e) = self.restart(position) {
+            self.notify(format!("Error: {}", e));
+        }
+    }
+
+    fn restart(&mut self, position: u32) -> Result<(), failure::Error> {
+        let current = &mut self.current;
+        let mut player = Player::new(
+            &current.track,
+            self.samples.clone(),
+            self.recorder.clone(),
+            current.player.volume(),
+            position,
+            &self.output,
+        )?;
+        player.update(PlayerEvent::Speed(current.player.speed()));
+        current.listened += current.player.played();
+        current.player = player;
+        Ok(())
+    }
+
+    // Carry on playing from the same position on another device
+    fn switch_device(&mut self, device: String) {
+        let output = match Output::speaker(Some(&device)) {
+            Ok(output) => output,
+            Err(e) => return self.notify(format!("Error: {}", e)),
+        };
+        let previous = mem::replace(&mut self.output, output);
+        let paused = self.current.player.state() == PlayerState::Paused;
+        match self.restart(self.current.player.elapsed()) {
+            Ok(()) => {
+                if paused {
+                    self.current.player.update(PlayerEvent::PlayPause);
+                }
+                self.notify(format!("Playing on {}", device));
+            }
+            Err(e) => {
+                self.output = previous;
+                self.notify(format!("Error: {}", e));
+            }
+        }
+    }
+
+    // Record the rest of the track to `user - title.flac`
+    fn start_recording(&mut self) {
+        let track = &self.current.track;
+        let path = PathBuf::from(record::filename(track, "flac"));
+        match record::Recording::create(&path, track) {
+            Ok(recording) => {
+                self.recorder.start(recording);
+                self.notify(format!("Recording to {}", path.display()));
+            }
+            Err(e) => self.notify(format!("Error: {}", e)),
+        }
+    }
+
+    fn stop_recording(&mut self) {
+        match self.recorder.stop() {
+            Some(Ok(path)) => self.notify(format!("Saved {}", path.display())),
+            Some(Err(e)) => self.notify(format!("Error: {}", e)),
+            None => {}
+        }
+    }
+
+    fn related(&self) -> Result<Option<soundcloud::Track>, failure::Error> {
+        let related = self.client.related(self.current.track.id)?;
+        Ok(radio::pick(related, &self.recent))
+    }
+
+    fn notify(&mut self, message: String) {
+        self.message = Some((message, Instant::now()));
+    }
+}
+
+impl Current {
+    fn load(
+        client: &soundcloud::Client,
+        track: soundcloud::Track,
+        samples: tap::Buffer,
+        recorder: record::Recorder,
+        volume: u8,
+        start: u32,
+        output: &Output,
+    ) -> Result<Current, failure::Error> {
+        let wave = client.wave(&track)?;
+        let artwork = match client.artwork(&track) {
+            Ok(Some(bytes)) => artwork::Artwork::decode(&bytes).ok(),
+            _ => None,
+        };
+
+        // like state is only known when logged in
+        let liked = if client.is_authenticated() {
+            client.is_liked(track.id).ok()
+        } else {
+            None
+        };
+
+        Ok(Current {
+            player: Player::new(&track, samples, recorder, volume, start, output)?,
+            comments: comments::Comments::fetch(track.id),
+            track,
+            wave,
+            artwork,
+            liked,
+            started_at: Local::now(),
+            listened: 0,
+        })
+    }
+}
+
+// Feedback message for the result of an action
+fn outcome(result: Result<(), failure::Error>, done: &str) -> String {
+    match result {
+        Ok(_) => done.to_string(),
+        Err(e) => format!("Error: {}", e),
+    }
+}
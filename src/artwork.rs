@@ -0,0 +1,65 @@
+use image::imageops::FilterType;
+use image::RgbImage;
+use tui::buffer::Buffer;
+use tui::layout::Rect;
+use tui::style::Color;
+use tui::widgets::Widget;
+
+// artwork is kept around at this size (in pixels), plenty for a terminal
+const SIZE: u32 = 64;
+
+/// Track artwork, drawn as half-block characters: each cell shows two
+/// pixels, the top one as foreground and the bottom one as background.
+#[derive(Clone)]
+pub struct Artwork {
+    image: RgbImage,
+}
+
+impl Artwork {
+    pub fn decode(bytes: &[u8]) -> Result<Artwork, failure::Error> {
+        let image = image::load_from_memory(bytes)?.to_rgb();
+        Ok(Artwork {
+            image: image::imageops::resize(&image, SIZE, SIZE, FilterType::Triangle),
+        })
+    }
+
+    #[cfg_attr(not(feature = "notifications"), allow(dead_code))]
+    pub fn image(&self) -> &RgbImage {
+        &self.image
+    }
+
+    /// Size in cells of the largest square that fits in `area`.
+    pub fn fit(area: Rect) -> Rect {
+        // cells are roughly twice as tall as they are wide,
+        // so a square image is twice as many columns as rows
+        let rows = area.height.min(area.width / 2);
+        Rect::new(area.left(), area.top(), rows * 2, rows)
+    }
+}
+
+impl Widget for Artwork {
+    fn draw(&mut self, area: Rect, buf: &mut Buffer) {
+        let area = Artwork::fit(area);
+        if area.area() == 0 {
+            return;
+        }
+
+        let pixels = image::imageops::resize(
+            &self.image,
+            area.width as u32,
+            area.height as u32 * 2,
+            FilterType::Triangle,
+        );
+
+        for y in 0..area.height {
+            for x in 0..area.width {
+                let top = pixels.get_pixel(x as u32, y as u32 * 2);
+                let bottom = pixels.get_pixel(x as u32, y as u32 * 2 + 1);
+                buf.get_mut(area.left() + x, area.top() + y)
+                    .set_symbol("▀")
+                    .set_fg(Color::Rgb(top[0], top[1], top[2]))
+                    .set_bg(Color::Rgb(bottom[0], bottom[1], bottom[2]));
+            }
+        }
+    }
+}
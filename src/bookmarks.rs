@@ -0,0 +1,255 @@
+// Tracks saved locally, optionally at a position ("the drop at 43:10"),
+// in $XDG_DATA_HOME/scli/bookmarks.json, no SoundCloud account needed
+use std::fs;
+use std::path::PathBuf;
+
+use chrono::{DateTime, Local};
+
+use crate::soundcloud;
+use crate::status::Clock;
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Bookmark {
+    pub track_id: u64,
+    pub url: String,
+    pub title: String,
+    pub username: String,
+    /// Position in the track, in ms, None for the whole track
+    pub position: Option<u32>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub added_at: DateTime<Local>,
+}
+
+impl Bookmark {
+    pub fn new(track: &soundcloud::Track, position: Option<u32>) -> Bookmark {
+        Bookmark {
+            track_id: track.id,
+            url: track.permalink_url.clone(),
+            title: track.title.clone(),
+            username: track.user.username.clone(),
+            position,
+            tags: Vec::new(),
+            added_at: Local::now(),
+        }
+    }
+
+    /// Single line summary, e.g. `trippycode - Art of Minimal Techno @ 43:10  #drop`
+    pub fn summary(&self) -> String {
+        let mut summary = format!("{} - {}", self.username, self.title);
+        if let Some(position) = self.position {
+            summary.push_str(&format!(" @ {}", Clock::format(position)));
+        }
+        if !self.tags.is_empty() {
+            let tags: Vec<String> = self.tags.iter().map(|t| format!("#{}", t)).collect();
+            summary.push_str(&format!("  {}", tags.join(" ")));
+        }
+
+        summary
+    }
+
+    /// Whether the title, uploader, url or a tag contain `term`, ignoring case.
+    pub fn matches(&self, term: &str) -> bool {
+        let term = term.to_lowercase();
+        [&self.title, &self.username, &self.url]
+            .iter()
+            .map(|field| field.as_str())
+            .chain(self.tags.iter().map(String::as_str))
+            .any(|field| field.to_lowercase().contains(&term))
+    }
+}
+
+/// Split tags as typed, on spaces or commas, dropping any leading `#`.
+pub fn parse_tags(text: &str) -> Vec<String> {
+    text.split(|c: char| c == ',' || c.is_whitespace())
+        .map(|tag| tag.trim_start_matches('#'))
+        .filter(|tag| !tag.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+pub struct Bookmarks {
+    path: Option<PathBuf>,
+    bookmarks: Vec<Bookmark>,
+    // the file couldn't be read, leave it be until a bookmark is added
+    keep_file: bool,
+}
+
+impl Bookmarks {
+    pub fn load() -> Result<Bookmarks, failure::Error> {
+        match path() {
+            Some(path) => Bookmarks::from_file(path),
+            None => Ok(Bookmarks::empty(None)),
+        }
+    }
+
+    /// Like `load`, but a file that can't be read gives an empty library,
+    /// along with why, rather than an error.
+    pub fn load_or_empty() -> (Bookmarks, Option<failure::Error>) {
+        match path() {
+            Some(path) => Bookmarks::from_file_or_empty(path),
+            None => (Bookmarks::empty(None), None),
+        }
+    }
+
+    pub fn from_file_or_empty(path: PathBuf) -> (Bookmarks, Option<failure::Error>) {
+        match Bookmarks::from_file(path.clone()) {
+            Ok(bookmarks) => (bookmarks, None),
+            Err(e) => {
+                let mut bookmarks = Bookmarks::empty(Some(path));
+                bookmarks.keep_file = true;
+                (bookmarks, Some(e))
+            }
+        }
+    }
+
+    fn empty(path: Option<PathBuf>) -> Bookmarks {
+        Bookmarks {
+            path,
+            bookmarks: Vec::new(),
+            keep_file: false,
+        }
+    }
+
+    pub fn from_file(path: PathBuf) -> Result<Bookmarks, failure::Error> {
+        let bookmarks = if path.exists() {
+            let contents = fs::read_to_string(&path)?;
+            serde_json::from_str(&contents)
+                .map_err(|e| failure::format_err!("invalid bookmarks {}: {}", path.display(), e))?
+        } else {
+            Vec::new()
+        };
+
+        Ok(Bookmarks {
+            path: Some(path),
+            bookmarks,
+            keep_file: false,
+        })
+    }
+
+    /// Every bookmark, oldest first.
+    pub fn all(&self) -> &[Bookmark] {
+        &self.bookmarks
+    }
+
+    pub fn add(&mut self, bookmark: Bookmark) -> Result<(), failure::Error> {
+        self.bookmarks.push(bookmark);
+        self.keep_file = false;
+        self.save()
+    }
+
+    pub fn remove(&mut self, index: usize) -> Result<(), failure::Error> {
+        if index < self.bookmarks.len() {
+            self.bookmarks.remove(index);
+        }
+        self.save()
+    }
+
+    pub fn tag(&mut self, index: usize, tags: Vec<String>) -> Result<(), failure::Error> {
+        if let Some(bookmark) = self.bookmarks.get_mut(index) {
+            bookmark.tags = tags;
+        }
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), failure::Error> {
+        let path = match self.path {
+            Some(ref path) if !self.keep_file => path,
+            _ => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        // write then rename, so a failed write can't lose everything
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(&self.bookmarks)?)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+fn path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("scli").join("bookmarks.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    fn track() -> soundcloud::Track {
+        soundcloud::Track {
+            id: 1,
+            title: String::from("Art of Minimal Techno"),
+            permalink_url: String::from("https://soundcloud.com/trippycode/art-of-minimal"),
+            user: soundcloud::User {
+                username: String::from("trippycode"),
+                ..soundcloud::User::default()
+            },
+            ..soundcloud::Track::default()
+        }
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut bookmark = Bookmark::new(&track(), None);
+        assert_eq!(bookmark.summary(), "trippycode - Art of Minimal Techno");
+
+        bookmark.position = Some((43 * 60 + 10) * 1000);
+        bookmark.tags = parse_tags("#drop, peak  time");
+        assert_eq!(
+            bookmark.summary(),
+            "trippycode - Art of Minimal Techno @ 43:10  #drop #peak #time"
+        );
+        assert!(bookmark.matches("PEAK"));
+        assert!(!bookmark.matches("house"));
+    }
+
+    #[test]
+    fn test_add_tag_remove() {
+        let dir = env::temp_dir().join(format!("scli-bookmarks-{}", process::id()));
+        let path = dir.join("bookmarks.json");
+
+        let mut bookmarks = Bookmarks::from_file(path.clone()).unwrap();
+        bookmarks.add(Bookmark::new(&track(), None)).unwrap();
+        bookmarks.add(Bookmark::new(&track(), Some(1000))).unwrap();
+        bookmarks.tag(1, vec!["drop".to_string()]).unwrap();
+
+        let mut loaded = Bookmarks::from_file(path.clone()).unwrap();
+        assert_eq!(loaded.all().len(), 2);
+        assert_eq!(loaded.all()[1].position, Some(1000));
+        assert_eq!(loaded.all()[1].tags, vec!["drop".to_string()]);
+
+        loaded.remove(0).unwrap();
+        let loaded = Bookmarks::from_file(path).unwrap();
+        assert_eq!(loaded.all().len(), 1);
+        assert_eq!(loaded.all()[0].position, Some(1000));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_corrupt() {
+        let dir = env::temp_dir().join(format!("scli-bookmarks-corrupt-{}", process::id()));
+        let path = dir.join("bookmarks.json");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&path, "[{\"track_id\": 1,").unwrap();
+
+        assert!(Bookmarks::from_file(path.clone()).is_err());
+        let (mut bookmarks, error) = Bookmarks::from_file_or_empty(path.clone());
+        assert!(error.is_some());
+        assert!(bookmarks.all().is_empty());
+
+        // left alone until there's something to replace it with
+        bookmarks.remove(0).unwrap();
+        bookmarks.tag(0, vec!["drop".to_string()]).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[{\"track_id\": 1,");
+
+        bookmarks.add(Bookmark::new(&track(), None)).unwrap();
+        assert_eq!(Bookmarks::from_file(path).unwrap().all().len(), 1);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
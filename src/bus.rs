@@ -0,0 +1,46 @@
+// A private session bus, so tests don't touch the desktop's
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+
+use dbus::blocking::Connection;
+use dbus::channel::Channel;
+
+pub struct Daemon {
+    child: Child,
+    pub address: String,
+}
+
+impl Daemon {
+    /// Start a bus, unless dbus-daemon isn't installed.
+    pub fn start() -> Option<Daemon> {
+        let mut child = Command::new("dbus-daemon")
+            .args(&["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .ok()?;
+        let mut address = String::new();
+        BufReader::new(child.stdout.as_mut()?)
+            .read_line(&mut address)
+            .ok()?;
+        Some(Daemon {
+            child,
+            address: address.trim().to_string(),
+        })
+    }
+
+    pub fn connect(&self) -> Result<Connection, dbus::Error> {
+        connect(&self.address)
+    }
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+    }
+}
+
+pub fn connect(address: &str) -> Result<Connection, dbus::Error> {
+    let mut channel = Channel::open_private(address)?;
+    channel.register()?;
+    Ok(Connection::from(channel))
+}
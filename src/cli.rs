@@ -0,0 +1,404 @@
+// Command line arguments, checked before anything touches the network
+// or the terminal so mistakes get a readable error
+use std::path::Path;
+
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+
+use crate::output;
+use crate::playlist;
+use crate::record;
+use crate::status::Clock;
+use crate::stretch;
+use crate::theme::Theme;
+
+pub fn app() -> App<'static, 'static> {
+    App::new("scli")
+        .version(clap::crate_version!())
+        .about("Stream SoundCloud in your terminal")
+        .setting(AppSettings::ArgRequiredElseHelp)
+        .setting(AppSettings::VersionlessSubcommands)
+        .arg(
+            Arg::with_name("volume")
+                .long("volume")
+                .value_name("percent")
+                .help("Starting volume, 0 to 100")
+                .global(true)
+                .validator(is_volume),
+        )
+        .arg(
+            Arg::with_name("theme")
+                .long("theme")
+                .value_name("name")
+                .help("Colour theme")
+                .global(true)
+                .possible_values(&Theme::names()),
+        )
+        .arg(
+            Arg::with_name("device")
+                .long("device")
+                .value_name("name")
+                .help("Output device to play on, see `scli devices`")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("audio")
+                .long("audio")
+                .value_name("speaker|null|wav:file|pcm")
+                .help("Where the audio goes, for machines without a speaker")
+                .global(true)
+                .validator(is_audio),
+        )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .value_name("file")
+                .help("Config file to use instead of ~/.config/scli/config.toml")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("no-tui")
+                .long("no-tui")
+                .help("Play without the full screen interface, showing progress on stderr")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("quiet")
+                .short("q")
+                .long("quiet")
+                .help("Play without showing anything at all")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("remote")
+                .long("remote")
+                .value_name("[host:]port")
+                .help("Serve a remote control API over HTTP, e.g. for a phone")
+                .global(true),
+        )
+        .args(&play())
+        .subcommand(
+            SubCommand::with_name("play")
+                .about("Play tracks, playlists or users' tracks")
+                .args(&play()),
+        )
+        .subcommand(
+            SubCommand::with_name("search")
+                .about("Search SoundCloud for tracks")
+                .arg(Arg::with_name("query").required(true).multiple(true))
+                .arg(
+                    Arg::with_name("limit")
+                        .long("limit")
+                        .value_name("n")
+                        .default_value("20")
+                        .validator(is_number),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("user")
+                .about("List a user's tracks")
+                .arg(Arg::with_name("user").value_name("name|url").required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("download")
+                .about("Save a track's audio as mp3")
+                .arg(Arg::with_name("url").required(true).validator(is_url))
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("file")
+                        .help("Defaults to \"<user> - <title>.mp3\""),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("record")
+                .about("Save a track as WAV or FLAC, or as mp3 with its details tagged")
+                .arg(Arg::with_name("url").required(true).validator(is_url))
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("file")
+                        .help(".wav, .flac or .mp3, defaults to \"<user> - <title>.flac\"")
+                        .validator(is_recording),
+                ),
+        )
+        .subcommand(SubCommand::with_name("devices").about("List output devices"))
+        .subcommand(
+            SubCommand::with_name("history")
+                .about("List the listening history, searching it for any terms")
+                .arg(Arg::with_name("terms").multiple(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("bookmarks")
+                .about("List bookmarks, searching them for any terms")
+                .arg(Arg::with_name("terms").multiple(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+                .about("Write the history or bookmarks to a playlist file")
+                .arg(
+                    Arg::with_name("what")
+                        .required(true)
+                        .possible_values(&["history", "bookmarks"]),
+                )
+                .arg(
+                    Arg::with_name("file")
+                        .required(true)
+                        .help(".m3u8, .pls or .json"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("ctl")
+                .about("Control a running player")
+                .arg(Arg::with_name("command").required(true).possible_values(&[
+                    "play", "pause", "toggle", "seek", "volume", "speed", "next", "enqueue", "status",
+                ]))
+                .arg(
+                    Arg::with_name("value")
+                        .required_ifs(&[
+                            ("command", "seek"),
+                            ("command", "volume"),
+                            ("command", "speed"),
+                            ("command", "enqueue"),
+                        ])
+                        .help("mm:ss to seek to, volume from 0 to 100, speed from 0.5 to 3, or url to enqueue"),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .help("Print the status as JSON"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("info")
+                .about("Show a track's details")
+                .arg(Arg::with_name("url").required(true).validator(is_url)),
+        )
+}
+
+// Arguments for playing, taken with or without the `play` subcommand
+fn play() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("sources")
+            .value_name("url|playlist file")
+            .help("SoundCloud track, playlist or user urls, or playlist files")
+            .multiple(true)
+            .validator(is_source),
+        Arg::with_name("autoplay")
+            .long("autoplay")
+            .help("Keep playing related tracks once the queue runs out"),
+        Arg::with_name("shuffle")
+            .long("shuffle")
+            .help("Start shuffled"),
+        Arg::with_name("seed")
+            .long("seed")
+            .value_name("n")
+            .help("Seed for the shuffle, to repeat an order")
+            .validator(is_number),
+        Arg::with_name("speed")
+            .long("speed")
+            .value_name("x")
+            .help("Playback speed, from 0.5 to 3, keeping the pitch")
+            .validator(is_speed),
+        Arg::with_name("from")
+            .long("from")
+            .value_name("mm:ss")
+            .help("Start the first track at this time")
+            .validator(is_time),
+    ]
+}
+
+/// The config file given, before or after the subcommand: a global given
+/// after it only shows up in the subcommand's matches.
+pub fn config<'a>(matches: &'a ArgMatches) -> Option<&'a str> {
+    matches
+        .subcommand()
+        .1
+        .and_then(|subcommand| subcommand.value_of("config"))
+        .or_else(|| matches.value_of("config"))
+}
+
+fn is_volume(value: String) -> Result<(), String> {
+    match value.parse::<u8>() {
+        Ok(volume) if volume <= 100 => Ok(()),
+        _ => Err(String::from("volume must be between 0 and 100")),
+    }
+}
+
+fn is_speed(value: String) -> Result<(), String> {
+    match value.parse::<f32>() {
+        Ok(speed) if (stretch::MIN_SPEED..=stretch::MAX_SPEED).contains(&speed) => Ok(()),
+        _ => Err(String::from("speed must be between 0.5 and 3")),
+    }
+}
+
+fn is_audio(value: String) -> Result<(), String> {
+    match output::Kind::parse(&value) {
+        Some(_) => Ok(()),
+        None => Err(format!(
+            "'{}' isn't an output, use speaker, null, wav:<file> or pcm",
+            value
+        )),
+    }
+}
+
+fn is_recording(value: String) -> Result<(), String> {
+    match record::Format::from_path(Path::new(&value)) {
+        Some(_) => Ok(()),
+        None => Err(format!(
+            "can't record to '{}', use .wav, .flac or .mp3",
+            value
+        )),
+    }
+}
+
+fn is_number(value: String) -> Result<(), String> {
+    value
+        .parse::<u64>()
+        .map(|_| ())
+        .map_err(|_| format!("'{}' isn't a number", value))
+}
+
+fn is_time(value: String) -> Result<(), String> {
+    match Clock::parse(&value) {
+        Some(_) => Ok(()),
+        None => Err(format!("'{}' isn't a time, use mm:ss", value)),
+    }
+}
+
+fn is_url(value: String) -> Result<(), String> {
+    let host = value
+        .splitn(2, "://")
+        .nth(1)
+        .and_then(|rest| rest.split('/').next())
+        .unwrap_or("");
+    let scheme_ok = value.starts_with("https://") || value.starts_with("http://");
+    if scheme_ok && (host == "soundcloud.com" || host.ends_with(".soundcloud.com")) {
+        Ok(())
+    } else {
+        Err(format!("'{}' isn't a SoundCloud url", value))
+    }
+}
+
+fn is_source(value: String) -> Result<(), String> {
+    let path = Path::new(&value);
+    if playlist::Format::from_path(path).is_some() && !value.contains("://") {
+        return if path.is_file() {
+            Ok(())
+        } else {
+            Err(format!("no such playlist file '{}'", value))
+        };
+    }
+
+    is_url(value.clone())
+        .map_err(|_| format!("'{}' isn't a SoundCloud url or playlist file", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<clap::ArgMatches<'static>, clap::Error> {
+        app().get_matches_from_safe(std::iter::once("scli").chain(args.iter().cloned()))
+    }
+
+    #[test]
+    fn test_play() {
+        let url = "https://soundcloud.com/trippycode/art-of-minimal";
+        let matches = parse(&["--volume", "50", "--shuffle", url]).unwrap();
+        assert_eq!(matches.subcommand_name(), None);
+        assert_eq!(matches.value_of("volume"), Some("50"));
+        assert!(matches.is_present("shuffle"));
+        assert_eq!(
+            matches.values_of("sources").unwrap().collect::<Vec<_>>(),
+            vec![url]
+        );
+
+        let matches = parse(&["play", "--from", "43:10", "--speed", "1.5", url, url]).unwrap();
+        let play = matches.subcommand_matches("play").unwrap();
+        assert_eq!(play.value_of("from"), Some("43:10"));
+        assert_eq!(play.value_of("speed"), Some("1.5"));
+        assert_eq!(play.values_of("sources").unwrap().count(), 2);
+
+        // global flags before or after the subcommand
+        let matches = parse(&["--volume", "30", "play", url]).unwrap();
+        let play = matches.subcommand_matches("play").unwrap();
+        assert_eq!(play.value_of("volume"), Some("30"));
+        let matches = parse(&["play", url, "--theme", "blue"]).unwrap();
+        let play = matches.subcommand_matches("play").unwrap();
+        assert_eq!(play.value_of("theme"), Some("blue"));
+        let matches = parse(&["--no-tui", url]).unwrap();
+        assert!(matches.is_present("no-tui"));
+        assert!(parse(&["--audio", "wav:mix.wav", url]).is_ok());
+        assert!(parse(&["--audio", "flac", url]).is_err());
+        let matches = parse(&["play", url, "--device", "USB Audio"]).unwrap();
+        let play = matches.subcommand_matches("play").unwrap();
+        assert_eq!(play.value_of("device"), Some("USB Audio"));
+
+        // a track called "search" is still a track once urls have started
+        assert!(parse(&[url, "search"]).is_err());
+    }
+
+    #[test]
+    fn test_invalid() {
+        // no arguments shows the help
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["--volume", "101", "https://soundcloud.com/a/b"]).is_err());
+        assert!(parse(&["--theme", "nope", "https://soundcloud.com/a/b"]).is_err());
+        assert!(parse(&["--from", "later", "https://soundcloud.com/a/b"]).is_err());
+        assert!(parse(&["--speed", "4", "https://soundcloud.com/a/b"]).is_err());
+        assert!(parse(&["https://example.com/a/b"]).is_err());
+        assert!(parse(&["soundcloud.com/a/b"]).is_err());
+        assert!(parse(&["missing.m3u8"]).is_err());
+        assert!(parse(&["info", "https://soundcloudxcom/a"]).is_err());
+        assert!(parse(&["export", "queue", "q.m3u8"]).is_err());
+    }
+
+    #[test]
+    fn test_subcommands() {
+        let matches = parse(&["search", "minimal", "techno", "--limit", "5"]).unwrap();
+        let search = matches.subcommand_matches("search").unwrap();
+        assert_eq!(
+            search.values_of("query").unwrap().collect::<Vec<_>>(),
+            vec!["minimal", "techno"]
+        );
+        assert_eq!(search.value_of("limit"), Some("5"));
+
+        let matches = parse(&["ctl", "seek", "1:00"]).unwrap();
+        let ctl = matches.subcommand_matches("ctl").unwrap();
+        assert_eq!(ctl.value_of("value"), Some("1:00"));
+        assert!(parse(&["ctl", "volume"]).is_err());
+        assert!(parse(&["ctl", "speed"]).is_err());
+        assert!(parse(&["ctl", "status", "--json"]).is_ok());
+
+        let matches = parse(&["history"]).unwrap();
+        assert_eq!(matches.subcommand_name(), Some("history"));
+        let matches = parse(&["devices"]).unwrap();
+        assert_eq!(matches.subcommand_name(), Some("devices"));
+
+        let matches = parse(&["download", "https://on.soundcloud.com/x", "-o", "x.mp3"]).unwrap();
+        let download = matches.subcommand_matches("download").unwrap();
+        assert_eq!(download.value_of("output"), Some("x.mp3"));
+
+        let matches = parse(&["record", "https://soundcloud.com/a/b", "-o", "mix.flac"]).unwrap();
+        let record = matches.subcommand_matches("record").unwrap();
+        assert_eq!(record.value_of("output"), Some("mix.flac"));
+        assert!(parse(&["record", "https://soundcloud.com/a/b", "-o", "mix.ogg"]).is_err());
+    }
+
+    #[test]
+    fn test_config() {
+        let url = "https://soundcloud.com/a/b";
+        let matches = parse(&["--config", "x.toml", url]).unwrap();
+        assert_eq!(config(&matches), Some("x.toml"));
+        let matches = parse(&["--config", "x.toml", "search", "foo"]).unwrap();
+        assert_eq!(config(&matches), Some("x.toml"));
+        let matches = parse(&["search", "foo", "--config", "x.toml"]).unwrap();
+        assert_eq!(config(&matches), Some("x.toml"));
+        let matches = parse(&["play", url, "--config", "x.toml"]).unwrap();
+        assert_eq!(config(&matches), Some("x.toml"));
+        let matches = parse(&["search", "foo"]).unwrap();
+        assert_eq!(config(&matches), None);
+    }
+}
@@ -0,0 +1,245 @@
+// Subcommands that print something and exit, rather than starting the player
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::bookmarks;
+use crate::control;
+use crate::history;
+use crate::info;
+use crate::output;
+use crate::playlist;
+use crate::record;
+use crate::soundcloud;
+use crate::status::Clock;
+use crate::stretch;
+
+/// Search SoundCloud, listing tracks that can be passed straight back to scli.
+pub fn search(client: &soundcloud::Client, query: &str, limit: u32) -> Result<(), failure::Error> {
+    let tracks = client.search(query, limit)?;
+    if tracks.is_empty() {
+        eprintln!("nothing found for '{}'", query);
+    }
+    list(&tracks);
+    Ok(())
+}
+
+/// List a user's tracks, by name or profile url.
+pub fn user(client: &soundcloud::Client, user: &str) -> Result<(), failure::Error> {
+    let url = if user.contains("://") {
+        user.to_string()
+    } else {
+        format!("https://soundcloud.com/{}", user)
+    };
+    list(&client.tracks(url)?);
+    Ok(())
+}
+
+/// Save a track's audio, as SoundCloud streams it.
+pub fn download(
+    client: &soundcloud::Client,
+    url: &str,
+    output: Option<&str>,
+) -> Result<(), failure::Error> {
+    let track = single(client, url)?;
+    if track.stream_url.is_empty() {
+        failure::bail!("{} can't be streamed", url);
+    }
+
+    let path = match output {
+        Some(output) => PathBuf::from(output),
+        None => PathBuf::from(record::filename(&track, "mp3")),
+    };
+    let mut stream = client.stream(&track.stream_url, 0)?;
+    let mut file = File::create(&path)?;
+    io::copy(&mut stream, &mut file)?;
+
+    println!("{}", path.display());
+    Ok(())
+}
+
+/// Save a track to a file, printing how far it's got on stderr.
+pub fn record(
+    client: &soundcloud::Client,
+    url: &str,
+    output: Option<&str>,
+) -> Result<(), failure::Error> {
+    let track = single(client, url)?;
+    if track.stream_url.is_empty() {
+        failure::bail!("{} can't be streamed", url);
+    }
+
+    let path = match output {
+        Some(output) => PathBuf::from(output),
+        None => PathBuf::from(record::filename(&track, "flac")),
+    };
+    let total = Clock::format(track.duration);
+    record::track(client, &track, &path, |done| {
+        eprint!("\r{} / {}", Clock::format(done), total);
+    })?;
+    eprintln!();
+
+    println!("{}", path.display());
+    Ok(())
+}
+
+/// Print a track's details.
+pub fn info(client: &soundcloud::Client, url: &str) -> Result<(), failure::Error> {
+    let track = single(client, url)?;
+    println!("{}", track.title);
+    println!("{}", track.user.username);
+    println!();
+    println!("{:<9}{}", "Length", Clock::format(track.duration));
+    for (name, value) in info::fields(&track) {
+        println!("{:<9}{}", name, value);
+    }
+    if let Some(ref description) = track.description {
+        if !description.trim().is_empty() {
+            println!("\n{}", description.trim());
+        }
+    }
+
+    Ok(())
+}
+
+/// Send a command to a running player, printing the status it replies with.
+pub fn ctl(command: &str, value: Option<&str>, json: bool) -> Result<(), failure::Error> {
+    let value = value.unwrap_or("");
+    let command = match command {
+        "play" => control::Command::Play,
+        "pause" => control::Command::Pause,
+        "toggle" => control::Command::Toggle,
+        "seek" => control::Command::Seek {
+            position: Clock::parse(value)
+                .ok_or_else(|| failure::format_err!("'{}' isn't a time, use mm:ss", value))?,
+        },
+        "volume" => control::Command::Volume {
+            volume: value
+                .parse()
+                .ok()
+                .filter(|&volume| volume <= 100)
+                .ok_or_else(|| failure::format_err!("volume must be between 0 and 100"))?,
+        },
+        "speed" => control::Command::Speed {
+            speed: value
+                .parse()
+                .ok()
+                .filter(|speed| (stretch::MIN_SPEED..=stretch::MAX_SPEED).contains(speed))
+                .ok_or_else(|| failure::format_err!("speed must be between 0.5 and 3"))?,
+        },
+        "next" => control::Command::Next,
+        "enqueue" => control::Command::Enqueue {
+            url: value.to_string(),
+        },
+        _ => control::Command::Status,
+    };
+    let show = command == control::Command::Status;
+
+    let reply = control::send(control::path(), &command)?;
+    if let Some(error) = reply.error {
+        failure::bail!(error);
+    }
+    if let (true, Some(status)) = (show, reply.status) {
+        if json {
+            println!("{}", serde_json::to_string(&status)?);
+        } else {
+            println!(
+                "{} {} - {}  {} / {}  {}%",
+                if status.playing { "Playing" } else { "Paused" },
+                status.username,
+                status.title,
+                Clock::format(status.position_ms),
+                Clock::format(status.duration_ms),
+                status.volume
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// List the listening history, oldest first, keeping entries matching every term.
+pub fn history(terms: &[&str]) -> Result<(), failure::Error> {
+    let entries = history::History::open().entries()?;
+    for entry in entries
+        .iter()
+        .filter(|entry| terms.iter().all(|term| entry.matches(term)))
+    {
+        println!("{}  {}", entry.summary(), entry.url);
+    }
+
+    Ok(())
+}
+
+/// List output devices, marking the default one.
+pub fn devices() -> Result<(), failure::Error> {
+    let default = output::default_device();
+    let devices = output::devices()?;
+    if devices.is_empty() {
+        failure::bail!("no audio output devices found");
+    }
+
+    for device in devices {
+        let mark = if Some(&device) == default.as_ref() {
+            "*"
+        } else {
+            " "
+        };
+        println!("{} {}", mark, device);
+    }
+
+    Ok(())
+}
+
+/// List bookmarks, oldest first, keeping those matching every term.
+pub fn bookmarks(terms: &[&str]) -> Result<(), failure::Error> {
+    let bookmarks = bookmarks::Bookmarks::load()?;
+    for bookmark in bookmarks
+        .all()
+        .iter()
+        .filter(|bookmark| terms.iter().all(|term| bookmark.matches(term)))
+    {
+        println!("{}  {}", bookmark.summary(), bookmark.url);
+    }
+
+    Ok(())
+}
+
+/// Write the history or bookmarks to a playlist file.
+pub fn export(what: &str, path: &Path) -> Result<(), failure::Error> {
+    let entries: Vec<playlist::Entry> = match what {
+        "history" => history::History::open()
+            .entries()?
+            .iter()
+            .map(playlist::Entry::from)
+            .collect(),
+        _ => bookmarks::Bookmarks::load()?
+            .all()
+            .iter()
+            .map(playlist::Entry::from)
+            .collect(),
+    };
+
+    playlist::export(path, &entries)
+}
+
+// `username - title  length  url`, one per line
+fn list(tracks: &[soundcloud::Track]) {
+    for track in tracks {
+        println!(
+            "{} - {}  {}  {}",
+            track.user.username,
+            track.title,
+            Clock::format(track.duration),
+            track.permalink_url
+        );
+    }
+}
+
+fn single(client: &soundcloud::Client, url: &str) -> Result<soundcloud::Track, failure::Error> {
+    let mut tracks = client.tracks(url.to_string())?;
+    match tracks.len() {
+        1 => Ok(tracks.remove(0)),
+        _ => failure::bail!("{} isn't a single track", url),
+    }
+}
@@ -0,0 +1,224 @@
+use std::mem;
+use std::sync::mpsc;
+use std::thread;
+
+use tui::buffer::Buffer;
+use tui::layout::Rect;
+use tui::style::{Color, Modifier, Style};
+use tui::widgets::Widget;
+
+use crate::soundcloud;
+use crate::status::Clock;
+
+/// A track's timed comments, sorted by timestamp. They're fetched on a
+/// background thread, since a long mix can have many pages of them.
+pub struct Comments {
+    rx: mpsc::Receiver<Vec<soundcloud::Comment>>,
+    comments: Vec<soundcloud::Comment>,
+}
+
+impl Comments {
+    pub fn fetch(track_id: u64) -> Comments {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let client = soundcloud::Client::new();
+            if let Ok(comments) = client.comments(track_id) {
+                let _ = tx.send(comments);
+            }
+        });
+
+        Comments {
+            rx,
+            comments: Vec::new(),
+        }
+    }
+
+    /// Pick up the comments, once they've been fetched.
+    pub fn poll(&mut self) {
+        if let Ok(mut comments) = self.rx.try_recv() {
+            comments.retain(|c| c.timestamp.is_some());
+            comments.sort_by_key(|c| c.timestamp);
+
+            // anything posted while fetching, unless the fetch already has it
+            let posted = mem::replace(&mut self.comments, comments);
+            for comment in posted {
+                if !self.comments.iter().any(|c| same(c, &comment)) {
+                    self.add(comment);
+                }
+            }
+        }
+    }
+
+    /// Add a comment (e.g. one just posted), keeping them sorted.
+    pub fn add(&mut self, comment: soundcloud::Comment) {
+        if comment.timestamp.is_none() {
+            return;
+        }
+
+        let index = match self
+            .comments
+            .binary_search_by_key(&comment.timestamp, |c| c.timestamp)
+        {
+            Ok(i) | Err(i) => i,
+        };
+        self.comments.insert(index, comment);
+    }
+
+    /// Comment positions, as a percentage of the track duration.
+    pub fn markers(&self, duration_ms: u32) -> Vec<f32> {
+        if duration_ms == 0 {
+            return Vec::new();
+        }
+
+        self.comments
+            .iter()
+            .filter_map(|c| c.timestamp)
+            .map(|ts| ts as f32 / duration_ms as f32 * 100.0)
+            .collect()
+    }
+
+    pub fn nearest(&self, elapsed_ms: u32) -> Option<&soundcloud::Comment> {
+        nearest(&self.comments, elapsed_ms)
+    }
+}
+
+// Comments have no id until they're fetched, so a posted one is matched by what it says
+fn same(a: &soundcloud::Comment, b: &soundcloud::Comment) -> bool {
+    a.timestamp == b.timestamp && a.body == b.body && a.user.username == b.user.username
+}
+
+// Comment with the timestamp closest to `at`, comments must be sorted
+fn nearest(comments: &[soundcloud::Comment], at: u32) -> Option<&soundcloud::Comment> {
+    let timestamp = |c: &soundcloud::Comment| c.timestamp.unwrap_or(0);
+    let index = match comments.binary_search_by_key(&at, timestamp) {
+        Ok(i) => return comments.get(i),
+        Err(i) => i,
+    };
+
+    // closest is either side of where `at` would be inserted
+    let before = index.checked_sub(1).and_then(|i| comments.get(i));
+    let after = comments.get(index);
+    match (before, after) {
+        (Some(b), Some(a)) => {
+            if at - timestamp(b) <= timestamp(a) - at {
+                Some(b)
+            } else {
+                Some(a)
+            }
+        }
+        (b, a) => b.or(a),
+    }
+}
+
+/// Single line showing a comment: `[12:34] username: body`
+pub struct Ticker {
+    pub timestamp: u32,
+    pub username: String,
+    pub body: String,
+}
+
+impl Default for Ticker {
+    fn default() -> Ticker {
+        Ticker {
+            timestamp: 0,
+            username: String::new(),
+            body: String::new(),
+        }
+    }
+}
+
+impl Ticker {
+    pub fn comment(&mut self, comment: &soundcloud::Comment) -> &mut Ticker {
+        self.timestamp = comment.timestamp.unwrap_or(0);
+        self.username = comment.user.username.clone();
+        // comments can span lines, the ticker can't
+        self.body = comment
+            .body
+            .split_whitespace()
+            .collect::<Vec<&str>>()
+            .join(" ");
+        self
+    }
+}
+
+impl Widget for Ticker {
+    fn draw(&mut self, area: Rect, buf: &mut Buffer) {
+        if area.area() == 0 || self.username.is_empty() {
+            return;
+        }
+
+        let segments = [
+            (
+                format!("[{}] ", Clock::format(self.timestamp)),
+                Style::default().fg(Color::DarkGray),
+            ),
+            (
+                format!("{}: ", self.username),
+                Style::default().modifier(Modifier::BOLD),
+            ),
+            (self.body.clone(), Style::default()),
+        ];
+
+        let mut x = area.left();
+        for (text, style) in segments.iter() {
+            if x >= area.right() {
+                return;
+            }
+
+            let width = (area.right() - x) as usize;
+            buf.set_stringn(x, area.top(), text, width, *style);
+            x += text.chars().count().min(width) as u16;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comment(timestamp: u32) -> soundcloud::Comment {
+        soundcloud::Comment {
+            body: format!("at {}", timestamp),
+            timestamp: Some(timestamp),
+            user: soundcloud::User::default(),
+        }
+    }
+
+    #[test]
+    fn test_nearest() {
+        let comments: Vec<soundcloud::Comment> =
+            [1000, 5000, 9000].iter().map(|&ts| comment(ts)).collect();
+        let at = |ms| nearest(&comments, ms).map(|c| c.timestamp.unwrap());
+
+        assert_eq!(at(0), Some(1000));
+        assert_eq!(at(1000), Some(1000));
+        assert_eq!(at(2999), Some(1000));
+        assert_eq!(at(3000), Some(1000));
+        assert_eq!(at(3001), Some(5000));
+        assert_eq!(at(8000), Some(9000));
+        assert_eq!(at(100000), Some(9000));
+        assert!(nearest(&[], 1000).is_none());
+    }
+
+    #[test]
+    fn test_posted_while_fetching() {
+        let (tx, rx) = mpsc::channel();
+        let mut comments = Comments {
+            rx,
+            comments: Vec::new(),
+        };
+        comments.add(comment(5000));
+        comments.add(comment(7000));
+
+        // the fetch has one of them already, the other is kept as well
+        tx.send(vec![comment(9000), comment(1000), comment(7000)])
+            .unwrap();
+        comments.poll();
+        let timestamps: Vec<u32> = comments
+            .comments
+            .iter()
+            .map(|c| c.timestamp.unwrap())
+            .collect();
+        assert_eq!(timestamps, vec![1000, 5000, 7000, 9000]);
+    }
+}
@@ -0,0 +1,85 @@
+// User configuration, read from $XDG_CONFIG_HOME/scli/config.toml
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// SoundCloud OAuth access token, needed to like, repost and comment
+    pub token: Option<String>,
+    /// Key binding overrides, action name to key name
+    pub keys: HashMap<String, String>,
+    /// Colour theme, see `theme::Theme::names`
+    pub theme: Option<String>,
+    /// Output device, see `scli devices`
+    pub device: Option<String>,
+    /// Address to serve the HTTP remote on, off when unset
+    pub remote: Option<String>,
+    /// What the HTTP remote's clients send as `Authorization: Bearer <token>`
+    pub remote_token: Option<String>,
+    /// What's playing, for status bars
+    pub now_playing: NowPlaying,
+    /// Desktop notifications when the next track starts
+    pub notifications: bool,
+    /// Where to scrobble to, nowhere by default
+    pub scrobble: Scrobble,
+}
+
+/// Where to tell status bars like tmux, polybar or i3blocks what's playing.
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct NowPlaying {
+    /// File kept up to date with the now playing line
+    pub file: Option<PathBuf>,
+    /// Shell command run whenever the track or its state changes
+    pub command: Option<String>,
+    /// The line's template, see `nowplaying::render`
+    pub format: Option<String>,
+}
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Scrobble {
+    pub lastfm: Option<LastFm>,
+    pub listenbrainz: Option<ListenBrainz>,
+}
+
+/// An API account and a session key for it,
+/// see https://www.last.fm/api/authentication
+#[derive(Clone, Deserialize)]
+pub struct LastFm {
+    pub api_key: String,
+    pub secret: String,
+    pub session_key: String,
+    /// API root, when it isn't Last.fm's own
+    pub endpoint: Option<String>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct ListenBrainz {
+    /// User token, from https://listenbrainz.org/profile/
+    pub token: String,
+    /// API root, when it isn't ListenBrainz's own
+    pub endpoint: Option<String>,
+}
+
+impl Config {
+    /// Load the config file, falling back to defaults when there isn't one.
+    pub fn load() -> Result<Config, failure::Error> {
+        match path() {
+            Some(path) if path.exists() => Config::from_file(&path),
+            _ => Ok(Config::default()),
+        }
+    }
+
+    pub fn from_file(path: &PathBuf) -> Result<Config, failure::Error> {
+        let contents = fs::read_to_string(path)?;
+        toml::from_str(&contents)
+            .map_err(|e| failure::format_err!("invalid config {}: {}", path.display(), e))
+    }
+}
+
+fn path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("scli").join("config.toml"))
+}
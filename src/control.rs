@@ -0,0 +1,265 @@
+// Control a running player from scripts, window manager media keys and so on:
+// one JSON command per line over a Unix socket, one JSON reply per line back
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::thread;
+
+use termion::event::Key;
+
+use crate::event::{Event, Events};
+use crate::queue::Repeat;
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "command", rename_all = "lowercase")]
+pub enum Command {
+    Play,
+    Pause,
+    Toggle,
+    /// Position to seek to, in ms
+    Seek {
+        position: u32,
+    },
+    Volume {
+        volume: u8,
+    },
+    /// Playback speed, from 0.5 to 3
+    Speed {
+        speed: f32,
+    },
+    Next,
+    /// Play a track, playlist or user's tracks now
+    Open {
+        url: String,
+    },
+    /// Add a track, playlist or user's tracks to the end of the queue
+    Enqueue {
+        url: String,
+    },
+    Status,
+    Shuffle {
+        shuffle: bool,
+    },
+    Repeat {
+        repeat: Repeat,
+    },
+    /// Anything a key can do, by its name in the config file
+    Action {
+        action: String,
+    },
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Reply {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<Status>,
+}
+
+impl Reply {
+    pub fn ok() -> Reply {
+        Reply {
+            ok: true,
+            ..Reply::default()
+        }
+    }
+
+    pub fn error(error: String) -> Reply {
+        Reply {
+            ok: false,
+            error: Some(error),
+            status: None,
+        }
+    }
+}
+
+/// What's playing, as reported by the status command.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Status {
+    pub track_id: u64,
+    pub title: String,
+    pub username: String,
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artwork_url: Option<String>,
+    pub playing: bool,
+    /// "playing", "paused" or "stopped"
+    pub state: String,
+    pub position_ms: u32,
+    pub duration_ms: u32,
+    pub volume: u8,
+    pub speed: f32,
+    pub queue: usize,
+    pub shuffle: bool,
+    pub repeat: Repeat,
+}
+
+/// A command, and where to send the reply to it.
+pub struct Request {
+    pub command: Command,
+    pub reply: mpsc::Sender<Reply>,
+}
+
+/// The listening socket, removed again once dropped.
+pub struct Server {
+    path: PathBuf,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Where the socket lives, in the runtime directory when there is one.
+pub fn path() -> PathBuf {
+    match dirs::runtime_dir() {
+        Some(dir) => dir.join("scli.sock"),
+        None => {
+            let user = env::var("USER").unwrap_or_default();
+            env::temp_dir().join(format!("scli-{}.sock", user))
+        }
+    }
+}
+
+/// Listen at the usual path, unless another player already is, in which
+/// case scripts keep talking to that one.
+pub fn start(events: &Events) -> Option<Server> {
+    listen(path(), events.sender()).ok()
+}
+
+/// Listen for commands, passing them on as events next to key presses.
+pub fn listen(path: PathBuf, events: mpsc::Sender<Event<Key>>) -> Result<Server, failure::Error> {
+    if path.exists() {
+        // left behind by a crash, unless another player is still using it
+        if UnixStream::connect(&path).is_ok() {
+            failure::bail!("scli is already listening on {}", path.display());
+        }
+        fs::remove_file(&path)?;
+    }
+
+    let listener = UnixListener::bind(&path)?;
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            if let Ok(stream) = stream {
+                let events = events.clone();
+                thread::spawn(move || serve(stream, events));
+            }
+        }
+    });
+
+    Ok(Server { path })
+}
+
+// Answer commands on a connection until it closes
+fn serve(stream: UnixStream, events: mpsc::Sender<Event<Key>>) {
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(_) => return,
+    };
+
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => return,
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let reply = match serde_json::from_str(&line) {
+            Ok(command) => {
+                let (tx, rx) = mpsc::channel();
+                let request = Request { command, reply: tx };
+                if events.send(Event::Control(request)).is_err() {
+                    return;
+                }
+                rx.recv()
+                    .unwrap_or_else(|_| Reply::error("player stopped".to_string()))
+            }
+            Err(e) => Reply::error(format!("invalid command: {}", e)),
+        };
+
+        let json = serde_json::to_string(&reply).unwrap_or_default();
+        if writeln!(writer, "{}", json).is_err() {
+            return;
+        }
+    }
+}
+
+/// Send a command to a running player and wait for the reply.
+pub fn send(path: PathBuf, command: &Command) -> Result<Reply, failure::Error> {
+    let mut stream = UnixStream::connect(&path)
+        .map_err(|e| failure::format_err!("no player running at {}: {}", path.display(), e))?;
+    writeln!(stream, "{}", serde_json::to_string(command)?)?;
+
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    Ok(serde_json::from_str(&line)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    #[test]
+    fn test_commands() {
+        let parse = |json: &str| serde_json::from_str::<Command>(json).ok();
+        assert_eq!(parse(r#"{"command": "toggle"}"#), Some(Command::Toggle));
+        assert_eq!(
+            parse(r#"{"command": "seek", "position": 60000}"#),
+            Some(Command::Seek { position: 60000 })
+        );
+        assert_eq!(
+            serde_json::to_string(&Command::Volume { volume: 50 }).unwrap(),
+            r#"{"command":"volume","volume":50}"#
+        );
+        assert_eq!(parse(r#"{"command": "rewind"}"#), None);
+        assert_eq!(parse(r#"{"command": "volume"}"#), None);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let path = env::temp_dir().join(format!("scli-control-{}.sock", process::id()));
+        let (tx, rx) = mpsc::channel();
+        let server = listen(path.clone(), tx).unwrap();
+
+        // stands in for the player's event loop
+        thread::spawn(move || {
+            for event in rx {
+                if let Event::Control(request) = event {
+                    let reply = match request.command {
+                        Command::Status => Reply {
+                            status: Some(Status {
+                                title: String::from("Art of Minimal Techno"),
+                                ..Status::default()
+                            }),
+                            ..Reply::ok()
+                        },
+                        _ => Reply::error(String::from("nope")),
+                    };
+                    request.reply.send(reply).unwrap();
+                }
+            }
+        });
+
+        let reply = send(path.clone(), &Command::Status).unwrap();
+        assert!(reply.ok);
+        assert_eq!(reply.status.unwrap().title, "Art of Minimal Techno");
+        let reply = send(path.clone(), &Command::Next).unwrap();
+        assert_eq!(reply.error, Some(String::from("nope")));
+
+        // only one player at a time
+        assert!(listen(path.clone(), mpsc::channel().0).is_err());
+
+        drop(server);
+        assert!(!path.exists());
+        assert!(send(path, &Command::Status).is_err());
+    }
+}
@@ -30,6 +30,28 @@ where
             current_frame_offset: 0,
         })
     }
+
+    /// Decode and throw away whole frames until `ms` in, for a stream that
+    /// starts earlier than it's wanted from.
+    pub fn seek(&mut self, ms: u32) {
+        let mut skipped_us = 0u64;
+        while skipped_us < ms as u64 * 1000 {
+            let frame = &self.current_frame;
+            let frames = (frame.data.len() / frame.channels.max(1)) as u64;
+            skipped_us += frames * 1_000_000 / frame.sample_rate.max(1) as u64;
+            match self.decoder.next_frame() {
+                Ok(frame) => {
+                    self.current_frame = frame;
+                    self.current_frame_offset = 0;
+                }
+                Err(_) => {
+                    // the end, nothing left to play
+                    self.current_frame_offset = self.current_frame.data.len();
+                    return;
+                }
+            }
+        }
+    }
 }
 
 impl<R> Source for Mp3Decoder<R>
@@ -4,32 +4,41 @@ use std::sync::mpsc;
 use std::thread;
 use std::time::Duration;
 
+use signal_hook::iterator::Signals;
 use termion::event::Key;
 use termion::input::TermRead;
 
+use crate::control;
+
 pub enum Event<I> {
     Input(I),
     Tick,
+    Resize,
+    // SIGINT or SIGTERM, time to save and quit
+    Interrupt,
+    // a command from the control socket
+    Control(control::Request),
 }
 
-/// A small event handler that wrap termion input and tick events. Each event
-/// type is handled in its own thread and returned to a common `Receiver`
+/// A small event handler that wrap termion input, tick, resize and interrupt events. Each
+/// event type is handled in its own thread and returned to a common `Receiver`
 pub struct Events {
     rx: mpsc::Receiver<Event<Key>>,
+    tx: mpsc::Sender<Event<Key>>,
     input_handle: thread::JoinHandle<()>,
     tick_handle: thread::JoinHandle<()>,
+    resize_handle: thread::JoinHandle<()>,
+    interrupt_handle: thread::JoinHandle<()>,
 }
 
 #[derive(Debug, Clone, Copy)]
 pub struct Config {
-    pub exit_key: Key,
     pub tick_rate: Duration,
 }
 
 impl Default for Config {
     fn default() -> Config {
         Config {
-            exit_key: Key::Char('q'),
             tick_rate: Duration::from_millis(250),
         }
     }
@@ -47,14 +56,13 @@ impl Events {
             thread::spawn(move || {
                 let stdin = io::stdin();
                 for evt in stdin.keys() {
+                    // keep reading until the receiver is gone, the quit key
+                    // can't stop input since it may be typed in a prompt
                     match evt {
                         Ok(key) => {
                             if let Err(_) = tx.send(Event::Input(key)) {
                                 return;
                             }
-                            if key == config.exit_key {
-                                return;
-                            }
                         }
                         Err(_) => {}
                     }
@@ -71,13 +79,49 @@ impl Events {
                 }
             })
         };
+        let resize_handle = {
+            let tx = tx.clone();
+            thread::spawn(move || {
+                let signals = match Signals::new(&[signal_hook::SIGWINCH]) {
+                    Ok(signals) => signals,
+                    Err(_) => return,
+                };
+                for _ in signals.forever() {
+                    if let Err(_) = tx.send(Event::Resize) {
+                        return;
+                    }
+                }
+            })
+        };
+        let interrupt_handle = {
+            let tx = tx.clone();
+            thread::spawn(move || {
+                let signals = match Signals::new(&[signal_hook::SIGINT, signal_hook::SIGTERM]) {
+                    Ok(signals) => signals,
+                    Err(_) => return,
+                };
+                for _ in signals.forever() {
+                    if let Err(_) = tx.send(Event::Interrupt) {
+                        return;
+                    }
+                }
+            })
+        };
         Events {
             rx,
+            tx,
             input_handle,
             tick_handle,
+            resize_handle,
+            interrupt_handle,
         }
     }
 
+    /// For sending events from elsewhere, e.g. the control socket.
+    pub fn sender(&self) -> mpsc::Sender<Event<Key>> {
+        self.tx.clone()
+    }
+
     pub fn next(&self) -> Result<Event<Key>, mpsc::RecvError> {
         self.rx.recv()
     }
@@ -0,0 +1,458 @@
+// Just enough of a FLAC encoder to keep a recording losslessly, at about
+// half the size of a WAV: fixed blocks, fixed predictors and Rice coded
+// residuals, no stereo decorrelation or LPC
+// https://xiph.org/flac/format.html
+use std::io::{self, Seek, SeekFrom, Write};
+
+// samples per channel in each frame
+const BLOCK_SIZE: usize = 4096;
+
+const VENDOR: &str = concat!("scli ", env!("CARGO_PKG_VERSION"));
+
+// the largest Rice parameter with 4 bit parameters, 15 is an escape code
+const MAX_RICE: u32 = 14;
+
+pub struct Encoder<W>
+where
+    W: Write + Seek,
+{
+    writer: W,
+    // where STREAMINFO starts, to fill in once the length is known
+    streaminfo: u64,
+    channels: u16,
+    sample_rate: u32,
+    // interleaved samples waiting for a full block
+    pending: Vec<i16>,
+    frames: u64,
+    samples: u64,
+    frame_sizes: Option<(u32, u32)>,
+    md5: md5::Context,
+}
+
+impl<W> Encoder<W>
+where
+    W: Write + Seek,
+{
+    /// Start a stream of 16 bit samples, with `tags` as its Vorbis comments,
+    /// e.g. `("TITLE", "Art of Minimal Techno")`.
+    pub fn new(
+        mut writer: W,
+        channels: u16,
+        sample_rate: u32,
+        tags: &[(&str, &str)],
+    ) -> io::Result<Encoder<W>> {
+        if channels == 0 || channels > 8 {
+            return Err(invalid(format!("{} channels", channels)));
+        }
+        if sample_rate == 0 || sample_rate >= 1 << 20 {
+            return Err(invalid(format!("a sample rate of {}", sample_rate)));
+        }
+
+        let streaminfo = writer.seek(SeekFrom::Current(0))? + 8;
+        let mut encoder = Encoder {
+            writer,
+            streaminfo,
+            channels,
+            sample_rate,
+            pending: Vec::with_capacity(BLOCK_SIZE * channels as usize),
+            frames: 0,
+            samples: 0,
+            frame_sizes: None,
+            md5: md5::Context::new(),
+        };
+
+        encoder.writer.write_all(b"fLaC")?;
+        encoder.writer.write_all(&block_header(false, 0, 34))?;
+        let streaminfo = encoder.streaminfo_block();
+        encoder.writer.write_all(&streaminfo)?;
+        let comments = comments(tags);
+        encoder
+            .writer
+            .write_all(&block_header(true, 4, comments.len()))?;
+        encoder.writer.write_all(&comments)?;
+
+        Ok(encoder)
+    }
+
+    /// Add interleaved samples.
+    pub fn write(&mut self, samples: &[i16]) -> io::Result<()> {
+        let block = BLOCK_SIZE * self.channels as usize;
+        for chunk in samples.chunks(block) {
+            let wanted = block - self.pending.len();
+            let (now, later) = chunk.split_at(chunk.len().min(wanted));
+            self.pending.extend_from_slice(now);
+            if self.pending.len() == block {
+                self.flush()?;
+            }
+            self.pending.extend_from_slice(later);
+        }
+        Ok(())
+    }
+
+    /// Write what's left, and go back to fill in the length and checksum.
+    pub fn finish(mut self) -> io::Result<W> {
+        // a partial frame of whole samples, anything after those is dropped
+        let channels = self.channels as usize;
+        self.pending
+            .truncate(self.pending.len() / channels * channels);
+        if !self.pending.is_empty() {
+            self.flush()?;
+        }
+
+        self.writer.flush()?;
+        let end = self.writer.seek(SeekFrom::Current(0))?;
+        self.writer.seek(SeekFrom::Start(self.streaminfo))?;
+        let streaminfo = self.streaminfo_block();
+        self.writer.write_all(&streaminfo)?;
+        self.writer.seek(SeekFrom::Start(end))?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    // Encode the pending samples as one frame
+    fn flush(&mut self) -> io::Result<()> {
+        let channels = self.channels as usize;
+        let len = self.pending.len() / channels;
+
+        let mut bits = Bits::default();
+        bits.write(0xfff8, 16);
+        let (size_code, size_bits) = match len {
+            BLOCK_SIZE => (12, 0),
+            _ => (7, 16),
+        };
+        // the sample rate's in STREAMINFO, 16 bit samples
+        bits.write(size_code << 4, 8);
+        bits.write((channels as u64 - 1) << 4 | 0b100 << 1, 8);
+        bits.bytes.extend(utf8(self.frames));
+        if size_bits > 0 {
+            bits.write(len as u64 - 1, size_bits);
+        }
+        let crc = crc8(&bits.bytes);
+        bits.write(crc as u64, 8);
+
+        let mut channel = Vec::with_capacity(len);
+        for c in 0..channels {
+            channel.clear();
+            channel.extend(
+                self.pending
+                    .iter()
+                    .skip(c)
+                    .step_by(channels)
+                    .map(|&s| s as i32),
+            );
+            subframe(&mut bits, &channel);
+        }
+        bits.align();
+        let crc = crc16(&bits.bytes);
+        bits.write(crc as u64, 16);
+
+        let size = bits.bytes.len() as u32;
+        self.frame_sizes = Some(match self.frame_sizes {
+            Some((min, max)) => (min.min(size), max.max(size)),
+            None => (size, size),
+        });
+        self.writer.write_all(&bits.bytes)?;
+
+        // the checksum's of the samples as little endian bytes, interleaved
+        let bytes: Vec<u8> = self
+            .pending
+            .iter()
+            .flat_map(|s| s.to_le_bytes().to_vec())
+            .collect();
+        self.md5.consume(&bytes);
+        self.samples += len as u64;
+        self.frames += 1;
+        self.pending.clear();
+        Ok(())
+    }
+
+    fn streaminfo_block(&self) -> Vec<u8> {
+        let mut bits = Bits::default();
+        bits.write(BLOCK_SIZE as u64, 16);
+        bits.write(BLOCK_SIZE as u64, 16);
+        let (min, max) = self.frame_sizes.unwrap_or((0, 0));
+        bits.write(min as u64, 24);
+        bits.write(max as u64, 24);
+        bits.write(self.sample_rate as u64, 20);
+        bits.write(self.channels as u64 - 1, 3);
+        bits.write(15, 5);
+        bits.write(self.samples, 36);
+        bits.bytes.extend_from_slice(&self.md5.clone().compute().0);
+        bits.bytes
+    }
+}
+
+// Bits, most significant first
+#[derive(Default)]
+struct Bits {
+    bytes: Vec<u8>,
+    current: u8,
+    used: u32,
+}
+
+impl Bits {
+    fn write(&mut self, value: u64, bits: u32) {
+        for i in (0..bits).rev() {
+            self.bit((value >> i) & 1 == 1);
+        }
+    }
+
+    fn bit(&mut self, bit: bool) {
+        self.current = self.current << 1 | bit as u8;
+        self.used += 1;
+        if self.used == 8 {
+            self.bytes.push(self.current);
+            self.current = 0;
+            self.used = 0;
+        }
+    }
+
+    // `n` zeros and a one
+    fn unary(&mut self, n: u32) {
+        for _ in 0..n {
+            self.bit(false);
+        }
+        self.bit(true);
+    }
+
+    fn align(&mut self) {
+        while self.used != 0 {
+            self.bit(false);
+        }
+    }
+}
+
+// Whichever of a constant, a fixed predictor or the samples as they are is smallest
+fn subframe(bits: &mut Bits, samples: &[i32]) {
+    if samples.iter().all(|&s| s == samples[0]) {
+        bits.write(0, 8);
+        bits.write(samples[0] as u64 & 0xffff, 16);
+        return;
+    }
+
+    let verbatim = samples.len() as u64 * 16;
+    let best = (0..=4.min(samples.len() - 1))
+        .map(|order| {
+            let residual = residual(samples, order);
+            let (k, cost) = rice(&residual);
+            (order, residual, k, order as u64 * 16 + 6 + 4 + cost)
+        })
+        .min_by_key(|&(_, _, _, cost)| cost);
+
+    match best {
+        Some((order, residual, k, cost)) if cost < verbatim => {
+            bits.write((0b001000 | order as u64) << 1, 8);
+            for &sample in &samples[..order] {
+                bits.write(sample as u64 & 0xffff, 16);
+            }
+            // Rice coded with 4 bit parameters, in a single partition
+            bits.write(0, 2);
+            bits.write(0, 4);
+            bits.write(k as u64, 4);
+            for &r in &residual {
+                let u = zigzag(r);
+                bits.unary(u >> k);
+                bits.write(u as u64, k);
+            }
+        }
+        _ => {
+            bits.write(0b000001 << 1, 8);
+            for &sample in samples {
+                bits.write(sample as u64 & 0xffff, 16);
+            }
+        }
+    }
+}
+
+// What's left after predicting each sample from the `order` before it
+fn residual(samples: &[i32], order: usize) -> Vec<i32> {
+    (order..samples.len())
+        .map(|i| {
+            let s = |back: usize| samples[i - back];
+            match order {
+                0 => s(0),
+                1 => s(0) - s(1),
+                2 => s(0) - 2 * s(1) + s(2),
+                3 => s(0) - 3 * s(1) + 3 * s(2) - s(3),
+                _ => s(0) - 4 * s(1) + 6 * s(2) - 4 * s(3) + s(4),
+            }
+        })
+        .collect()
+}
+
+// The Rice parameter coding `residual` in the fewest bits, and how many
+fn rice(residual: &[i32]) -> (u32, u64) {
+    (0..=MAX_RICE)
+        .map(|k| {
+            let bits: u64 = residual
+                .iter()
+                .map(|&r| (zigzag(r) >> k) as u64 + 1 + k as u64)
+                .sum();
+            (k, bits)
+        })
+        .min_by_key(|&(_, bits)| bits)
+        .unwrap()
+}
+
+// 0, -1, 1, -2, 2... as 0, 1, 2, 3, 4...
+fn zigzag(n: i32) -> u32 {
+    ((n << 1) ^ (n >> 31)) as u32
+}
+
+fn block_header(last: bool, kind: u8, len: usize) -> [u8; 4] {
+    [
+        (last as u8) << 7 | kind,
+        (len >> 16) as u8,
+        (len >> 8) as u8,
+        len as u8,
+    ]
+}
+
+// VORBIS_COMMENT, the one part of FLAC that's little endian
+fn comments(tags: &[(&str, &str)]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&(VENDOR.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(VENDOR.as_bytes());
+    bytes.extend_from_slice(&(tags.len() as u32).to_le_bytes());
+    for (name, value) in tags {
+        let comment = format!("{}={}", name, value);
+        bytes.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(comment.as_bytes());
+    }
+    bytes
+}
+
+// Frame numbers are coded like UTF-8, extended to 36 bits
+fn utf8(n: u64) -> Vec<u8> {
+    if n < 0x80 {
+        return vec![n as u8];
+    }
+
+    let mut len = 2;
+    while n >= 1 << (5 * len + 1) {
+        len += 1;
+    }
+    let mut bytes = vec![(0xff00 >> len) as u8 | (n >> (6 * (len - 1))) as u8];
+    for i in (0..len - 1).rev() {
+        bytes.push(0x80 | (n >> (6 * i)) as u8 & 0x3f);
+    }
+    bytes
+}
+
+fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |crc, &byte| {
+        (0..8).fold(crc ^ byte, |crc, _| {
+            if crc & 0x80 != 0 {
+                crc << 1 ^ 0x07
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0, |crc, &byte| {
+        (0..8).fold(crc ^ (byte as u16) << 8, |crc, _| {
+            if crc & 0x8000 != 0 {
+                crc << 1 ^ 0x8005
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+fn invalid(what: String) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("FLAC can't hold {}", what),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn encode(samples: &[i16], channels: u16) -> Vec<u8> {
+        let mut encoder = Encoder::new(
+            Cursor::new(Vec::new()),
+            channels,
+            44100,
+            &[("TITLE", "Art of Minimal Techno")],
+        )
+        .unwrap();
+        // in uneven pieces, as they come off the decoder
+        for chunk in samples.chunks(1151) {
+            encoder.write(chunk).unwrap();
+        }
+        encoder.finish().unwrap().into_inner()
+    }
+
+    fn decode(bytes: Vec<u8>) -> (claxon::metadata::StreamInfo, Vec<i16>) {
+        let mut reader = claxon::FlacReader::new(Cursor::new(bytes)).unwrap();
+        let samples = reader
+            .samples()
+            .map(|sample| sample.unwrap() as i16)
+            .collect();
+        (reader.streaminfo(), samples)
+    }
+
+    #[test]
+    fn test_utf8() {
+        assert_eq!(utf8(0x41), vec![0x41]);
+        assert_eq!("é".as_bytes(), &utf8(0xe9)[..]);
+        assert_eq!("€".as_bytes(), &utf8(0x20ac)[..]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        // a tone, silence, noise and a partial block at the end
+        let mut samples = Vec::new();
+        for i in 0..10000 {
+            let tone = ((i as f32 / 20.0).sin() * 12000.0) as i16;
+            samples.push(tone);
+            samples.push(-tone / 2);
+        }
+        samples.extend(vec![0; 9000]);
+        let mut seed = 7u32;
+        for _ in 0..5000 {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            samples.push((seed >> 16) as i16);
+        }
+        samples.push(i16::max_value());
+        samples.push(i16::min_value());
+        let bytes = encode(&samples, 2);
+        let size = bytes.len();
+
+        let (info, decoded) = decode(bytes);
+        assert_eq!(info.channels, 2);
+        assert_eq!(info.sample_rate, 44100);
+        assert_eq!(info.bits_per_sample, 16);
+        assert_eq!(info.samples, Some(samples.len() as u64 / 2));
+        assert_eq!(decoded, samples);
+        let bytes: Vec<u8> = samples
+            .iter()
+            .flat_map(|s| s.to_le_bytes().to_vec())
+            .collect();
+        assert_eq!(info.md5sum, md5::compute(bytes).0);
+        assert!(size < samples.len() * 2);
+    }
+
+    #[test]
+    fn test_tags() {
+        let reader = claxon::FlacReader::new(Cursor::new(encode(&[1, 2, 3], 1))).unwrap();
+        assert_eq!(
+            reader.get_tag("TITLE").next(),
+            Some("Art of Minimal Techno")
+        );
+        assert_eq!(reader.vendor(), Some(VENDOR));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(Encoder::new(Cursor::new(Vec::new()), 0, 44100, &[]).is_err());
+        assert!(Encoder::new(Cursor::new(Vec::new()), 2, 0, &[]).is_err());
+    }
+}
@@ -0,0 +1,252 @@
+// Playback without the full screen interface, for scripts and ssh: a single
+// progress line on stderr, and the simpler keys read from stdin
+use std::io::{self, Write};
+
+use termion::event::Key;
+use termion::raw::IntoRawMode;
+
+use crate::app::App;
+use crate::event::{Event, Events};
+use crate::keys::Action;
+use crate::services::{Services, Setup};
+use crate::soundcloud;
+use crate::status::Clock;
+
+/// Exit status after Ctrl-C or a signal, as shells report SIGINT.
+pub const INTERRUPTED: i32 = 130;
+
+/// Exit status when tracks failed to load and none played.
+pub const FAILED: i32 = 1;
+
+// actions that make sense without a screen, the rest open panes
+// and prompts nobody would see
+const ACTIONS: [Action; 13] = [
+    Action::PlayPause,
+    Action::Quit,
+    Action::VolumeUp,
+    Action::VolumeDown,
+    Action::Slower,
+    Action::Faster,
+    Action::Like,
+    Action::Repost,
+    Action::Autoplay,
+    Action::Shuffle,
+    Action::Repeat,
+    Action::Bookmark,
+    Action::BookmarkTrack,
+];
+
+/// Play until the queue runs out or quit is pressed, returning the exit status.
+pub fn run(app: &mut App, setup: Setup, quiet: bool) -> Result<i32, failure::Error> {
+    // keys as they're pressed, when there's a terminal to press them in,
+    // otherwise whatever is piped in
+    let raw = if termion::is_tty(&io::stdin()) {
+        Some(termion::get_tty()?.into_raw_mode()?)
+    } else {
+        None
+    };
+    let mut progress = Progress {
+        quiet,
+        tty: termion::is_tty(&io::stderr()),
+        newline: if raw.is_some() { "\r\n" } else { "\n" },
+        track_id: None,
+        message: None,
+    };
+    let events = Events::new();
+    let mut services = Services::start(&events, setup);
+
+    let status = loop {
+        match events.next()? {
+            Event::Tick => {
+                if !app.tick() {
+                    break 0;
+                }
+            }
+            Event::Input(Key::Ctrl('c')) | Event::Interrupt => break INTERRUPTED,
+            Event::Input(key) => {
+                if accepts(app, key) && !app.input(key) {
+                    break 0;
+                }
+            }
+            Event::Resize => {}
+            Event::Control(request) => {
+                let _ = request.reply.send(app.control(request.command));
+            }
+        }
+
+        services.publish(app);
+        progress.show(app)?;
+    };
+
+    progress.clear()?;
+    app.quit()?;
+    Ok(exit_status(status, app.played(), app.failed()))
+}
+
+// Nothing heard because every track failed to load is a failure to scripts,
+// however the player came to stop
+fn exit_status(status: i32, played: usize, failed: usize) -> i32 {
+    if status == 0 && played == 0 && failed > 0 {
+        FAILED
+    } else {
+        status
+    }
+}
+
+// Whether a key does anything without a screen
+fn accepts(app: &App, key: Key) -> bool {
+    let action = app.action(key);
+    if app.screen().resume.is_some() {
+        return match key {
+            Key::Char('y') | Key::Char('n') | Key::Char('\n') | Key::Esc => true,
+            _ => action == Some(Action::Quit),
+        };
+    }
+
+    action.map_or(false, |action| ACTIONS.contains(&action))
+}
+
+struct Progress {
+    quiet: bool,
+    // redraw the line in place, rather than a line per track
+    tty: bool,
+    // raw mode needs carriage returns spelled out
+    newline: &'static str,
+    track_id: Option<u64>,
+    message: Option<String>,
+}
+
+impl Progress {
+    fn show(&mut self, app: &App) -> io::Result<()> {
+        if self.quiet {
+            return Ok(());
+        }
+
+        let screen = app.screen();
+        let mut stderr = io::stderr();
+        self.clear()?;
+
+        // new tracks and messages get lines of their own, so they can be scrolled back to
+        if self.track_id != Some(screen.track.id) {
+            self.track_id = Some(screen.track.id);
+            if !self.tty {
+                let track = screen.track;
+                write!(
+                    stderr,
+                    "Playing {} - {}  {}{}",
+                    track.user.username, track.title, track.permalink_url, self.newline
+                )?;
+            }
+        }
+        let message = screen.message.map(String::from);
+        if message != self.message {
+            if let Some(ref message) = message {
+                write!(stderr, "{}{}", message, self.newline)?;
+            }
+            self.message = message;
+        }
+
+        if self.tty {
+            let text = match screen.resume {
+                Some(position) => format!("Resume from {}? y/n", Clock::format(position)),
+                None => line(
+                    screen.track,
+                    screen.is_playing,
+                    screen.elapsed_ms,
+                    screen.volume,
+                    screen.speed,
+                ),
+            };
+            let width = termion::terminal_size().map(|size| size.0).unwrap_or(80);
+            write!(stderr, "{}", fit(&text, width as usize))?;
+        }
+
+        stderr.flush()
+    }
+
+    // Rub out the progress line, so anything printed next starts clean
+    fn clear(&self) -> io::Result<()> {
+        if self.quiet || !self.tty {
+            return Ok(());
+        }
+
+        write!(io::stderr(), "\r{}", termion::clear::CurrentLine)
+    }
+}
+
+// e.g. `> trippycode - Art of Minimal Techno  30:00 / 1:08:32  1.5x  100%`,
+// the speed only when it isn't 1x
+fn line(
+    track: &soundcloud::Track,
+    is_playing: bool,
+    elapsed_ms: u32,
+    volume: u8,
+    speed: f32,
+) -> String {
+    let speed = if (speed - 1.0).abs() > std::f32::EPSILON {
+        format!("{}x  ", speed)
+    } else {
+        String::new()
+    };
+    format!(
+        "{} {} - {}  {} / {}  {}{}%",
+        if is_playing { ">" } else { "||" },
+        track.user.username,
+        track.title,
+        Clock::format(elapsed_ms),
+        Clock::format(track.duration),
+        speed,
+        volume
+    )
+}
+
+// Cut a line short of the terminal width, a wrapped line can't be redrawn in place
+fn fit(text: &str, width: usize) -> String {
+    if text.chars().count() < width {
+        return text.to_string();
+    }
+
+    let mut fitted: String = text.chars().take(width.saturating_sub(2)).collect();
+    fitted.push('~');
+    fitted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line() {
+        let track = soundcloud::Track {
+            duration: 4112738,
+            title: String::from("Art of Minimal Techno"),
+            user: soundcloud::User {
+                username: String::from("trippycode"),
+                ..soundcloud::User::default()
+            },
+            ..soundcloud::Track::default()
+        };
+        assert_eq!(
+            line(&track, true, 1000 * 60 * 30, 100, 1.0),
+            "> trippycode - Art of Minimal Techno  30:00 / 01:08:32  100%"
+        );
+        assert!(line(&track, false, 0, 50, 1.0).starts_with("|| trippycode"));
+        assert!(line(&track, true, 0, 50, 1.5).ends_with("00:00 / 01:08:32  1.5x  50%"));
+    }
+
+    #[test]
+    fn test_exit_status() {
+        assert_eq!(exit_status(0, 3, 0), 0);
+        // some played, some didn't
+        assert_eq!(exit_status(0, 1, 2), 0);
+        assert_eq!(exit_status(0, 0, 2), FAILED);
+        assert_eq!(exit_status(INTERRUPTED, 0, 2), INTERRUPTED);
+    }
+
+    #[test]
+    fn test_fit() {
+        assert_eq!(fit("short", 80), "short");
+        assert_eq!(fit("exactly", 7), "exact~");
+        assert_eq!(fit("anything", 0), "~");
+    }
+}
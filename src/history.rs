@@ -0,0 +1,158 @@
+// Listening history, one JSON object per line in
+// $XDG_DATA_HOME/scli/history.jsonl, oldest first
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+use chrono::{DateTime, Local};
+
+use crate::soundcloud;
+use crate::status::Clock;
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Entry {
+    pub track_id: u64,
+    pub url: String,
+    pub title: String,
+    pub username: String,
+    pub started_at: DateTime<Local>,
+    /// How long the track was actually played for, in ms
+    pub listened_ms: u32,
+}
+
+impl Entry {
+    pub fn new(track: &soundcloud::Track, started_at: DateTime<Local>, listened_ms: u32) -> Entry {
+        Entry {
+            track_id: track.id,
+            url: track.permalink_url.clone(),
+            title: track.title.clone(),
+            username: track.user.username.clone(),
+            started_at,
+            listened_ms,
+        }
+    }
+
+    /// Single line summary, e.g. for listing the history.
+    pub fn summary(&self) -> String {
+        format!(
+            "{}  {:>8}  {} - {}",
+            self.started_at.format("%Y-%m-%d %H:%M"),
+            Clock::format(self.listened_ms),
+            self.username,
+            self.title
+        )
+    }
+
+    /// Whether the title, uploader or url contain `term`, ignoring case.
+    pub fn matches(&self, term: &str) -> bool {
+        let term = term.to_lowercase();
+        [&self.title, &self.username, &self.url]
+            .iter()
+            .any(|field| field.to_lowercase().contains(&term))
+    }
+}
+
+pub struct History {
+    path: Option<PathBuf>,
+}
+
+impl History {
+    pub fn open() -> History {
+        match dirs::data_dir() {
+            Some(dir) => History::at(dir.join("scli").join("history.jsonl")),
+            None => History { path: None },
+        }
+    }
+
+    pub fn at(path: PathBuf) -> History {
+        History { path: Some(path) }
+    }
+
+    pub fn append(&self, entry: &Entry) -> Result<(), failure::Error> {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{}", serde_json::to_string(entry)?)?;
+        Ok(())
+    }
+
+    /// Every entry, oldest first. Lines that can't be read (e.g. cut
+    /// short by a crash) are skipped rather than losing the rest.
+    pub fn entries(&self) -> Result<Vec<Entry>, failure::Error> {
+        let path = match self.path {
+            Some(ref path) if path.exists() => path,
+            _ => return Ok(Vec::new()),
+        };
+
+        let contents = fs::read_to_string(path)?;
+        Ok(contents
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    fn track(id: u64, title: &str) -> soundcloud::Track {
+        soundcloud::Track {
+            id,
+            title: title.to_string(),
+            permalink_url: format!("https://soundcloud.com/trippycode/{}", id),
+            user: soundcloud::User {
+                username: String::from("trippycode"),
+                ..soundcloud::User::default()
+            },
+            ..soundcloud::Track::default()
+        }
+    }
+
+    #[test]
+    fn test_append_entries() {
+        let dir = env::temp_dir().join(format!("scli-history-{}", process::id()));
+        let path = dir.join("history.jsonl");
+        let history = History::at(path.clone());
+        assert!(history.entries().unwrap().is_empty());
+
+        let now = Local::now();
+        history
+            .append(&Entry::new(&track(1, "Art of Minimal Techno"), now, 60000))
+            .unwrap();
+        history
+            .append(&Entry::new(&track(2, "Tripping the Mad Doctor"), now, 5000))
+            .unwrap();
+
+        // a line cut short doesn't lose the rest
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        write!(file, "{{\"track_id\": 3, \"ur").unwrap();
+
+        let entries = history.entries().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].track_id, 1);
+        assert_eq!(entries[1].listened_ms, 5000);
+        assert_eq!(entries[1].started_at, now);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_matches() {
+        let entry = Entry::new(&track(1, "Art of Minimal Techno"), Local::now(), 0);
+        assert!(entry.matches("minimal"));
+        assert!(entry.matches("TRIPPY"));
+        assert!(!entry.matches("house"));
+        assert!(entry
+            .summary()
+            .ends_with("00:00  trippycode - Art of Minimal Techno"));
+    }
+}
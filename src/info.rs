@@ -0,0 +1,131 @@
+use tui::buffer::Buffer;
+use tui::layout::Rect;
+use tui::style::{Color, Modifier, Style};
+use tui::widgets::{Paragraph, Text, Widget};
+
+use crate::artwork;
+use crate::soundcloud;
+
+/// Track details pane, artwork on the left and metadata on the right.
+pub struct Info<'a> {
+    pub track: &'a soundcloud::Track,
+    pub artwork: Option<artwork::Artwork>,
+}
+
+impl<'a> Info<'a> {
+    pub fn new(track: &'a soundcloud::Track) -> Info<'a> {
+        Info {
+            track,
+            artwork: None,
+        }
+    }
+
+    pub fn artwork(&mut self, artwork: Option<artwork::Artwork>) -> &mut Info<'a> {
+        self.artwork = artwork;
+        self
+    }
+}
+
+impl<'a> Widget for Info<'a> {
+    fn draw(&mut self, area: Rect, buf: &mut Buffer) {
+        if area.area() == 0 {
+            return;
+        }
+
+        // artwork takes up to half the width, as long as there's a
+        // reasonable amount of room left for the details
+        let mut details = area;
+        if let Some(ref mut artwork) = self.artwork {
+            let cover = artwork::Artwork::fit(Rect::new(
+                area.left(),
+                area.top(),
+                area.width / 2,
+                area.height,
+            ));
+            if cover.height >= 4 {
+                artwork.draw(cover, buf);
+                details = Rect::new(
+                    cover.right() + 2,
+                    area.top(),
+                    area.width - cover.width - 2,
+                    area.height,
+                );
+            }
+        }
+
+        let track = self.track;
+        let label = Style::default().fg(Color::DarkGray);
+        let bold = Style::default().modifier(Modifier::BOLD);
+
+        let mut text = vec![
+            Text::styled(format!("{}\n", track.title), bold),
+            Text::raw(format!("{}\n\n", track.user.username)),
+        ];
+
+        for (name, value) in fields(track) {
+            text.push(Text::styled(format!("{:<9}", name), label));
+            text.push(Text::raw(format!("{}\n", value)));
+        }
+
+        if let Some(ref description) = track.description {
+            if !description.trim().is_empty() {
+                text.push(Text::raw(format!("\n{}", description.trim())));
+            }
+        }
+
+        Paragraph::new(text.iter()).wrap(true).draw(details, buf);
+    }
+}
+
+/// Named details of a track, leaving out any that are empty.
+pub fn fields(track: &soundcloud::Track) -> Vec<(&'static str, String)> {
+    let stats = [
+        (track.playback_count, "plays"),
+        (track.likes_count, "likes"),
+        (track.reposts_count, "reposts"),
+        (track.comment_count, "comments"),
+    ]
+    .iter()
+    .filter_map(|&(count, name)| count.map(|c| format!("{} {}", thousands(c), name)))
+    .collect::<Vec<String>>()
+    .join("  ");
+
+    vec![
+        ("Genre", track.genre.clone()),
+        ("Tags", track.tags().join(", ")),
+        ("Uploaded", track.created_at.clone()),
+        ("License", track.license.clone()),
+        ("Link", track.permalink_url.clone()),
+        ("Stats", stats),
+    ]
+    .into_iter()
+    .filter(|field| !field.1.is_empty())
+    .collect()
+}
+
+// Format a count with thousands separators, e.g. 1234567 -> 1,234,567
+fn thousands(value: u32) -> String {
+    let digits = value.to_string();
+    let mut formatted = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            formatted.push(',');
+        }
+        formatted.push(c);
+    }
+
+    formatted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thousands() {
+        assert_eq!(thousands(0), "0");
+        assert_eq!(thousands(999), "999");
+        assert_eq!(thousands(1000), "1,000");
+        assert_eq!(thousands(1234567), "1,234,567");
+    }
+}
@@ -0,0 +1,330 @@
+// Key bindings for every player action, with defaults that can
+// be overridden from the `[keys]` table of the config file
+use std::collections::HashMap;
+
+use termion::event::Key;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Action {
+    PlayPause,
+    Quit,
+    VolumeUp,
+    VolumeDown,
+    Slower,
+    Faster,
+    View,
+    Info,
+    Like,
+    Repost,
+    Comment,
+    Autoplay,
+    Shuffle,
+    Repeat,
+    History,
+    Bookmark,
+    BookmarkTrack,
+    Library,
+    Export,
+    Devices,
+    Record,
+    Help,
+}
+
+impl Action {
+    /// Every action, in the order they're listed in the help.
+    pub fn all() -> &'static [Action] {
+        &[
+            Action::PlayPause,
+            Action::Quit,
+            Action::VolumeUp,
+            Action::VolumeDown,
+            Action::Slower,
+            Action::Faster,
+            Action::View,
+            Action::Info,
+            Action::Like,
+            Action::Repost,
+            Action::Comment,
+            Action::Autoplay,
+            Action::Shuffle,
+            Action::Repeat,
+            Action::History,
+            Action::Bookmark,
+            Action::BookmarkTrack,
+            Action::Library,
+            Action::Export,
+            Action::Devices,
+            Action::Record,
+            Action::Help,
+        ]
+    }
+
+    /// Name used for the action in the config file.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::PlayPause => "play_pause",
+            Action::Quit => "quit",
+            Action::VolumeUp => "volume_up",
+            Action::VolumeDown => "volume_down",
+            Action::Slower => "slower",
+            Action::Faster => "faster",
+            Action::View => "view",
+            Action::Info => "info",
+            Action::Like => "like",
+            Action::Repost => "repost",
+            Action::Comment => "comment",
+            Action::Autoplay => "autoplay",
+            Action::Shuffle => "shuffle",
+            Action::Repeat => "repeat",
+            Action::History => "history",
+            Action::Bookmark => "bookmark",
+            Action::BookmarkTrack => "bookmark_track",
+            Action::Library => "library",
+            Action::Export => "export",
+            Action::Devices => "devices",
+            Action::Record => "record",
+            Action::Help => "help",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::PlayPause => "Play/Pause",
+            Action::Quit => "Quit",
+            Action::VolumeUp => "Volume up",
+            Action::VolumeDown => "Volume down",
+            Action::Slower => "Slower",
+            Action::Faster => "Faster",
+            Action::View => "Waveform/spectrum view",
+            Action::Info => "Track details",
+            Action::Like => "Like/unlike",
+            Action::Repost => "Repost",
+            Action::Comment => "Comment at current position",
+            Action::Autoplay => "Autoplay related tracks",
+            Action::Shuffle => "Shuffle on/off",
+            Action::Repeat => "Repeat off/all/one",
+            Action::History => "Listening history",
+            Action::Bookmark => "Bookmark at current position",
+            Action::BookmarkTrack => "Bookmark track",
+            Action::Library => "Bookmarks",
+            Action::Export => "Export queue",
+            Action::Devices => "Output device",
+            Action::Record => "Start/stop recording",
+            Action::Help => "Show/hide help",
+        }
+    }
+
+    fn default_key(self) -> Key {
+        match self {
+            Action::PlayPause => Key::Char(' '),
+            Action::Quit => Key::Char('q'),
+            Action::VolumeUp => Key::Up,
+            Action::VolumeDown => Key::Down,
+            Action::Slower => Key::Char('['),
+            Action::Faster => Key::Char(']'),
+            Action::View => Key::Char('v'),
+            Action::Info => Key::Char('i'),
+            Action::Like => Key::Char('l'),
+            Action::Repost => Key::Char('R'),
+            Action::Comment => Key::Char('c'),
+            Action::Autoplay => Key::Char('a'),
+            Action::Shuffle => Key::Char('s'),
+            Action::Repeat => Key::Char('r'),
+            Action::History => Key::Char('h'),
+            Action::Bookmark => Key::Char('b'),
+            Action::BookmarkTrack => Key::Char('B'),
+            Action::Library => Key::Char('L'),
+            Action::Export => Key::Char('e'),
+            Action::Devices => Key::Char('o'),
+            Action::Record => Key::Char('w'),
+            Action::Help => Key::Char('?'),
+        }
+    }
+}
+
+pub struct Bindings {
+    keys: Vec<(Action, Key)>,
+}
+
+impl Default for Bindings {
+    fn default() -> Bindings {
+        Bindings {
+            keys: Action::all()
+                .iter()
+                .map(|&action| (action, action.default_key()))
+                .collect(),
+        }
+    }
+}
+
+impl Bindings {
+    /// Default bindings, overridden by `action name -> key` pairs.
+    pub fn with_overrides(overrides: &HashMap<String, String>) -> Result<Bindings, failure::Error> {
+        let mut bindings = Bindings::default();
+        for (name, value) in overrides {
+            let action = Action::all()
+                .iter()
+                .find(|action| action.name() == name)
+                .ok_or_else(|| failure::format_err!("unknown action '{}' in [keys]", name))?;
+            let key = parse(value)
+                .ok_or_else(|| failure::format_err!("unknown key '{}' for '{}'", value, name))?;
+
+            for binding in bindings.keys.iter_mut() {
+                if binding.0 == *action {
+                    binding.1 = key;
+                }
+            }
+        }
+
+        // two actions on one key would make one of them unreachable
+        for (i, &(action, key)) in bindings.keys.iter().enumerate() {
+            if let Some(&(other, _)) = bindings.keys[i + 1..].iter().find(|b| b.1 == key) {
+                failure::bail!(
+                    "'{}' is bound to both '{}' and '{}'",
+                    describe(key),
+                    action.name(),
+                    other.name()
+                );
+            }
+        }
+
+        Ok(bindings)
+    }
+
+    pub fn action(&self, key: Key) -> Option<Action> {
+        self.keys.iter().find(|b| b.1 == key).map(|b| b.0)
+    }
+
+    /// Key and description of every action, for display.
+    pub fn help(&self) -> Vec<(String, String)> {
+        self.keys
+            .iter()
+            .map(|&(action, key)| (describe(key), action.description().to_string()))
+            .collect()
+    }
+}
+
+/// Parse a key name as written in the config file,
+/// e.g. "q", "space", "up", "ctrl-p" or "f5".
+pub fn parse(value: &str) -> Option<Key> {
+    let lower = value.to_lowercase();
+    let key = match lower.as_str() {
+        "space" => Key::Char(' '),
+        "enter" => Key::Char('\n'),
+        "tab" => Key::Char('\t'),
+        "esc" => Key::Esc,
+        "backspace" => Key::Backspace,
+        "delete" => Key::Delete,
+        "insert" => Key::Insert,
+        "up" => Key::Up,
+        "down" => Key::Down,
+        "left" => Key::Left,
+        "right" => Key::Right,
+        "home" => Key::Home,
+        "end" => Key::End,
+        "pageup" => Key::PageUp,
+        "pagedown" => Key::PageDown,
+        _ => {
+            if lower.starts_with("ctrl-") {
+                return single(&value[5..]).map(Key::Ctrl);
+            }
+            if lower.starts_with("alt-") {
+                return single(&value[4..]).map(Key::Alt);
+            }
+            if lower.starts_with('f') && lower.len() > 1 {
+                if let Ok(n) = lower[1..].parse::<u8>() {
+                    return Some(Key::F(n));
+                }
+            }
+            return single(value).map(Key::Char);
+        }
+    };
+
+    Some(key)
+}
+
+fn single(value: &str) -> Option<char> {
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+/// Display name of a key, the inverse of `parse`.
+pub fn describe(key: Key) -> String {
+    match key {
+        Key::Char(' ') => "space".to_string(),
+        Key::Char('\n') => "enter".to_string(),
+        Key::Char('\t') => "tab".to_string(),
+        Key::Char(c) => c.to_string(),
+        Key::Ctrl(c) => format!("ctrl-{}", c),
+        Key::Alt(c) => format!("alt-{}", c),
+        Key::F(n) => format!("f{}", n),
+        Key::Esc => "esc".to_string(),
+        Key::Backspace => "backspace".to_string(),
+        Key::Delete => "delete".to_string(),
+        Key::Insert => "insert".to_string(),
+        Key::Up => "up".to_string(),
+        Key::Down => "down".to_string(),
+        Key::Left => "left".to_string(),
+        Key::Right => "right".to_string(),
+        Key::Home => "home".to_string(),
+        Key::End => "end".to_string(),
+        Key::PageUp => "pageup".to_string(),
+        Key::PageDown => "pagedown".to_string(),
+        _ => "?".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_describe() {
+        let keys = [
+            Key::Char('q'),
+            Key::Char(' '),
+            Key::Char('?'),
+            Key::Up,
+            Key::Esc,
+            Key::Ctrl('p'),
+            Key::Alt('x'),
+            Key::F(5),
+        ];
+        for &key in keys.iter() {
+            assert_eq!(parse(&describe(key)), Some(key));
+        }
+
+        assert_eq!(parse("Space"), Some(Key::Char(' ')));
+        assert_eq!(parse("F"), Some(Key::Char('F')));
+        assert_eq!(parse("nope"), None);
+        assert_eq!(parse(""), None);
+    }
+
+    #[test]
+    fn test_overrides() {
+        let mut overrides = HashMap::new();
+        overrides.insert("play_pause".to_string(), "p".to_string());
+        let bindings = Bindings::with_overrides(&overrides).unwrap();
+
+        assert_eq!(bindings.action(Key::Char('p')), Some(Action::PlayPause));
+        assert_eq!(bindings.action(Key::Char(' ')), None);
+        assert_eq!(bindings.action(Key::Char('q')), Some(Action::Quit));
+        assert!(bindings
+            .help()
+            .contains(&("p".to_string(), "Play/Pause".to_string())));
+    }
+
+    #[test]
+    fn test_overrides_invalid() {
+        let mut overrides = HashMap::new();
+        overrides.insert("dance".to_string(), "d".to_string());
+        assert!(Bindings::with_overrides(&overrides).is_err());
+
+        let mut overrides = HashMap::new();
+        overrides.insert("quit".to_string(), "space".to_string());
+        assert!(Bindings::with_overrides(&overrides).is_err());
+    }
+}
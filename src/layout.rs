@@ -0,0 +1,202 @@
+// Screen layout, chosen from the terminal size so that small terminals
+// still get a usable (and panic free) player
+use tui::layout::{Margin, Rect};
+
+// smallest terminal the normal layout fits in: margins, header, wave, status
+// and comment ticker, with a blank line before the ticker
+const NORMAL_WIDTH: u16 = 40;
+const NORMAL_HEIGHT: u16 = 2 + 3 + 10 + 1 + 2 + 2;
+
+// terminals at least this big get a taller waveform
+const EXPANDED_WIDTH: u16 = 100;
+const EXPANDED_HEIGHT: u16 = 30;
+
+const MARGIN: u16 = 2;
+const HEADER_HEIGHT: u16 = 3;
+const WAVE_HEIGHT: u16 = 10;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Mode {
+    // no margins, single line header
+    Compact,
+    // fixed size header, waveform and status
+    Normal,
+    // waveform grows to fill the screen
+    Expanded,
+}
+
+impl Mode {
+    pub fn from_size(size: Rect) -> Mode {
+        if size.width < NORMAL_WIDTH || size.height < NORMAL_HEIGHT {
+            Mode::Compact
+        } else if size.width >= EXPANDED_WIDTH && size.height >= EXPANDED_HEIGHT {
+            Mode::Expanded
+        } else {
+            Mode::Normal
+        }
+    }
+}
+
+/// Where each part of the player is drawn. Any of these can be empty
+/// (zero sized) when the terminal is too small to show it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Areas {
+    pub mode: Mode,
+    pub header: Rect,
+    pub wave: Rect,
+    pub status: Rect,
+    pub ticker: Rect,
+}
+
+pub fn split(size: Rect) -> Areas {
+    let mode = Mode::from_size(size);
+    match mode {
+        Mode::Compact => compact(size),
+        Mode::Normal => {
+            let inner = size.inner(&Margin {
+                vertical: MARGIN,
+                horizontal: MARGIN,
+            });
+            let header = row(inner, inner.top(), HEADER_HEIGHT);
+            let wave = row(inner, header.bottom(), WAVE_HEIGHT);
+            let status = row(inner, wave.bottom(), 1);
+            let ticker = row(inner, status.bottom() + 1, 1);
+
+            Areas {
+                mode,
+                header,
+                wave,
+                status,
+                ticker,
+            }
+        }
+        Mode::Expanded => {
+            let inner = size.inner(&Margin {
+                vertical: MARGIN,
+                horizontal: MARGIN,
+            });
+            let header = row(inner, inner.top(), HEADER_HEIGHT);
+            let ticker = row(inner, inner.bottom() - 1, 1);
+            let status = row(inner, ticker.top() - 2, 1);
+
+            // leave a blank line between the header, waveform and status
+            let wave_top = header.bottom() + 1;
+            let wave = row(inner, wave_top, status.top() - 1 - wave_top);
+
+            Areas {
+                mode,
+                header,
+                wave,
+                status,
+                ticker,
+            }
+        }
+    }
+}
+
+// Status gets the bottom line first, then the header the top line, the
+// ticker the line above status, and the waveform whatever is left in between
+fn compact(size: Rect) -> Areas {
+    let empty = Rect::new(size.left(), size.top(), 0, 0);
+    let mut areas = Areas {
+        mode: Mode::Compact,
+        header: empty,
+        wave: empty,
+        status: empty,
+        ticker: empty,
+    };
+
+    if size.width == 0 || size.height == 0 {
+        return areas;
+    }
+
+    areas.status = row(size, size.bottom() - 1, 1);
+    if size.height >= 2 {
+        areas.header = row(size, size.top(), 1);
+    }
+    if size.height >= 4 {
+        areas.ticker = row(size, size.bottom() - 2, 1);
+        areas.wave = row(size, size.top() + 1, size.height - 3);
+    } else if size.height == 3 {
+        areas.wave = row(size, size.top() + 1, 1);
+    }
+
+    areas
+}
+
+// A full width row of `area`, clipped to fit inside it
+fn row(area: Rect, y: u16, height: u16) -> Rect {
+    let y = y.max(area.top()).min(area.bottom());
+    let height = height.min(area.bottom() - y);
+    Rect::new(area.left(), y, area.width, height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contains(outer: Rect, inner: Rect) -> bool {
+        inner.area() == 0
+            || (inner.left() >= outer.left()
+                && inner.right() <= outer.right()
+                && inner.top() >= outer.top()
+                && inner.bottom() <= outer.bottom())
+    }
+
+    #[test]
+    fn test_mode() {
+        assert_eq!(Mode::from_size(Rect::new(0, 0, 0, 0)), Mode::Compact);
+        assert_eq!(Mode::from_size(Rect::new(0, 0, 30, 40)), Mode::Compact);
+        assert_eq!(Mode::from_size(Rect::new(0, 0, 80, 12)), Mode::Compact);
+        assert_eq!(Mode::from_size(Rect::new(0, 0, 80, 24)), Mode::Normal);
+        assert_eq!(Mode::from_size(Rect::new(0, 0, 200, 24)), Mode::Normal);
+        assert_eq!(Mode::from_size(Rect::new(0, 0, 120, 40)), Mode::Expanded);
+    }
+
+    #[test]
+    fn test_split_normal() {
+        let areas = split(Rect::new(0, 0, 80, 24));
+        assert_eq!(areas.header, Rect::new(2, 2, 76, 3));
+        assert_eq!(areas.wave, Rect::new(2, 5, 76, 10));
+        assert_eq!(areas.status, Rect::new(2, 15, 76, 1));
+        assert_eq!(areas.ticker, Rect::new(2, 17, 76, 1));
+    }
+
+    #[test]
+    fn test_split_expanded() {
+        let areas = split(Rect::new(0, 0, 120, 40));
+        assert_eq!(areas.header, Rect::new(2, 2, 116, 3));
+        assert_eq!(areas.wave, Rect::new(2, 6, 116, 28));
+        assert_eq!(areas.status, Rect::new(2, 35, 116, 1));
+        assert_eq!(areas.ticker, Rect::new(2, 37, 116, 1));
+    }
+
+    #[test]
+    fn test_split_compact() {
+        let areas = split(Rect::new(0, 0, 20, 1));
+        assert_eq!(areas.status, Rect::new(0, 0, 20, 1));
+        assert_eq!(areas.header.area(), 0);
+        assert_eq!(areas.wave.area(), 0);
+        assert_eq!(areas.ticker.area(), 0);
+
+        let areas = split(Rect::new(0, 0, 30, 8));
+        assert_eq!(areas.header, Rect::new(0, 0, 30, 1));
+        assert_eq!(areas.wave, Rect::new(0, 1, 30, 5));
+        assert_eq!(areas.ticker, Rect::new(0, 6, 30, 1));
+        assert_eq!(areas.status, Rect::new(0, 7, 30, 1));
+    }
+
+    #[test]
+    fn test_split_fits() {
+        for width in 0..130 {
+            for height in 0..45 {
+                let size = Rect::new(0, 0, width, height);
+                let areas = split(size);
+                assert!(contains(size, areas.header), "{:?}", size);
+                assert!(contains(size, areas.wave), "{:?}", size);
+                assert!(contains(size, areas.status), "{:?}", size);
+                assert!(contains(size, areas.ticker), "{:?}", size);
+            }
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use std::time::{Duration, Instant};
+
+use tui::buffer::Buffer;
+use tui::layout::Rect;
+use tui::style::{Color, Style};
+use tui::widgets::Widget;
+
+use crate::tap;
+
+// window of samples measured on every update
+const WINDOW: Duration = Duration::from_millis(50);
+
+// how long a peak is held before falling back to the current level
+const HOLD: Duration = Duration::from_millis(1500);
+
+// how long the clip indicator stays lit after clipping
+const CLIP_HOLD: Duration = Duration::from_secs(2);
+
+// levels below this (in dBFS) are not displayed
+const FLOOR_DB: f32 = -48.0;
+
+/// Peak and RMS amplitude (0.0 to 1.0) of the left and right channels.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Levels {
+    pub peak: [f32; 2],
+    pub rms: [f32; 2],
+    pub clipped: bool,
+}
+
+/// Measure interleaved samples. Mono is reported on both channels, and
+/// anything past two channels is ignored.
+pub fn measure(samples: &[i16], channels: usize) -> Levels {
+    let mut levels = Levels::default();
+    let channels = channels.max(1);
+    let frames = samples.len() / channels;
+    if frames == 0 {
+        return levels;
+    }
+
+    let mut sums = [0.0; 2];
+    for frame in samples.chunks(channels) {
+        for c in 0..2 {
+            let sample = frame[c.min(frame.len() - 1)];
+            if sample == i16::max_value() || sample == i16::min_value() {
+                levels.clipped = true;
+            }
+
+            let value = (sample as f32 / 32768.0).abs();
+            levels.peak[c] = levels.peak[c].max(value);
+            sums[c] += value * value;
+        }
+    }
+
+    for c in 0..2 {
+        levels.rms[c] = (sums[c] / frames as f32).sqrt();
+    }
+
+    levels
+}
+
+/// Meter state, tracking held peaks and clipping between updates.
+pub struct Meter {
+    reading: Reading,
+    position: u64,
+    held_at: [Instant; 2],
+    clipped_at: Option<Instant>,
+}
+
+/// A snapshot of the meter, ready to be displayed.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Reading {
+    pub levels: Levels,
+    pub hold: [f32; 2],
+    pub clip: bool,
+}
+
+impl Meter {
+    pub fn new() -> Meter {
+        let now = Instant::now();
+        Meter {
+            reading: Reading::default(),
+            position: 0,
+            held_at: [now; 2],
+            clipped_at: None,
+        }
+    }
+
+    /// Measure the latest samples from the tap. When nothing new has been
+    /// played since the last update (paused, stopped) the meter drops to zero.
+    pub fn update(&mut self, samples: &tap::Buffer) {
+        let position = samples.position();
+        let levels = if position != self.position {
+            let channels = samples.channels().max(1) as usize;
+            let window = samples.sample_rate() as u128 * WINDOW.as_millis() / 1000;
+            let latest = samples.latest(window as usize * channels);
+            measure(&latest, channels)
+        } else {
+            Levels::default()
+        };
+        self.position = position;
+
+        self.update_at(levels, Instant::now());
+    }
+
+    fn update_at(&mut self, levels: Levels, now: Instant) {
+        for c in 0..2 {
+            let expired = now.duration_since(self.held_at[c]) >= HOLD;
+            if levels.peak[c] >= self.reading.hold[c] || expired {
+                self.reading.hold[c] = levels.peak[c];
+                self.held_at[c] = now;
+            }
+        }
+
+        if levels.clipped {
+            self.clipped_at = Some(now);
+        }
+
+        self.reading.levels = levels;
+        self.reading.clip = match self.clipped_at {
+            Some(at) => now.duration_since(at) < CLIP_HOLD,
+            None => false,
+        };
+    }
+
+    pub fn reading(&self) -> Reading {
+        self.reading
+    }
+}
+
+// Map a linear amplitude onto the displayed (dB) scale, 0.0 to 1.0
+fn scale(amplitude: f32) -> f32 {
+    if amplitude <= 0.0 {
+        return 0.0;
+    }
+
+    let db = 20.0 * amplitude.log10();
+    ((db - FLOOR_DB) / -FLOOR_DB).max(0.0).min(1.0)
+}
+
+/// Compact stereo level meter on a single line, RMS drawn solid with the
+/// peak and held peak on top of it: `L ███▒▒ |  R ██▒▒  |  CLIP`
+pub struct Vu {
+    pub reading: Reading,
+}
+
+impl Default for Vu {
+    fn default() -> Vu {
+        Vu {
+            reading: Reading::default(),
+        }
+    }
+}
+
+impl Vu {
+    pub fn reading(&mut self, reading: Reading) -> &mut Vu {
+        self.reading = reading;
+        self
+    }
+
+    /// Width needed to display the meter with bars of the given width.
+    pub fn width(bar: u16) -> u16 {
+        // "L " + bar + " " for each channel, then "CLIP"
+        2 * (bar + 3) + 4
+    }
+}
+
+impl Widget for Vu {
+    fn draw(&mut self, area: Rect, buf: &mut Buffer) {
+        if area.width < Vu::width(1) || area.height == 0 {
+            return;
+        }
+
+        let bar = (area.width - Vu::width(0)) / 2;
+        let y = area.top();
+        let mut x = area.left();
+
+        for (c, label) in ["L", "R"].iter().enumerate() {
+            buf.set_string(x, y, label, Style::default());
+            x += 2;
+
+            let rms = (scale(self.reading.levels.rms[c]) * bar as f32).round() as u16;
+            let peak = (scale(self.reading.levels.peak[c]) * bar as f32).round() as u16;
+            let hold = (scale(self.reading.hold[c]) * bar as f32).round() as u16;
+
+            for i in 0..bar {
+                let symbol = if i < rms {
+                    "█"
+                } else if i < peak {
+                    "▒"
+                } else if hold > 0 && i == hold - 1 {
+                    "|"
+                } else {
+                    " "
+                };
+
+                // green up to -9 dB, yellow up to -3 dB, then red
+                let position = (i + 1) as f32 / bar as f32;
+                let color = if position > scale(0.708) {
+                    Color::Red
+                } else if position > scale(0.355) {
+                    Color::Yellow
+                } else {
+                    Color::Green
+                };
+
+                buf.set_string(x + i, y, symbol, Style::default().fg(color));
+            }
+            x += bar + 1;
+        }
+
+        if self.reading.clip {
+            buf.set_string(x, y, "CLIP", Style::default().fg(Color::Red));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_measure() {
+        // left full scale square wave, right silent
+        let samples = vec![i16::max_value(), 0, i16::min_value(), 0];
+        let levels = measure(&samples, 2);
+
+        assert!(levels.clipped);
+        assert!((levels.peak[0] - 1.0).abs() < 0.001);
+        assert!((levels.rms[0] - 1.0).abs() < 0.001);
+        assert_eq!(levels.peak[1], 0.0);
+        assert_eq!(levels.rms[1], 0.0);
+    }
+
+    #[test]
+    fn test_measure_mono() {
+        let samples = vec![16384, -16384];
+        let levels = measure(&samples, 1);
+
+        assert!(!levels.clipped);
+        assert_eq!(levels.peak, [0.5, 0.5]);
+        assert_eq!(levels.rms, [0.5, 0.5]);
+    }
+
+    #[test]
+    fn test_peak_hold() {
+        let mut meter = Meter::new();
+        let start = Instant::now();
+        let loud = Levels {
+            peak: [0.9, 0.9],
+            rms: [0.5, 0.5],
+            clipped: true,
+        };
+        let quiet = Levels {
+            peak: [0.1, 0.1],
+            rms: [0.05, 0.05],
+            clipped: false,
+        };
+
+        meter.update_at(loud, start);
+        meter.update_at(quiet, start + Duration::from_millis(500));
+        assert_eq!(meter.reading().hold, [0.9, 0.9]);
+        assert!(meter.reading().clip);
+
+        meter.update_at(quiet, start + HOLD + CLIP_HOLD);
+        assert_eq!(meter.reading().hold, [0.1, 0.1]);
+        assert!(!meter.reading().clip);
+    }
+
+    #[test]
+    fn test_scale() {
+        assert_eq!(scale(0.0), 0.0);
+        assert_eq!(scale(1.0), 1.0);
+        assert!((scale(0.5) - (1.0 - 6.0206 / 48.0)).abs() < 0.001);
+    }
+}
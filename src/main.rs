@@ -1,139 +1,250 @@
 #[macro_use]
 extern crate serde;
 
-use rodio::Sink;
-use rodio::Source;
-use std::env;
+use std::collections::HashMap;
 use std::io;
-use std::io::BufReader;
-use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::process;
 use termion;
-use termion::event::Key;
 use termion::raw::IntoRawMode;
 use termion::screen::AlternateScreen;
 use tui::backend::TermionBackend;
-use tui::layout::{Alignment, Constraint, Direction, Layout};
-use tui::style::{Modifier, Style};
-use tui::widgets::{Paragraph, Text, Widget};
 use tui::Terminal;
+mod app;
+mod artwork;
+mod bookmarks;
+#[cfg(all(
+    test,
+    target_os = "linux",
+    any(feature = "mpris", feature = "notifications")
+))]
+mod bus;
+mod cli;
+mod commands;
+mod comments;
+mod config;
+mod control;
 mod decoder;
 mod event;
+mod flac;
+mod headless;
+mod history;
+mod info;
+mod keys;
+mod layout;
+mod level;
+#[cfg(all(target_os = "linux", feature = "mpris"))]
+mod mpris;
+#[cfg(all(target_os = "linux", feature = "notifications"))]
+mod notify;
+mod nowplaying;
+mod output;
+mod picker;
+mod player;
+mod playlist;
+mod popup;
+mod queue;
+mod radio;
+mod record;
+mod remote;
+mod resume;
+mod scrobble;
+mod services;
 mod soundcloud;
+mod spectrum;
 mod status;
+mod stretch;
+mod tap;
+mod theme;
+mod ui;
 mod wave;
 
-struct Player<'a> {
-    track: &'a soundcloud::Track,
-    audio: rodio::Sink,
-    timer: Arc<Mutex<Duration>>,
-    state: PlayerState,
-    progress: f32,
-    volume: u8,
-}
+// Urls to play for a command line argument, a url or a playlist file, with
+// the position to start at when the playlist has one
+fn urls(arg: String) -> Result<Vec<(String, Option<u32>)>, failure::Error> {
+    let path = Path::new(&arg);
+    if playlist::Format::from_path(path).is_none() || !path.is_file() {
+        return Ok(vec![(arg, None)]);
+    }
 
-#[derive(Clone, Copy, Eq, PartialEq)]
-enum PlayerState {
-    Playing,
-    Paused,
-    Stopped,
-}
+    let mut urls = Vec::new();
+    for entry in playlist::import(path)? {
+        // e.g. local files, from another player's playlist
+        if !entry.url.starts_with("http") {
+            eprintln!("skipping {}, not a SoundCloud url", entry.url);
+            continue;
+        }
+        urls.push((entry.url, entry.position_ms));
+    }
 
-enum PlayerEvent {
-    Tick,
-    PlayPause,
-    VolumeUp,
-    VolumeDown,
+    Ok(urls)
 }
 
-impl Player<'_> {
-    fn new(track: &soundcloud::Track) -> Player {
-        // load default output device
-        let device = rodio::default_output_device().unwrap();
-
-        // resolve and decode stream
-        let client = soundcloud::Client::new();
-        let stream = client.stream(&track.stream_url).unwrap();
-        let source = decoder::Mp3Decoder::new(BufReader::new(stream)).unwrap();
-
-        let timer = Arc::new(Mutex::new(Duration::from_secs(0)));
-        let with_elapsed = source.buffered().elapsed(Arc::clone(&timer));
-
-        // start audio on registered device
-        let sink = Sink::new(&device);
-        sink.append(with_elapsed);
-
-        Player {
-            track: track,
-            audio: sink,
-            timer: timer,
-            state: PlayerState::Playing,
-            progress: 0.0,
-            volume: 100,
+fn main() {
+    match run() {
+        Ok(status) => process::exit(status),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(1);
         }
     }
+}
 
-    fn update(&mut self, msg: PlayerEvent) {
-        match msg {
-            PlayerEvent::Tick => {
-                if self.audio.empty() {
-                    self.state = PlayerState::Stopped;
-                } else {
-                    if self.state == PlayerState::Stopped {
-                        self.progress = 0.0;
-                    } else {
-                        self.progress =
-                            (self.elapsed() as f32 / self.track.duration as f32) * 100.0;
-                    }
-                }
-            }
-            PlayerEvent::PlayPause => {
-                if self.audio.is_paused() {
-                    self.audio.play();
-                    self.state = PlayerState::Playing;
-                } else {
-                    self.audio.pause();
-                    self.state = PlayerState::Paused;
-                }
-            }
-            PlayerEvent::VolumeUp => {
-                if self.volume == 100 {
-                    return;
-                }
+// Exit status once done, 0 when the queue ran out or quit was pressed
+fn run() -> Result<i32, failure::Error> {
+    let matches = cli::app().get_matches();
 
-                self.volume = self.volume + 1;
-                self.audio.set_volume(self.volume as f32 / 100.0);
-            }
-            PlayerEvent::VolumeDown => {
-                if self.volume == 0 {
-                    return;
-                }
+    // load config, arguments and tracks before touching the terminal, so errors are readable
+    let config = match cli::config(&matches) {
+        Some(path) => config::Config::from_file(&PathBuf::from(path))?,
+        None => config::Config::load()?,
+    };
+    let sc = soundcloud::Client::new().token(config.token.clone());
 
-                self.volume = self.volume - 1;
-                self.audio.set_volume(self.volume as f32 / 100.0);
-            }
+    let args = match matches.subcommand() {
+        ("search", Some(args)) => {
+            let query: Vec<&str> = args.values_of("query").unwrap_or_default().collect();
+            let limit = args.value_of("limit").unwrap_or("20").parse()?;
+            commands::search(&sc, &query.join(" "), limit)?;
+            return Ok(0);
+        }
+        ("user", Some(args)) => {
+            commands::user(&sc, args.value_of("user").unwrap_or(""))?;
+            return Ok(0);
+        }
+        ("download", Some(args)) => {
+            commands::download(
+                &sc,
+                args.value_of("url").unwrap_or(""),
+                args.value_of("output"),
+            )?;
+            return Ok(0);
+        }
+        ("record", Some(args)) => {
+            commands::record(
+                &sc,
+                args.value_of("url").unwrap_or(""),
+                args.value_of("output"),
+            )?;
+            return Ok(0);
+        }
+        ("ctl", Some(args)) => {
+            commands::ctl(
+                args.value_of("command").unwrap_or(""),
+                args.value_of("value"),
+                args.is_present("json"),
+            )?;
+            return Ok(0);
+        }
+        ("info", Some(args)) => {
+            commands::info(&sc, args.value_of("url").unwrap_or(""))?;
+            return Ok(0);
+        }
+        ("devices", Some(_)) => {
+            commands::devices()?;
+            return Ok(0);
         }
+        ("history", Some(args)) => {
+            let terms: Vec<&str> = args.values_of("terms").unwrap_or_default().collect();
+            commands::history(&terms)?;
+            return Ok(0);
+        }
+        ("bookmarks", Some(args)) => {
+            let terms: Vec<&str> = args.values_of("terms").unwrap_or_default().collect();
+            commands::bookmarks(&terms)?;
+            return Ok(0);
+        }
+        ("export", Some(args)) => {
+            commands::export(
+                args.value_of("what").unwrap_or(""),
+                Path::new(args.value_of("file").unwrap_or("")),
+            )?;
+            return Ok(0);
+        }
+        ("play", Some(args)) => args,
+        _ => &matches,
+    };
+
+    let bindings = keys::Bindings::with_overrides(&config.keys)?;
+    let theme = match args
+        .value_of("theme")
+        .or_else(|| config.theme.as_ref().map(String::as_str))
+    {
+        Some(name) => theme::Theme::named(name)
+            .ok_or_else(|| failure::format_err!("unknown theme '{}'", name))?,
+        None => theme::Theme::default(),
+    };
+    // stdout is the screen, unless there isn't one
+    let audio = args
+        .value_of("audio")
+        .and_then(output::Kind::parse)
+        .unwrap_or(output::Kind::Speaker);
+    let headless = args.is_present("no-tui") || args.is_present("quiet");
+    if audio == output::Kind::Pcm && !headless {
+        failure::bail!("--audio pcm writes to stdout, so it needs --no-tui or --quiet");
     }
+    let device = args
+        .value_of("device")
+        .or_else(|| config.device.as_ref().map(String::as_str));
+    let mut options = app::Options {
+        autoplay: args.is_present("autoplay"),
+        start: args.value_of("from").and_then(status::Clock::parse),
+        starts: HashMap::new(),
+        volume: args.value_of("volume").unwrap_or("100").parse()?,
+        speed: args.value_of("speed").unwrap_or("1").parse()?,
+        theme,
+        scrobble: config.scrobble.clone(),
+        output: output::Output::open(&audio, device)?,
+    };
 
-    fn state(&self) -> PlayerState {
-        self.state
+    let mut urls = Vec::new();
+    for source in args.values_of("sources").unwrap_or_default() {
+        urls.extend(self::urls(source.to_string())?);
+    }
+    if urls.is_empty() {
+        failure::bail!("nothing to play, pass a SoundCloud url or playlist file");
     }
 
-    fn progress(&self) -> f32 {
-        self.progress
+    // resolve tracks, SoundCloud playlists and users into a single queue
+    let mut tracks = Vec::new();
+    for (url, position) in urls {
+        let resolved = sc.tracks(url)?;
+        // a position only means something for a single track
+        if let (Some(position), [track]) = (position, resolved.as_slice()) {
+            options.starts.insert(track.id, position);
+        }
+        tracks.extend(resolved);
     }
 
-    fn volume(&self) -> u8 {
-        self.volume
+    let mut queue = queue::Queue::new(tracks);
+    if let Some(seed) = args.value_of("seed") {
+        queue.seed(seed.parse()?);
     }
+    queue.set_shuffle(args.is_present("shuffle"));
+
+    let remote = match args
+        .value_of("remote")
+        .or(config.remote.as_ref().map(String::as_str))
+    {
+        Some(address) => {
+            let token = config.remote_token.clone().ok_or_else(|| {
+                failure::format_err!("the remote needs a remote_token in the config")
+            })?;
+            Some(remote::bind(address, token)?)
+        }
+        None => None,
+    };
+    let setup = services::Setup {
+        remote,
+        now_playing: config.now_playing.clone(),
+        notifications: config.notifications,
+    };
 
-    fn elapsed(&self) -> u32 {
-        let val = *self.timer.lock().unwrap();
-        val.as_millis() as u32
+    if headless {
+        let mut app = app::App::new(sc, bindings, queue, options)?;
+        return headless::run(&mut app, setup, args.is_present("quiet"));
     }
-}
 
-fn main() -> Result<(), failure::Error> {
     // terminal init
     let stdout = io::stdout().into_raw_mode()?;
     let stdout = AlternateScreen::from(stdout);
@@ -141,87 +252,37 @@ fn main() -> Result<(), failure::Error> {
     let mut terminal = Terminal::new(backend)?;
     terminal.hide_cursor()?;
 
-    // read file name
-    let args: Vec<String> = env::args().collect();
-    let url = &args[1];
-
-    // resolve the track and waveform
-    let sc = soundcloud::Client::new();
-    let track = sc.track(url.to_string()).unwrap();
-    let wave = sc.wave(&track).unwrap();
-
-    // start player thread and listen for incoming from it
-    let mut player = Player::new(&track);
+    // start playing the first track
+    let mut app = app::App::new(sc, bindings, queue, options)?;
     let events = event::Events::new();
+    let mut services = services::Services::start(&events, setup);
 
-    loop {
-        terminal.draw(|mut f| {
-            let chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .margin(2)
-                .constraints(
-                    [
-                        Constraint::Length(3),
-                        Constraint::Length(10),
-                        Constraint::Length(1),
-                    ]
-                    .as_ref(),
-                )
-                .split(f.size());
-
-            // track info
-            let header = [
-                Text::styled(&track.user.username, Style::default()),
-                Text::raw("\n"),
-                Text::styled(&track.title, Style::default().modifier(Modifier::BOLD)),
-            ];
-            Paragraph::new(header.iter())
-                .alignment(Alignment::Left)
-                .render(&mut f, chunks[0]);
-
-            // waveform
-            wave::Wave::default()
-                .width(wave.width)
-                .height(wave.height)
-                .samples(wave.samples.clone())
-                .progress(player.progress())
-                .render(&mut f, chunks[1]);
-
-            // player status
-            status::Status::default()
-                .is_playing(player.state() == PlayerState::Playing)
-                .volume(player.volume())
-                .clock(status::Clock {
-                    elapsed_ms: player.elapsed(),
-                    total_ms: track.duration,
-                })
-                .render(&mut f, chunks[2]);
-        })?;
+    let status = loop {
+        terminal.draw(|mut f| ui::draw(&mut f, &app.screen()))?;
 
         match events.next()? {
             event::Event::Tick => {
-                player.update(PlayerEvent::Tick);
-                if player.state() == PlayerState::Stopped {
-                    break;
+                if !app.tick() {
+                    break 0;
                 }
             }
-            event::Event::Input(input) => match input {
-                Key::Char('q') => {
-                    break;
-                }
-                Key::Char(' ') => {
-                    player.update(PlayerEvent::PlayPause);
-                }
-                Key::Up => {
-                    player.update(PlayerEvent::VolumeUp);
+            event::Event::Input(input) => {
+                if !app.input(input) {
+                    break 0;
                 }
-                Key::Down => {
-                    player.update(PlayerEvent::VolumeDown);
-                }
-                _ => {}
-            },
+            }
+            event::Event::Resize => {
+                // redrawn on the next pass, the terminal resizes itself
+            }
+            event::Event::Interrupt => break headless::INTERRUPTED,
+            event::Event::Control(request) => {
+                let _ = request.reply.send(app.control(request.command));
+            }
         }
-    }
 
-    Ok(())
+        services.publish(&app);
+    };
+
+    app.quit()?;
+    Ok(status)
 }
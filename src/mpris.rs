@@ -0,0 +1,426 @@
+// MPRIS on the session bus, so media keys, desktop widgets and playerctl
+// can control the player and show what's playing
+// https://specifications.freedesktop.org/mpris-spec/latest/
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use dbus::arg::{PropMap, RefArg, Variant};
+use dbus::blocking::stdintf::org_freedesktop_dbus::PropertiesPropertiesChanged;
+use dbus::blocking::Connection;
+use dbus::channel::{MatchingReceiver, Sender};
+use dbus::message::{MatchRule, SignalArgs};
+use dbus::Path;
+use dbus_crossroads::{Crossroads, MethodErr};
+use termion::event::Key;
+
+use crate::control::{Command, Reply, Request, Status};
+use crate::event::Event;
+use crate::queue::Repeat;
+use crate::stretch;
+
+const PATH: &str = "/org/mpris/MediaPlayer2";
+const BUS_NAME: &str = "org.mpris.MediaPlayer2.scli";
+const PLAYER: &str = "org.mpris.MediaPlayer2.Player";
+
+/// Shares the player's state with the bus.
+pub struct Mpris {
+    status: Arc<Mutex<Option<Status>>>,
+}
+
+// What methods and properties work with, kept by crossroads
+struct Bus {
+    events: mpsc::Sender<Event<Key>>,
+    status: Arc<Mutex<Option<Status>>>,
+}
+
+impl Bus {
+    // Pass a command on to the player, like the control socket does
+    fn send(&self, command: Command) -> Result<Reply, MethodErr> {
+        let (tx, rx) = mpsc::channel();
+        self.events
+            .send(Event::Control(Request { command, reply: tx }))
+            .map_err(|_| MethodErr::failed("player stopped"))?;
+        let reply = rx.recv().map_err(|_| MethodErr::failed("player stopped"))?;
+        match reply.error {
+            Some(error) => Err(MethodErr::failed(&error)),
+            None => Ok(reply),
+        }
+    }
+
+    fn status(&self) -> Status {
+        self.status.lock().unwrap().clone().unwrap_or_default()
+    }
+}
+
+impl Mpris {
+    /// Join the session bus, unless there isn't one.
+    pub fn start(events: mpsc::Sender<Event<Key>>) -> Option<Mpris> {
+        Mpris::serve(Connection::new_session, events).ok()
+    }
+
+    fn serve<F>(connect: F, events: mpsc::Sender<Event<Key>>) -> Result<Mpris, failure::Error>
+    where
+        F: FnOnce() -> Result<Connection, dbus::Error> + Send + 'static,
+    {
+        let status = Arc::new(Mutex::new(None));
+        let bus = Bus {
+            events,
+            status: Arc::clone(&status),
+        };
+
+        // the connection stays on its own thread, only whether it worked comes back
+        let (ready, started) = mpsc::channel();
+        thread::spawn(move || {
+            let conn = match connect().and_then(|conn| register(&conn).map(|_| conn)) {
+                Ok(conn) => conn,
+                Err(e) => {
+                    let _ = ready.send(Err(e));
+                    return;
+                }
+            };
+            let _ = ready.send(Ok(()));
+            run(conn, bus);
+        });
+
+        started.recv()??;
+        Ok(Mpris { status })
+    }
+
+    /// Update what's shown on the bus.
+    pub fn publish(&self, status: Status) {
+        *self.status.lock().unwrap() = Some(status);
+    }
+}
+
+// Take the player's name, or one of its own when another scli already has it
+fn register(conn: &Connection) -> Result<(), dbus::Error> {
+    use dbus::blocking::stdintf::org_freedesktop_dbus::RequestNameReply;
+
+    match conn.request_name(BUS_NAME, false, false, true)? {
+        RequestNameReply::PrimaryOwner | RequestNameReply::AlreadyOwner => Ok(()),
+        _ => {
+            let name = format!("{}.instance{}", BUS_NAME, std::process::id());
+            conn.request_name(name, false, false, true).map(|_| ())
+        }
+    }
+}
+
+fn run(conn: Connection, bus: Bus) {
+    let status = Arc::clone(&bus.status);
+    let mut cr = Crossroads::new();
+    let root = cr.register("org.mpris.MediaPlayer2", |b| {
+        b.method("Raise", (), (), |_, _: &mut Bus, _: ()| Ok(()));
+        b.method("Quit", (), (), |_, _: &mut Bus, _: ()| {
+            Err::<(), _>(MethodErr::failed("quit from the player itself"))
+        });
+        b.property("CanQuit").get(|_, _: &mut Bus| Ok(false));
+        b.property("CanRaise").get(|_, _: &mut Bus| Ok(false));
+        b.property("HasTrackList").get(|_, _: &mut Bus| Ok(false));
+        b.property("Identity")
+            .get(|_, _: &mut Bus| Ok("scli".to_string()));
+        b.property("SupportedUriSchemes")
+            .get(|_, _: &mut Bus| Ok(vec!["https".to_string()]));
+        b.property("SupportedMimeTypes")
+            .get(|_, _: &mut Bus| Ok(Vec::<String>::new()));
+    });
+    let player = cr.register(PLAYER, |b| {
+        b.method("Play", (), (), |_, bus: &mut Bus, _: ()| {
+            bus.send(Command::Play).map(|_| ())
+        });
+        b.method("Pause", (), (), |_, bus: &mut Bus, _: ()| {
+            bus.send(Command::Pause).map(|_| ())
+        });
+        b.method("PlayPause", (), (), |_, bus: &mut Bus, _: ()| {
+            bus.send(Command::Toggle).map(|_| ())
+        });
+        // there's nothing to stop to, pausing is the closest
+        b.method("Stop", (), (), |_, bus: &mut Bus, _: ()| {
+            bus.send(Command::Pause).map(|_| ())
+        });
+        b.method("Next", (), (), |_, bus: &mut Bus, _: ()| {
+            bus.send(Command::Next).map(|_| ())
+        });
+        b.method("Previous", (), (), |_, _: &mut Bus, _: ()| {
+            Err::<(), _>(MethodErr::failed("there's no previous track"))
+        });
+        let seeked = b.signal::<(i64,), _>("Seeked", ("Position",)).msg_fn();
+        b.method(
+            "Seek",
+            ("Offset",),
+            (),
+            move |ctx, bus: &mut Bus, (offset,): (i64,)| {
+                let status = bus.status();
+                let position = (status.position_ms as i64 + offset / 1000).max(0) as u32;
+                bus.send(Command::Seek { position })?;
+                ctx.push_msg(seeked(ctx.path(), &(position as i64 * 1000,)));
+                Ok(())
+            },
+        );
+        let seeked = b.signal::<(i64,), _>("Seeked", ("Position",)).msg_fn();
+        b.method(
+            "SetPosition",
+            ("TrackId", "Position"),
+            (),
+            move |ctx, bus: &mut Bus, (track, position): (Path<'static>, i64)| {
+                // ignored when the track has moved on since, as the spec says
+                let status = bus.status();
+                if track != track_path(status.track_id) || position < 0 {
+                    return Ok(());
+                }
+                bus.send(Command::Seek {
+                    position: (position / 1000) as u32,
+                })?;
+                ctx.push_msg(seeked(ctx.path(), &(position,)));
+                Ok(())
+            },
+        );
+        b.method(
+            "OpenUri",
+            ("Uri",),
+            (),
+            |_, bus: &mut Bus, (url,): (String,)| bus.send(Command::Open { url }).map(|_| ()),
+        );
+
+        b.property("PlaybackStatus")
+            .get(|_, bus: &mut Bus| Ok(playback_status(&bus.status()).to_string()));
+        b.property("LoopStatus")
+            .get(|_, bus: &mut Bus| Ok(loop_status(bus.status().repeat).to_string()))
+            .set(|_, bus: &mut Bus, value: String| {
+                let repeat = repeat(&value).ok_or_else(|| MethodErr::invalid_arg(&value))?;
+                bus.send(Command::Repeat { repeat })?;
+                Ok(None)
+            });
+        b.property("Shuffle")
+            .get(|_, bus: &mut Bus| Ok(bus.status().shuffle))
+            .set(|_, bus: &mut Bus, shuffle: bool| {
+                bus.send(Command::Shuffle { shuffle })?;
+                Ok(None)
+            });
+        b.property("Volume")
+            .get(|_, bus: &mut Bus| Ok(bus.status().volume as f64 / 100.0))
+            .set(|_, bus: &mut Bus, volume: f64| {
+                let volume = (volume.max(0.0).min(1.0) * 100.0).round() as u8;
+                bus.send(Command::Volume { volume })?;
+                Ok(None)
+            });
+        b.property("Metadata")
+            .get(|_, bus: &mut Bus| Ok(metadata(&bus.status())));
+        b.property("Position")
+            .emits_changed_false()
+            .get(|_, bus: &mut Bus| Ok(bus.status().position_ms as i64 * 1000));
+        b.property("Rate")
+            .get(|_, bus: &mut Bus| Ok(bus.status().speed as f64))
+            .set(|_, bus: &mut Bus, rate: f64| {
+                bus.send(Command::Speed { speed: rate as f32 })?;
+                Ok(None)
+            });
+        b.property("MinimumRate")
+            .get(|_, _: &mut Bus| Ok(stretch::MIN_SPEED as f64));
+        b.property("MaximumRate")
+            .get(|_, _: &mut Bus| Ok(stretch::MAX_SPEED as f64));
+        for name in &["CanGoNext", "CanPlay", "CanPause", "CanSeek", "CanControl"] {
+            b.property(*name).get(|_, _: &mut Bus| Ok(true));
+        }
+        b.property("CanGoPrevious").get(|_, _: &mut Bus| Ok(false));
+    });
+    cr.insert(PATH, &[root, player], bus);
+
+    conn.start_receive(
+        MatchRule::new_method_call(),
+        Box::new(move |msg, conn| {
+            let _ = cr.handle_message(msg, conn);
+            true
+        }),
+    );
+
+    // the player's state is polled, widgets are told whenever it changes
+    let mut shown: Option<Status> = None;
+    loop {
+        if conn.process(Duration::from_millis(250)).is_err() {
+            return;
+        }
+
+        let mut current = match *status.lock().unwrap() {
+            Some(ref status) => status.clone(),
+            None => continue,
+        };
+        // the position isn't announced, only jumps in it
+        current.position_ms = 0;
+        if shown.as_ref() == Some(&current) {
+            continue;
+        }
+
+        let changed = PropertiesPropertiesChanged {
+            interface_name: PLAYER.to_string(),
+            changed_properties: properties(&current),
+            invalidated_properties: Vec::new(),
+        };
+        let _ = conn.send(changed.to_emit_message(&Path::from(PATH)));
+        shown = Some(current);
+    }
+}
+
+fn playback_status(status: &Status) -> &'static str {
+    match status.state.as_str() {
+        "playing" => "Playing",
+        "paused" => "Paused",
+        _ => "Stopped",
+    }
+}
+
+fn loop_status(repeat: Repeat) -> &'static str {
+    match repeat {
+        Repeat::Off => "None",
+        Repeat::All => "Playlist",
+        Repeat::One => "Track",
+    }
+}
+
+fn repeat(loop_status: &str) -> Option<Repeat> {
+    match loop_status {
+        "None" => Some(Repeat::Off),
+        "Playlist" => Some(Repeat::All),
+        "Track" => Some(Repeat::One),
+        _ => None,
+    }
+}
+
+fn track_path(track_id: u64) -> Path<'static> {
+    if track_id == 0 {
+        return Path::from("/org/mpris/MediaPlayer2/TrackList/NoTrack");
+    }
+    Path::from(format!("/org/scli/track/{}", track_id))
+}
+
+fn metadata(status: &Status) -> PropMap {
+    let mut metadata = PropMap::new();
+    let mut insert = |key: &str, value: Box<dyn RefArg>| {
+        metadata.insert(key.to_string(), Variant(value));
+    };
+    insert("mpris:trackid", Box::new(track_path(status.track_id)));
+    insert("mpris:length", Box::new(status.duration_ms as i64 * 1000));
+    insert("xesam:title", Box::new(status.title.clone()));
+    insert("xesam:artist", Box::new(vec![status.username.clone()]));
+    insert("xesam:url", Box::new(status.url.clone()));
+    if let Some(ref artwork_url) = status.artwork_url {
+        insert("mpris:artUrl", Box::new(artwork_url.clone()));
+    }
+
+    metadata
+}
+
+// Everything that's announced when it changes
+fn properties(status: &Status) -> PropMap {
+    let mut properties = PropMap::new();
+    let mut insert = |key: &str, value: Box<dyn RefArg>| {
+        properties.insert(key.to_string(), Variant(value));
+    };
+    insert(
+        "PlaybackStatus",
+        Box::new(playback_status(status).to_string()),
+    );
+    insert(
+        "LoopStatus",
+        Box::new(loop_status(status.repeat).to_string()),
+    );
+    insert("Shuffle", Box::new(status.shuffle));
+    insert("Volume", Box::new(status.volume as f64 / 100.0));
+    insert("Rate", Box::new(status.speed as f64));
+    insert("Metadata", Box::new(metadata(status)));
+
+    properties
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::{self, Daemon};
+    use dbus::blocking::stdintf::org_freedesktop_dbus::Properties;
+
+    fn status() -> Status {
+        Status {
+            track_id: 1,
+            title: String::from("Art of Minimal Techno"),
+            username: String::from("trippycode"),
+            state: String::from("playing"),
+            position_ms: 60000,
+            duration_ms: 4112000,
+            volume: 80,
+            ..Status::default()
+        }
+    }
+
+    #[test]
+    fn test_mapping() {
+        assert_eq!(playback_status(&status()), "Playing");
+        assert_eq!(playback_status(&Status::default()), "Stopped");
+        for &r in &[Repeat::Off, Repeat::All, Repeat::One] {
+            assert_eq!(repeat(loop_status(r)), Some(r));
+        }
+        assert_eq!(repeat("Forever"), None);
+        assert_eq!(
+            track_path(0),
+            Path::from("/org/mpris/MediaPlayer2/TrackList/NoTrack")
+        );
+    }
+
+    #[test]
+    fn test_bus() {
+        let daemon = match Daemon::start() {
+            Some(daemon) => daemon,
+            None => return eprintln!("skipping, dbus-daemon isn't installed"),
+        };
+
+        // stands in for the player's event loop, passing commands back to the test
+        let (events, rx) = mpsc::channel();
+        let (commands, received) = mpsc::channel();
+        thread::spawn(move || {
+            for event in rx {
+                if let Event::Control(request) = event {
+                    commands.send(request.command).unwrap();
+                    request.reply.send(Reply::ok()).unwrap();
+                }
+            }
+        });
+
+        let address = daemon.address.clone();
+        let mpris = Mpris::serve(move || bus::connect(&address), events).unwrap();
+        mpris.publish(status());
+
+        let client = daemon.connect().unwrap();
+        let proxy = client.with_proxy(BUS_NAME, PATH, Duration::from_secs(5));
+
+        let playback: String = proxy.get(PLAYER, "PlaybackStatus").unwrap();
+        assert_eq!(playback, "Playing");
+        let metadata: PropMap = proxy.get(PLAYER, "Metadata").unwrap();
+        assert_eq!(
+            metadata["xesam:title"].0.as_str(),
+            Some("Art of Minimal Techno")
+        );
+        let identity: String = proxy.get("org.mpris.MediaPlayer2", "Identity").unwrap();
+        assert_eq!(identity, "scli");
+
+        let _: () = proxy.method_call(PLAYER, "PlayPause", ()).unwrap();
+        assert_eq!(received.recv().unwrap(), Command::Toggle);
+
+        // 30 seconds on from a minute in
+        let _: () = proxy.method_call(PLAYER, "Seek", (30_000_000i64,)).unwrap();
+        assert_eq!(received.recv().unwrap(), Command::Seek { position: 90000 });
+
+        proxy.set(PLAYER, "Volume", 0.5f64).unwrap();
+        assert_eq!(received.recv().unwrap(), Command::Volume { volume: 50 });
+        proxy
+            .set(PLAYER, "LoopStatus", "Track".to_string())
+            .unwrap();
+        assert_eq!(
+            received.recv().unwrap(),
+            Command::Repeat {
+                repeat: Repeat::One
+            }
+        );
+
+        let previous: Result<(), _> = proxy.method_call(PLAYER, "Previous", ());
+        assert!(previous.is_err());
+    }
+}
@@ -0,0 +1,197 @@
+// Desktop notifications when the next track starts, for when scli is
+// playing in a background terminal
+// https://specifications.freedesktop.org/notification-spec/latest/
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use dbus::arg::{PropMap, RefArg, Variant};
+use dbus::blocking::Connection;
+
+use crate::artwork::Artwork;
+use crate::control::Status;
+
+const NAME: &str = "org.freedesktop.Notifications";
+const PATH: &str = "/org/freedesktop/Notifications";
+
+// What a notification shows
+struct Notification {
+    title: String,
+    username: String,
+    artwork: Option<Artwork>,
+}
+
+pub struct Notifier {
+    notifications: mpsc::Sender<Notification>,
+    track_id: u64,
+}
+
+impl Notifier {
+    /// Connect to the session bus, unless there isn't one.
+    pub fn start() -> Option<Notifier> {
+        Notifier::serve(Connection::new_session).ok()
+    }
+
+    fn serve<F>(connect: F) -> Result<Notifier, failure::Error>
+    where
+        F: FnOnce() -> Result<Connection, dbus::Error> + Send + 'static,
+    {
+        let (ready, started) = mpsc::channel();
+        let (tx, rx) = mpsc::channel::<Notification>();
+        thread::spawn(move || {
+            let conn = match connect() {
+                Ok(conn) => conn,
+                Err(e) => {
+                    let _ = ready.send(Err(e));
+                    return;
+                }
+            };
+            let _ = ready.send(Ok(()));
+
+            // each one replaces the last, rather than piling up
+            let proxy = conn.with_proxy(NAME, PATH, Duration::from_secs(5));
+            let mut id = 0;
+            for notification in rx {
+                let reply: Result<(u32,), _> = proxy.method_call(
+                    NAME,
+                    "Notify",
+                    (
+                        "scli",
+                        id,
+                        "",
+                        &notification.title,
+                        escape(&notification.username),
+                        Vec::<String>::new(),
+                        hints(notification.artwork.as_ref()),
+                        -1,
+                    ),
+                );
+                if let Ok((replaces,)) = reply {
+                    id = replaces;
+                }
+            }
+        });
+
+        started.recv()??;
+        Ok(Notifier {
+            notifications: tx,
+            track_id: 0,
+        })
+    }
+
+    /// Notify when the next track has started, but not for the first,
+    /// which was just picked.
+    pub fn publish(&mut self, status: &Status, artwork: Option<&Artwork>) {
+        if status.track_id == self.track_id {
+            return;
+        }
+        if self.track_id != 0 {
+            let _ = self.notifications.send(Notification {
+                title: status.title.clone(),
+                username: status.username.clone(),
+                artwork: artwork.cloned(),
+            });
+        }
+        self.track_id = status.track_id;
+    }
+}
+
+fn hints(artwork: Option<&Artwork>) -> PropMap {
+    let mut hints = PropMap::new();
+    hints.insert(
+        "urgency".to_string(),
+        Variant(Box::new(0u8) as Box<dyn RefArg>),
+    );
+    if let Some(artwork) = artwork {
+        // raw pixels: width, height, rowstride, has alpha, bits per sample, channels
+        let image = artwork.image();
+        let (width, height) = image.dimensions();
+        let data = (
+            width as i32,
+            height as i32,
+            width as i32 * 3,
+            false,
+            8,
+            3,
+            image.clone().into_raw(),
+        );
+        hints.insert("image-data".to_string(), Variant(Box::new(data)));
+    }
+
+    hints
+}
+
+// The body may be marked up, a username's & and < should show as written
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::{self, Daemon};
+    use dbus::channel::MatchingReceiver;
+    use dbus::message::MatchRule;
+
+    fn status(track_id: u64, title: &str) -> Status {
+        Status {
+            track_id,
+            title: title.to_string(),
+            username: String::from("trippy & code"),
+            ..Status::default()
+        }
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("<b>a & b</b>"), "&lt;b&gt;a &amp; b&lt;/b&gt;");
+    }
+
+    #[test]
+    fn test_notify() {
+        let daemon = match Daemon::start() {
+            Some(daemon) => daemon,
+            None => return eprintln!("skipping, dbus-daemon isn't installed"),
+        };
+
+        // a notification server, passing what it's shown back to the test
+        let (shown, received) = mpsc::channel();
+        let server = daemon.connect().unwrap();
+        server.request_name(NAME, false, false, true).unwrap();
+        server.start_receive(
+            MatchRule::new_method_call(),
+            Box::new(move |msg, conn| {
+                use dbus::channel::Sender;
+                let (_, replaces, _, summary, body): (String, u32, String, String, String) =
+                    msg.read5().unwrap();
+                shown.send((replaces, summary, body)).unwrap();
+                let _ = conn.send(msg.method_return().append1(7u32));
+                true
+            }),
+        );
+        thread::spawn(move || loop {
+            server.process(Duration::from_millis(100)).unwrap();
+        });
+
+        let address = daemon.address.clone();
+        let mut notifier = Notifier::serve(move || bus::connect(&address)).unwrap();
+        notifier.publish(&status(1, "Art of Minimal Techno"), None);
+        notifier.publish(&status(1, "Art of Minimal Techno"), None);
+        notifier.publish(&status(2, "Deep Minimal"), None);
+        notifier.publish(&status(3, "Minimal Techno Mix"), None);
+
+        let timeout = Duration::from_secs(5);
+        assert_eq!(
+            received.recv_timeout(timeout).unwrap(),
+            (
+                0,
+                String::from("Deep Minimal"),
+                String::from("trippy &amp; code")
+            )
+        );
+        assert_eq!(received.recv_timeout(timeout).unwrap().0, 7);
+        assert!(received.recv_timeout(Duration::from_millis(200)).is_err());
+    }
+}
@@ -0,0 +1,210 @@
+// What's playing, for status bars: a one line file kept up to date,
+// and/or a command run whenever the track or its state changes
+use std::fs;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::config;
+use crate::control::Status;
+use crate::status::Clock;
+
+const FORMAT: &str = "{icon} {artist} - {title}  {elapsed} / {duration}";
+
+pub struct NowPlaying {
+    config: config::NowPlaying,
+    // last line written, and the track and state the command last ran for
+    line: Option<String>,
+    changed: Option<(u64, String)>,
+}
+
+impl NowPlaying {
+    pub fn new(config: config::NowPlaying) -> NowPlaying {
+        NowPlaying {
+            config,
+            line: None,
+            changed: None,
+        }
+    }
+
+    /// Write the line when it's changed, and run the command when
+    /// the track or its state has.
+    pub fn publish(&mut self, status: &Status) {
+        let format = self.config.format.as_ref().map(String::as_str);
+        let line = render(format.unwrap_or(FORMAT), status);
+        if self.line.as_ref() != Some(&line) {
+            if let Some(ref path) = self.config.file {
+                // replaced in one go, a status bar never reads half a line
+                let tmp = path.with_extension("tmp");
+                let _ = fs::write(&tmp, format!("{}\n", line)).and_then(|_| fs::rename(&tmp, path));
+            }
+            self.line = Some(line.clone());
+        }
+
+        let changed = Some((status.track_id, status.state.clone()));
+        if self.changed != changed {
+            if let Some(ref command) = self.config.command {
+                run(command, status, &line);
+            }
+            self.changed = changed;
+        }
+    }
+}
+
+impl Drop for NowPlaying {
+    // nothing's playing once scli is gone
+    fn drop(&mut self) {
+        if let Some(ref path) = self.config.file {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Fill in a template's `{title}`, `{artist}`, `{url}`, `{state}`, `{icon}`,
+/// `{elapsed}`, `{duration}` and `{volume}`. Nothing's shown without a track.
+pub fn render(format: &str, status: &Status) -> String {
+    if status.track_id == 0 {
+        return String::new();
+    }
+
+    // one pass over the template, so a title with `{artist}` in it stays as it is
+    let mut line = String::new();
+    let mut rest = format;
+    while let Some(start) = rest.find('{') {
+        line.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = match rest.find('}') {
+            Some(end) => end,
+            None => break,
+        };
+        match value(&rest[1..end], status) {
+            Some(value) => {
+                line.push_str(&value);
+                rest = &rest[end + 1..];
+            }
+            // a stray `{`, there may be a placeholder after it
+            None => {
+                line.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    line.push_str(rest);
+
+    line
+}
+
+// What a placeholder stands for, None for one that isn't known
+fn value(name: &str, status: &Status) -> Option<String> {
+    let value = match name {
+        "title" => status.title.clone(),
+        "artist" => status.username.clone(),
+        "url" => status.url.clone(),
+        "state" => status.state.clone(),
+        "icon" => match status.state.as_str() {
+            "playing" => String::from(">"),
+            "paused" => String::from("||"),
+            _ => String::from("[]"),
+        },
+        "elapsed" => Clock::format(status.position_ms),
+        "duration" => Clock::format(status.duration_ms),
+        "volume" => status.volume.to_string(),
+        _ => return None,
+    };
+
+    Some(value)
+}
+
+// Run the command in the background, with what's playing in its environment
+fn run(command: &str, status: &Status, line: &str) {
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("SCLI_TITLE", &status.title)
+        .env("SCLI_ARTIST", &status.username)
+        .env("SCLI_URL", &status.url)
+        .env("SCLI_STATE", &status.state)
+        .env("SCLI_ELAPSED", Clock::format(status.position_ms))
+        .env("SCLI_DURATION", Clock::format(status.duration_ms))
+        .env("SCLI_LINE", line)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+
+    // waited on elsewhere, so a slow command doesn't hold up playback
+    if let Ok(mut child) = child {
+        thread::spawn(move || child.wait());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    fn status() -> Status {
+        Status {
+            track_id: 1,
+            title: String::from("Art of Minimal Techno"),
+            username: String::from("trippycode"),
+            state: String::from("playing"),
+            position_ms: 61000,
+            duration_ms: 4112000,
+            ..Status::default()
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(
+            render(FORMAT, &status()),
+            "> trippycode - Art of Minimal Techno  01:01 / 01:08:32"
+        );
+        assert_eq!(
+            render("{state}: {title}", &status()),
+            "playing: Art of Minimal Techno"
+        );
+        assert_eq!(render(FORMAT, &Status::default()), "");
+
+        // values go in as they are, placeholders in them and all
+        let status = Status {
+            title: String::from("{artist} tribute {url"),
+            ..status()
+        };
+        assert_eq!(
+            render("{title} by {{artist}} {nope} {", &status),
+            "{artist} tribute {url by {trippycode} {nope} {"
+        );
+    }
+
+    #[test]
+    fn test_publish() {
+        let path = env::temp_dir().join(format!("scli-nowplaying-{}", process::id()));
+        let mut now_playing = NowPlaying::new(config::NowPlaying {
+            file: Some(path.clone()),
+            format: Some(String::from("{title} {elapsed}")),
+            ..config::NowPlaying::default()
+        });
+
+        now_playing.publish(&status());
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "Art of Minimal Techno 01:01\n"
+        );
+
+        // ticking along doesn't count as a change for the command
+        now_playing.publish(&Status {
+            position_ms: 62000,
+            ..status()
+        });
+        assert_eq!(now_playing.changed, Some((1, String::from("playing"))));
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "Art of Minimal Techno 01:02\n"
+        );
+
+        drop(now_playing);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,761 @@
+// Where the audio goes: a speaker, or for machines without one (and tests)
+// nowhere in real time, a WAV file, or raw PCM piped into another program
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::mem;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rodio::{DeviceTrait, Source};
+
+// how often a paused output checks whether to carry on
+const PAUSE_POLL: Duration = Duration::from_millis(20);
+
+/// Which output to use, as given with `--audio`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Kind {
+    Speaker,
+    Null,
+    Wav(PathBuf),
+    Pcm,
+}
+
+impl Kind {
+    /// "speaker", "null", "wav:<file>" or "pcm".
+    pub fn parse(value: &str) -> Option<Kind> {
+        match value {
+            "speaker" => Some(Kind::Speaker),
+            "null" => Some(Kind::Null),
+            "pcm" => Some(Kind::Pcm),
+            _ if value.starts_with("wav:") && value.len() > 4 => {
+                Some(Kind::Wav(PathBuf::from(&value[4..])))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Names of the output devices that can be played on.
+pub fn devices() -> Result<Vec<String>, failure::Error> {
+    let devices = rodio::output_devices()
+        .map_err(|e| failure::format_err!("can't list output devices: {}", e))?;
+    Ok(devices.filter_map(|device| device.name().ok()).collect())
+}
+
+/// Name of the device played on unless another is picked.
+pub fn default_device() -> Option<String> {
+    rodio::default_output_device().and_then(|device| device.name().ok())
+}
+
+/// Lives as long as the player, handing each track a `Sink` to play into.
+pub enum Output {
+    Speaker {
+        device: rodio::Device,
+        // when one other than the default was picked
+        name: Option<String>,
+    },
+    Null,
+    Wav(Arc<Mutex<Wav>>),
+    Pcm(Arc<Mutex<Pcm>>),
+}
+
+impl Output {
+    /// Open an output, `device` being the speaker to play on.
+    pub fn open(kind: &Kind, device: Option<&str>) -> Result<Output, failure::Error> {
+        match kind {
+            Kind::Speaker => Output::speaker(device),
+            Kind::Null => Ok(Output::Null),
+            Kind::Wav(path) => Output::wav(path.clone()),
+            Kind::Pcm => Ok(Output::pcm(Box::new(io::stdout()))),
+        }
+    }
+
+    /// The device called `name`, or the default one.
+    pub fn speaker(name: Option<&str>) -> Result<Output, failure::Error> {
+        let device = match name {
+            Some(name) => rodio::output_devices()
+                .map_err(|e| failure::format_err!("can't list output devices: {}", e))?
+                .find(|device| device.name().map_or(false, |n| n == name))
+                .ok_or_else(|| {
+                    failure::format_err!("no output device called '{}', see `scli devices`", name)
+                })?,
+            None => rodio::default_output_device()
+                .ok_or_else(|| failure::format_err!("no audio output device found"))?,
+        };
+
+        Ok(Output::Speaker {
+            device,
+            name: name.map(String::from),
+        })
+    }
+
+    /// Every track, one after the other, into one file.
+    pub fn wav(path: PathBuf) -> Result<Output, failure::Error> {
+        // the format isn't known until the first track starts,
+        // but a path that can't be written to should fail now
+        File::create(&path)
+            .map_err(|e| failure::format_err!("can't write to {}: {}", path.display(), e))?;
+        Ok(Output::Wav(Arc::new(Mutex::new(Wav {
+            path,
+            spec: None,
+            writer: None,
+            finished: false,
+            error: None,
+        }))))
+    }
+
+    /// Signed 16 bit little endian samples, interleaved, e.g. into
+    /// `aplay -f cd` or `ffmpeg -f s16le -ar 44100 -ac 2 -i -`.
+    pub fn pcm(writer: Box<dyn Write + Send>) -> Output {
+        Output::Pcm(Arc::new(Mutex::new(Pcm {
+            writer,
+            spec: None,
+            error: None,
+        })))
+    }
+
+    /// Name of the speaker playing, when one other than the default was picked.
+    pub fn device(&self) -> Option<&str> {
+        match self {
+            Output::Speaker { name, .. } => name.as_ref().map(String::as_str),
+            _ => None,
+        }
+    }
+
+    /// Start playing `source` at `volume`, from 0 to 1.
+    pub fn play<S>(&self, source: S, volume: f32) -> Sink
+    where
+        S: Source<Item = i16> + Send + 'static,
+    {
+        match self {
+            Output::Speaker { device, .. } => {
+                let sink = rodio::Sink::new(device);
+                sink.set_volume(volume);
+                sink.append(source);
+                Sink::Speaker(sink)
+            }
+            // nothing's waiting on it, so it's paced like a speaker
+            Output::Null => Sink::Worker(Worker::spawn(source, volume, true, |_, _, _| Ok(()))),
+            // as fast as it can be written, a pipe slows it down as much as it needs
+            Output::Wav(wav) => {
+                let source = Convert::first(source, &mut wav.lock().unwrap().spec);
+                let wav = Arc::clone(wav);
+                Sink::Worker(Worker::spawn(
+                    source,
+                    volume,
+                    false,
+                    move |samples, channels, rate| {
+                        wav.lock().unwrap().write(samples, channels, rate)
+                    },
+                ))
+            }
+            Output::Pcm(pcm) => {
+                let source = Convert::first(source, &mut pcm.lock().unwrap().spec);
+                let pcm = Arc::clone(pcm);
+                Sink::Worker(Worker::spawn(
+                    source,
+                    volume,
+                    false,
+                    move |samples, _, _| pcm.lock().unwrap().write(samples),
+                ))
+            }
+        }
+    }
+
+    /// Finish writing, so a file is complete before quitting, or say why
+    /// the audio couldn't all be written.
+    pub fn finish(&self) -> Result<(), failure::Error> {
+        match self {
+            Output::Wav(wav) => wav.lock().unwrap().finish(),
+            Output::Pcm(pcm) => match pcm.lock().unwrap().error {
+                Some(ref e) => failure::bail!("can't write the audio out: {}", e),
+                None => Ok(()),
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+// Raw samples have no header to say what they are, so every track is
+// converted to the channels and rate of the first, like a file's
+pub struct Pcm {
+    writer: Box<dyn Write + Send>,
+    spec: Option<(u16, u32)>,
+    // the write that failed, once one has nothing more is written
+    error: Option<String>,
+}
+
+impl Pcm {
+    fn write(&mut self, samples: &[i16]) -> io::Result<()> {
+        if let Some(ref e) = self.error {
+            return Err(io::Error::new(io::ErrorKind::Other, e.clone()));
+        }
+        let bytes: Vec<u8> = samples
+            .iter()
+            .flat_map(|s| s.to_le_bytes().to_vec())
+            .collect();
+        let result = self
+            .writer
+            .write_all(&bytes)
+            .and_then(|_| self.writer.flush());
+        if let Err(ref e) = result {
+            self.error = Some(e.to_string());
+        }
+        result
+    }
+}
+
+pub struct Wav {
+    path: PathBuf,
+    // channels and rate, of the first track to play
+    spec: Option<(u16, u32)>,
+    writer: Option<hound::WavWriter<BufWriter<File>>>,
+    finished: bool,
+    // the write that failed, once one has nothing more is written
+    error: Option<String>,
+}
+
+impl Wav {
+    fn write(&mut self, samples: &[i16], channels: u16, rate: u32) -> io::Result<()> {
+        if self.finished {
+            return Err(io::Error::new(io::ErrorKind::Other, "finished writing"));
+        }
+        if let Some(ref e) = self.error {
+            return Err(io::Error::new(io::ErrorKind::Other, e.clone()));
+        }
+        let result = self.append(samples, channels, rate);
+        if let Err(ref e) = result {
+            self.error = Some(e.to_string());
+        }
+        result
+    }
+
+    fn append(&mut self, samples: &[i16], channels: u16, rate: u32) -> io::Result<()> {
+        if self.writer.is_none() {
+            let spec = hound::WavSpec {
+                channels,
+                sample_rate: rate,
+                bits_per_sample: 16,
+                sample_format: hound::SampleFormat::Int,
+            };
+            self.writer = Some(hound::WavWriter::create(&self.path, spec).map_err(wav_error)?);
+        }
+
+        let writer = self.writer.as_mut().unwrap();
+        for &sample in samples {
+            writer.write_sample(sample).map_err(wav_error)?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), failure::Error> {
+        self.finished = true;
+        if let Some(ref e) = self.error {
+            failure::bail!("can't write to {}: {}", self.path.display(), e);
+        }
+        if let Some(writer) = self.writer.take() {
+            writer.finalize()?;
+        }
+        Ok(())
+    }
+}
+
+fn wav_error(e: hound::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, e.to_string())
+}
+
+/// A source at another number of channels and sample rate, so tracks can
+/// follow one another into the same file whatever they were uploaded as.
+pub struct Convert<S>
+where
+    S: Source<Item = i16>,
+{
+    input: S,
+    channels: u16,
+    rate: u32,
+    // the input frames either side of the next output frame, already at
+    // the output's channels, and how far between them it is
+    previous: Vec<f32>,
+    next: Vec<f32>,
+    position: f64,
+    // past the last input frame, which is held until the time it lasts is up
+    ended: bool,
+    frame: Vec<i16>,
+    emitted: usize,
+}
+
+impl<S> Convert<S>
+where
+    S: Source<Item = i16>,
+{
+    pub fn new(input: S, channels: u16, rate: u32) -> Convert<S> {
+        Convert {
+            input,
+            channels,
+            rate,
+            previous: Vec::new(),
+            next: Vec::new(),
+            position: 0.0,
+            ended: false,
+            frame: Vec::new(),
+            emitted: 0,
+        }
+    }
+
+    // Converted to whatever the first source through `spec` was
+    fn first(input: S, spec: &mut Option<(u16, u32)>) -> Convert<S> {
+        let (channels, rate) = *spec.get_or_insert((input.channels(), input.sample_rate()));
+        Convert::new(input, channels, rate)
+    }
+
+    fn is_same(&self) -> bool {
+        self.input.channels() == self.channels && self.input.sample_rate() == self.rate
+    }
+
+    // The next input frame, mixed down or spread out to the output's channels
+    fn read(&mut self) -> Option<Vec<f32>> {
+        let from = self.input.channels().max(1) as usize;
+        let mut frame = Vec::with_capacity(from);
+        for _ in 0..from {
+            frame.push(self.input.next()? as f32);
+        }
+
+        let to = self.channels as usize;
+        Some(if to == 1 {
+            vec![frame.iter().sum::<f32>() / from as f32]
+        } else {
+            (0..to).map(|c| frame[c % from]).collect()
+        })
+    }
+
+    // The next output frame, between two input frames
+    fn step(&mut self) -> bool {
+        if self.next.is_empty() {
+            match self.read() {
+                Some(first) => self.next = first,
+                None => return false,
+            }
+            self.position = 1.0;
+        }
+        while self.position >= 1.0 {
+            match self.read() {
+                Some(next) => self.previous = mem::replace(&mut self.next, next),
+                None if !self.ended => {
+                    self.ended = true;
+                    self.previous = self.next.clone();
+                }
+                None => return false,
+            }
+            self.position -= 1.0;
+        }
+
+        let t = self.position as f32;
+        self.frame.clear();
+        for (previous, next) in self.previous.iter().zip(&self.next) {
+            self.frame
+                .push((previous + (next - previous) * t).round() as i16);
+        }
+        self.emitted = 0;
+        self.position += self.input.sample_rate() as f64 / self.rate as f64;
+        true
+    }
+}
+
+impl<S> Iterator for Convert<S>
+where
+    S: Source<Item = i16>,
+{
+    type Item = i16;
+
+    #[inline]
+    fn next(&mut self) -> Option<i16> {
+        if self.is_same() {
+            return self.input.next();
+        }
+
+        if self.emitted == self.frame.len() && !self.step() {
+            return None;
+        }
+        let sample = self.frame[self.emitted];
+        self.emitted += 1;
+        Some(sample)
+    }
+}
+
+impl<S> Source for Convert<S>
+where
+    S: Source<Item = i16>,
+{
+    #[inline]
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    #[inline]
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> u32 {
+        self.rate
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
+
+/// One track playing, on whichever output.
+pub enum Sink {
+    Speaker(rodio::Sink),
+    Worker(Worker),
+}
+
+impl Sink {
+    pub fn play(&self) {
+        match self {
+            Sink::Speaker(sink) => sink.play(),
+            Sink::Worker(worker) => worker.shared.paused.store(false, Ordering::SeqCst),
+        }
+    }
+
+    pub fn pause(&self) {
+        match self {
+            Sink::Speaker(sink) => sink.pause(),
+            Sink::Worker(worker) => worker.shared.paused.store(true, Ordering::SeqCst),
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        match self {
+            Sink::Speaker(sink) => sink.is_paused(),
+            Sink::Worker(worker) => worker.shared.paused.load(Ordering::SeqCst),
+        }
+    }
+
+    pub fn set_volume(&self, volume: f32) {
+        match self {
+            Sink::Speaker(sink) => sink.set_volume(volume),
+            Sink::Worker(worker) => *worker.shared.volume.lock().unwrap() = volume,
+        }
+    }
+
+    /// Whether the track has played to the end, or why it was cut short.
+    pub fn empty(&self) -> io::Result<bool> {
+        match self {
+            Sink::Speaker(sink) => Ok(sink.empty()),
+            Sink::Worker(worker) => match *worker.shared.error.lock().unwrap() {
+                Some(ref e) => Err(io::Error::new(e.kind(), e.to_string())),
+                None => Ok(worker.shared.done.load(Ordering::SeqCst)),
+            },
+        }
+    }
+}
+
+// What the player and the thread playing a track share
+#[derive(Default)]
+struct Shared {
+    paused: AtomicBool,
+    stopped: AtomicBool,
+    done: AtomicBool,
+    volume: Mutex<f32>,
+    // why writing stopped before the end
+    error: Mutex<Option<io::Error>>,
+}
+
+/// A thread writing a track's samples out, for anything but a speaker.
+pub struct Worker {
+    shared: Arc<Shared>,
+}
+
+impl Worker {
+    fn spawn<S, F>(mut source: S, volume: f32, realtime: bool, mut write: F) -> Worker
+    where
+        S: Source<Item = i16> + Send + 'static,
+        F: FnMut(&[i16], u16, u32) -> io::Result<()> + Send + 'static,
+    {
+        let shared = Arc::new(Shared {
+            volume: Mutex::new(volume),
+            ..Shared::default()
+        });
+        let worker = Worker {
+            shared: Arc::clone(&shared),
+        };
+
+        thread::spawn(move || {
+            let channels = source.channels();
+            let rate = source.sample_rate();
+            // 10ms at a time
+            let chunk = (rate as usize / 100).max(1) * channels as usize;
+            let mut samples = Vec::with_capacity(chunk);
+            // when pacing started, and how many frames were written since
+            let mut started = Instant::now();
+            let mut frames = 0u64;
+
+            while !shared.stopped.load(Ordering::SeqCst) {
+                if shared.paused.load(Ordering::SeqCst) {
+                    thread::sleep(PAUSE_POLL);
+                    started = Instant::now();
+                    frames = 0;
+                    continue;
+                }
+
+                let volume = *shared.volume.lock().unwrap();
+                samples.clear();
+                samples.extend(
+                    source
+                        .by_ref()
+                        .take(chunk)
+                        .map(|sample| (sample as f32 * volume) as i16),
+                );
+                if samples.is_empty() {
+                    break;
+                }
+                if let Err(e) = write(&samples, channels, rate) {
+                    *shared.error.lock().unwrap() = Some(e);
+                    break;
+                }
+
+                if realtime {
+                    frames += (samples.len() / channels as usize) as u64;
+                    let due = Duration::from_micros(frames * 1_000_000 / rate as u64);
+                    let elapsed = started.elapsed();
+                    if due > elapsed {
+                        thread::sleep(due - elapsed);
+                    }
+                }
+            }
+
+            shared.done.store(true, Ordering::SeqCst);
+        });
+
+        worker
+    }
+}
+
+impl Drop for Worker {
+    // like a speaker's sink, the track stops once it's dropped
+    fn drop(&mut self) {
+        self.shared.stopped.store(true, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    // Some stereo samples, at 44.1kHz unless given
+    struct Tone {
+        samples: std::vec::IntoIter<i16>,
+        channels: u16,
+        rate: u32,
+    }
+
+    impl Tone {
+        fn new(frames: usize) -> Tone {
+            Tone::with_spec(frames, 2, 44100)
+        }
+
+        fn with_spec(frames: usize, channels: u16, rate: u32) -> Tone {
+            let samples: Vec<i16> = (0..frames * channels as usize)
+                .map(|i| (i % 1000) as i16)
+                .collect();
+            Tone {
+                samples: samples.into_iter(),
+                channels,
+                rate,
+            }
+        }
+    }
+
+    impl Iterator for Tone {
+        type Item = i16;
+
+        fn next(&mut self) -> Option<i16> {
+            self.samples.next()
+        }
+    }
+
+    impl Source for Tone {
+        fn current_frame_len(&self) -> Option<usize> {
+            None
+        }
+
+        fn channels(&self) -> u16 {
+            self.channels
+        }
+
+        fn sample_rate(&self) -> u32 {
+            self.rate
+        }
+
+        fn total_duration(&self) -> Option<Duration> {
+            None
+        }
+    }
+
+    // Wait for a sink to play to the end
+    fn finished(sink: &Sink) -> bool {
+        (0..200).any(|_| {
+            thread::sleep(Duration::from_millis(10));
+            sink.empty().unwrap()
+        })
+    }
+
+    // Collects what's written, to be read back
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(bytes)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Kind::parse("null"), Some(Kind::Null));
+        assert_eq!(
+            Kind::parse("wav:mix.wav"),
+            Some(Kind::Wav(PathBuf::from("mix.wav")))
+        );
+        assert_eq!(Kind::parse("wav:"), None);
+        assert_eq!(Kind::parse("flac"), None);
+    }
+
+    #[test]
+    fn test_null() {
+        // a tenth of a second takes about that long
+        let started = Instant::now();
+        let sink = Output::Null.play(Tone::new(4410), 1.0);
+        assert!(!sink.empty().unwrap());
+        assert!(finished(&sink));
+        assert!(started.elapsed() >= Duration::from_millis(90));
+
+        // nothing plays while paused
+        let sink = Output::Null.play(Tone::new(4410), 1.0);
+        sink.pause();
+        thread::sleep(Duration::from_millis(200));
+        assert!(!sink.empty().unwrap());
+        sink.play();
+        assert!(finished(&sink));
+    }
+
+    #[test]
+    fn test_pcm() {
+        let buffer = Buffer::default();
+        let output = Output::pcm(Box::new(buffer.clone()));
+        let sink = output.play(Tone::new(100), 0.5);
+        assert!(finished(&sink));
+
+        let bytes = buffer.0.lock().unwrap();
+        assert_eq!(bytes.len(), 100 * 2 * 2);
+        // the third sample, 2 at half volume
+        assert_eq!(&bytes[4..6], &1i16.to_le_bytes());
+    }
+
+    // Turns every write down, like a pipe with nothing reading it
+    struct Broken;
+
+    impl Write for Broken {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, "broken pipe"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_broken() {
+        let output = Output::pcm(Box::new(Broken));
+        let sink = output.play(Tone::new(100), 1.0);
+        assert!((0..200).any(|_| {
+            thread::sleep(Duration::from_millis(10));
+            sink.empty().is_err()
+        }));
+        assert_eq!(
+            output.finish().unwrap_err().to_string(),
+            "can't write the audio out: broken pipe"
+        );
+    }
+
+    #[test]
+    fn test_wav() {
+        let path = env::temp_dir().join(format!("scli-output-{}.wav", process::id()));
+        let output = Output::wav(path.clone()).unwrap();
+
+        // one track after another, all in the same file
+        assert!(finished(&output.play(Tone::new(1000), 1.0)));
+        assert!(finished(&output.play(Tone::new(500), 1.0)));
+        output.finish().unwrap();
+
+        let reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.spec().channels, 2);
+        assert_eq!(reader.spec().sample_rate, 44100);
+        assert_eq!(reader.len(), 1500 * 2);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_spec_change() {
+        let path = env::temp_dir().join(format!("scli-spec-{}.wav", process::id()));
+        let output = Output::wav(path.clone()).unwrap();
+
+        // a second of each, the second brought to the first's rate and channels
+        assert!(finished(
+            &output.play(Tone::with_spec(44100, 2, 44100), 1.0)
+        ));
+        assert!(finished(
+            &output.play(Tone::with_spec(48000, 1, 48000), 1.0)
+        ));
+        output.finish().unwrap();
+
+        let reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.spec().channels, 2);
+        assert_eq!(reader.spec().sample_rate, 44100);
+        let frames = reader.len() as i64 / 2;
+        assert!((frames - 88200).abs() <= 2, "{} frames", frames);
+        fs::remove_file(path).unwrap();
+
+        // the same for raw samples, which can't say they've changed
+        let buffer = Buffer::default();
+        let output = Output::pcm(Box::new(buffer.clone()));
+        assert!(finished(&output.play(Tone::with_spec(100, 1, 22050), 1.0)));
+        assert!(finished(&output.play(Tone::with_spec(200, 2, 44100), 1.0)));
+        let frames = buffer.0.lock().unwrap().len() as i64 / 2;
+        assert!((frames - 200).abs() <= 2, "{} frames", frames);
+    }
+
+    #[test]
+    fn test_convert() {
+        // the same samples back when there's nothing to do
+        let samples: Vec<i16> = Tone::new(100).collect();
+        assert_eq!(
+            Convert::new(Tone::new(100), 2, 44100).collect::<Vec<_>>(),
+            samples
+        );
+
+        // mono spread to both channels, at twice the rate halfway in between
+        let stereo: Vec<i16> = Convert::new(Tone::with_spec(4, 1, 22050), 2, 44100).collect();
+        assert_eq!(stereo, vec![0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 3, 3]);
+
+        // stereo mixed down
+        let mono: Vec<i16> = Convert::new(Tone::with_spec(3, 2, 44100), 1, 44100).collect();
+        assert_eq!(mono, vec![1, 3, 5]);
+    }
+}
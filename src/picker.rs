@@ -0,0 +1,64 @@
+// A list of items with one selected, for the panes that
+// browse something (history, bookmarks) and act on the selection
+pub struct Picker<T> {
+    items: Vec<T>,
+    selected: usize,
+}
+
+impl<T> Picker<T> {
+    pub fn new(items: Vec<T>) -> Picker<T> {
+        Picker { items, selected: 0 }
+    }
+
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    pub fn selected(&self) -> Option<&T> {
+        self.items.get(self.selected)
+    }
+
+    pub fn index(&self) -> usize {
+        self.selected
+    }
+
+    /// Select the item at `index`, or the last one if there aren't that many.
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.items.len().saturating_sub(1));
+    }
+
+    pub fn up(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn down(&mut self) {
+        if self.selected + 1 < self.items.len() {
+            self.selected += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_picker() {
+        let mut picker = Picker::new(vec!['a', 'b', 'c']);
+        assert_eq!(picker.selected(), Some(&'a'));
+        picker.up();
+        assert_eq!(picker.selected(), Some(&'a'));
+        picker.down();
+        picker.down();
+        picker.down();
+        assert_eq!(picker.selected(), Some(&'c'));
+        assert_eq!(picker.index(), 2);
+
+        picker.select(1);
+        assert_eq!(picker.selected(), Some(&'b'));
+        picker.select(5);
+        assert_eq!(picker.selected(), Some(&'c'));
+
+        assert_eq!(Picker::<char>::new(Vec::new()).selected(), None);
+    }
+}
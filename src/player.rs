@@ -0,0 +1,195 @@
+use reqwest::StatusCode;
+use rodio::Source;
+use std::io::{self, BufReader};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::decoder;
+use crate::output::{Output, Sink};
+use crate::record;
+use crate::soundcloud;
+use crate::stretch;
+use crate::tap;
+
+// how much faster or slower each step is
+const SPEED_STEP: f32 = 0.25;
+
+pub struct Player {
+    duration: u32,
+    // where in the track playback started, in ms
+    start: u32,
+    audio: Sink,
+    timer: Arc<Mutex<Duration>>,
+    speed: Arc<Mutex<f32>>,
+    state: PlayerState,
+    progress: f32,
+    volume: u8,
+    // why the output stopped taking the track, if it did
+    error: Option<io::Error>,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum PlayerState {
+    Playing,
+    Paused,
+    Stopped,
+}
+
+pub enum PlayerEvent {
+    Tick,
+    PlayPause,
+    VolumeUp,
+    VolumeDown,
+    // set the volume outright, 0 to 100
+    Volume(u8),
+    Faster,
+    Slower,
+    // set the speed outright, 0.5 to 3
+    Speed(f32),
+}
+
+impl Player {
+    /// Start playing a track `start_ms` into it on `output`, copying its
+    /// samples into `samples` for visualization and to `recorder` when
+    /// it's recording. The volume carries over from the previous track.
+    pub fn new(
+        track: &soundcloud::Track,
+        samples: tap::Buffer,
+        recorder: record::Recorder,
+        volume: u8,
+        start_ms: u32,
+        output: &Output,
+    ) -> Result<Player, failure::Error> {
+        // resolve and decode stream
+        let client = soundcloud::Client::new();
+        let stream = client.stream(&track.stream_url, start_ms)?;
+        // a server that ignores the range sends the whole track, which
+        // has to be decoded up to the start instead
+        let partial = stream.status() == StatusCode::PARTIAL_CONTENT;
+        let mut source = decoder::Mp3Decoder::new(BufReader::new(stream))
+            .map_err(|_| failure::format_err!("can't decode {}", track.title))?;
+        if start_ms > 0 && !partial {
+            source.seek(start_ms);
+        }
+
+        // timed before it's sped up or slowed down, so it's time in the track
+        let timer = Arc::new(Mutex::new(Duration::from_secs(0)));
+        let with_elapsed = source.buffered().elapsed(Arc::clone(&timer));
+
+        // recorded as the track is, then stretched to the speed it plays at
+        let recorded = record::Tee::new(with_elapsed, recorder);
+        let speed = Arc::new(Mutex::new(1.0));
+        let stretched = stretch::Stretch::new(recorded, Arc::clone(&speed));
+
+        // tap the samples on their way out, for visualization
+        let tapped = tap::Tap::new(stretched, samples);
+
+        let sink = output.play(tapped, volume as f32 / 100.0);
+
+        Ok(Player {
+            duration: track.duration,
+            start: start_ms,
+            audio: sink,
+            timer: timer,
+            speed: speed,
+            state: PlayerState::Playing,
+            progress: 0.0,
+            volume: volume,
+            error: None,
+        })
+    }
+
+    pub fn update(&mut self, msg: PlayerEvent) {
+        match msg {
+            PlayerEvent::Tick => match self.audio.empty() {
+                Ok(true) => self.state = PlayerState::Stopped,
+                Ok(false) => {
+                    if self.state == PlayerState::Stopped {
+                        self.progress = 0.0;
+                    } else {
+                        self.progress = (self.elapsed() as f32 / self.duration as f32) * 100.0;
+                    }
+                }
+                Err(e) => {
+                    self.state = PlayerState::Stopped;
+                    self.error = Some(e);
+                }
+            },
+            PlayerEvent::PlayPause => {
+                if self.audio.is_paused() {
+                    self.audio.play();
+                    self.state = PlayerState::Playing;
+                } else {
+                    self.audio.pause();
+                    self.state = PlayerState::Paused;
+                }
+            }
+            PlayerEvent::VolumeUp => {
+                if self.volume == 100 {
+                    return;
+                }
+
+                self.volume = self.volume + 1;
+                self.audio.set_volume(self.volume as f32 / 100.0);
+            }
+            PlayerEvent::VolumeDown => {
+                if self.volume == 0 {
+                    return;
+                }
+
+                self.volume = self.volume - 1;
+                self.audio.set_volume(self.volume as f32 / 100.0);
+            }
+            PlayerEvent::Volume(volume) => {
+                self.volume = volume.min(100);
+                self.audio.set_volume(self.volume as f32 / 100.0);
+            }
+            // in steps, so stepping back and forth lands on 1x again, even
+            // from a speed that was given exactly
+            PlayerEvent::Faster => {
+                let speed = ((self.speed() / SPEED_STEP).round() + 1.0) * SPEED_STEP;
+                self.update(PlayerEvent::Speed(speed));
+            }
+            PlayerEvent::Slower => {
+                let speed = ((self.speed() / SPEED_STEP).round() - 1.0) * SPEED_STEP;
+                self.update(PlayerEvent::Speed(speed));
+            }
+            PlayerEvent::Speed(speed) => {
+                *self.speed.lock().unwrap() = speed.max(stretch::MIN_SPEED).min(stretch::MAX_SPEED);
+            }
+        }
+    }
+
+    pub fn state(&self) -> PlayerState {
+        self.state
+    }
+
+    /// Why the output stopped taking the track before the end.
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    pub fn progress(&self) -> f32 {
+        self.progress
+    }
+
+    pub fn volume(&self) -> u8 {
+        self.volume
+    }
+
+    /// How fast the track plays, 1 being as it was made.
+    pub fn speed(&self) -> f32 {
+        *self.speed.lock().unwrap()
+    }
+
+    /// Position in the track, in ms
+    pub fn elapsed(&self) -> u32 {
+        self.start + self.played()
+    }
+
+    /// How much of the track this player has played, in ms
+    pub fn played(&self) -> u32 {
+        let val = *self.timer.lock().unwrap();
+        val.as_millis() as u32
+    }
+}
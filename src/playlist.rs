@@ -0,0 +1,330 @@
+// Playlist files, so queues, history and bookmarks can be shared with other
+// players: M3U8 and PLS with SoundCloud permalinks, or JSON with everything
+use std::fs;
+use std::path::Path;
+
+use crate::bookmarks;
+use crate::history;
+use crate::soundcloud;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+    M3u,
+    Pls,
+    Json,
+}
+
+impl Format {
+    /// Format for a file, going by its extension.
+    pub fn from_path(path: &Path) -> Option<Format> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "m3u" | "m3u8" => Some(Format::M3u),
+            "pls" => Some(Format::Pls),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Entry {
+    pub url: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub username: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub track_id: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u32>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub genre: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Position in the track, for bookmarks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position_ms: Option<u32>,
+}
+
+impl Entry {
+    // `username - title`, as most players show it
+    fn display(&self) -> String {
+        if self.username.is_empty() {
+            self.title.clone()
+        } else {
+            format!("{} - {}", self.username, self.title)
+        }
+    }
+
+    // Inverse of `display`, a guess at best for files from elsewhere
+    fn set_display(&mut self, display: &str) {
+        match display.find(" - ") {
+            Some(i) => {
+                self.username = display[..i].to_string();
+                self.title = display[i + 3..].to_string();
+            }
+            None => self.title = display.to_string(),
+        }
+    }
+}
+
+impl<'a> From<&'a soundcloud::Track> for Entry {
+    fn from(track: &soundcloud::Track) -> Entry {
+        Entry {
+            url: track.permalink_url.clone(),
+            title: track.title.clone(),
+            username: track.user.username.clone(),
+            track_id: Some(track.id),
+            duration_ms: Some(track.duration),
+            genre: track.genre.clone(),
+            tags: track.tags(),
+            position_ms: None,
+        }
+    }
+}
+
+impl<'a> From<&'a history::Entry> for Entry {
+    fn from(entry: &history::Entry) -> Entry {
+        Entry {
+            url: entry.url.clone(),
+            title: entry.title.clone(),
+            username: entry.username.clone(),
+            track_id: Some(entry.track_id),
+            ..Entry::default()
+        }
+    }
+}
+
+impl<'a> From<&'a bookmarks::Bookmark> for Entry {
+    fn from(bookmark: &bookmarks::Bookmark) -> Entry {
+        Entry {
+            url: bookmark.url.clone(),
+            title: bookmark.title.clone(),
+            username: bookmark.username.clone(),
+            track_id: Some(bookmark.track_id),
+            tags: bookmark.tags.clone(),
+            position_ms: bookmark.position,
+            ..Entry::default()
+        }
+    }
+}
+
+pub fn export(path: &Path, entries: &[Entry]) -> Result<(), failure::Error> {
+    let format = format(path)?;
+    fs::write(path, write(format, entries)?)?;
+    Ok(())
+}
+
+pub fn import(path: &Path) -> Result<Vec<Entry>, failure::Error> {
+    let format = format(path)?;
+    let contents = fs::read_to_string(path)?;
+    parse(format, &contents)
+        .map_err(|e| failure::format_err!("invalid playlist {}: {}", path.display(), e))
+}
+
+fn format(path: &Path) -> Result<Format, failure::Error> {
+    Format::from_path(path).ok_or_else(|| {
+        failure::format_err!(
+            "unknown playlist format {}, use .m3u8, .pls or .json",
+            path.display()
+        )
+    })
+}
+
+// A whole number of seconds in ms, None when it isn't one or doesn't fit
+fn milliseconds(seconds: &str) -> Option<u32> {
+    seconds.parse::<u32>().ok()?.checked_mul(1000)
+}
+
+pub fn write(format: Format, entries: &[Entry]) -> Result<String, failure::Error> {
+    let mut out = String::new();
+    match format {
+        Format::M3u => {
+            out.push_str("#EXTM3U\n");
+            for entry in entries {
+                let seconds = entry.duration_ms.map(|ms| (ms / 1000) as i64).unwrap_or(-1);
+                out.push_str(&format!("#EXTINF:{},{}\n", seconds, entry.display()));
+                out.push_str(&format!("{}\n", entry.url));
+            }
+        }
+        Format::Pls => {
+            out.push_str("[playlist]\n");
+            for (i, entry) in entries.iter().enumerate() {
+                let n = i + 1;
+                let seconds = entry.duration_ms.map(|ms| (ms / 1000) as i64).unwrap_or(-1);
+                out.push_str(&format!("File{}={}\n", n, entry.url));
+                out.push_str(&format!("Title{}={}\n", n, entry.display()));
+                out.push_str(&format!("Length{}={}\n", n, seconds));
+            }
+            out.push_str(&format!("NumberOfEntries={}\nVersion=2\n", entries.len()));
+        }
+        Format::Json => {
+            out = serde_json::to_string_pretty(entries)?;
+            out.push('\n');
+        }
+    }
+
+    Ok(out)
+}
+
+pub fn parse(format: Format, contents: &str) -> Result<Vec<Entry>, failure::Error> {
+    match format {
+        Format::M3u => {
+            let mut entries = Vec::new();
+            let mut next = Entry::default();
+            for line in contents.lines().map(str::trim) {
+                if line.starts_with("#EXTINF:") {
+                    // #EXTINF:<seconds>,<display title>
+                    let info = &line[8..];
+                    let (seconds, display) = match info.find(',') {
+                        Some(i) => (&info[..i], &info[i + 1..]),
+                        None => (info, ""),
+                    };
+                    next.duration_ms = milliseconds(seconds.trim());
+                    next.set_display(display);
+                } else if !line.is_empty() && !line.starts_with('#') {
+                    next.url = line.to_string();
+                    entries.push(next);
+                    next = Entry::default();
+                }
+            }
+            Ok(entries)
+        }
+        Format::Pls => {
+            // keys are numbered, and not necessarily in order
+            let mut entries: Vec<(usize, Entry)> = Vec::new();
+            for line in contents.lines().map(str::trim) {
+                let (key, value) = match line.find('=') {
+                    Some(i) => (&line[..i], &line[i + 1..]),
+                    None => continue,
+                };
+                let split = key.find(|c: char| c.is_ascii_digit()).unwrap_or(key.len());
+                let n = match key[split..].parse::<usize>() {
+                    Ok(n) => n,
+                    Err(_) => continue,
+                };
+
+                let index = match entries.iter().position(|e| e.0 == n) {
+                    Some(index) => index,
+                    None => {
+                        entries.push((n, Entry::default()));
+                        entries.len() - 1
+                    }
+                };
+                let entry = &mut entries[index].1;
+                match key[..split].to_lowercase().as_str() {
+                    "file" => entry.url = value.to_string(),
+                    "title" => entry.set_display(value),
+                    "length" => entry.duration_ms = milliseconds(value),
+                    _ => {}
+                }
+            }
+
+            entries.sort_by_key(|e| e.0);
+            Ok(entries
+                .into_iter()
+                .map(|e| e.1)
+                .filter(|e| !e.url.is_empty())
+                .collect())
+        }
+        Format::Json => Ok(serde_json::from_str(contents)?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn entries() -> Vec<Entry> {
+        vec![
+            Entry {
+                url: String::from("https://soundcloud.com/trippycode/art-of-minimal"),
+                title: String::from("Art of Minimal Techno"),
+                username: String::from("trippycode"),
+                track_id: Some(1),
+                duration_ms: Some(4112000),
+                genre: String::from("Techno"),
+                tags: vec![String::from("minimal techno")],
+                position_ms: Some(2590000),
+            },
+            Entry {
+                url: String::from("https://soundcloud.com/someone/untitled"),
+                title: String::from("untitled"),
+                ..Entry::default()
+            },
+        ]
+    }
+
+    #[test]
+    fn test_format() {
+        let format = |path: &str| Format::from_path(&PathBuf::from(path));
+        assert_eq!(format("queue.m3u8"), Some(Format::M3u));
+        assert_eq!(format("queue.M3U"), Some(Format::M3u));
+        assert_eq!(format("queue.pls"), Some(Format::Pls));
+        assert_eq!(format("/tmp/queue.json"), Some(Format::Json));
+        assert_eq!(format("queue.txt"), None);
+        assert_eq!(format("queue"), None);
+    }
+
+    #[test]
+    fn test_m3u() {
+        let written = write(Format::M3u, &entries()).unwrap();
+        assert_eq!(
+            written,
+            "#EXTM3U\n\
+             #EXTINF:4112,trippycode - Art of Minimal Techno\n\
+             https://soundcloud.com/trippycode/art-of-minimal\n\
+             #EXTINF:-1,untitled\n\
+             https://soundcloud.com/someone/untitled\n"
+        );
+
+        // only what the format can hold survives the round trip
+        let parsed = parse(Format::M3u, &written).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].url, entries()[0].url);
+        assert_eq!(parsed[0].username, "trippycode");
+        assert_eq!(parsed[0].duration_ms, Some(4112000));
+        assert_eq!(parsed[1].title, "untitled");
+        assert_eq!(parsed[1].duration_ms, None);
+
+        // plain lists, as other players write them
+        let parsed = parse(Format::M3u, "# comment\n\nhttps://a\r\nhttps://b\n").unwrap();
+        let urls: Vec<&str> = parsed.iter().map(|e| e.url.as_str()).collect();
+        assert_eq!(urls, vec!["https://a", "https://b"]);
+
+        // too long to count in ms, as good as no length
+        let parsed = parse(Format::M3u, "#EXTINF:4294968,long\nhttps://a\n").unwrap();
+        assert_eq!(parsed[0].duration_ms, None);
+        assert_eq!(parsed[0].title, "long");
+    }
+
+    #[test]
+    fn test_pls() {
+        let written = write(Format::Pls, &entries()).unwrap();
+        assert!(written.starts_with("[playlist]\nFile1=https://soundcloud.com/trippycode/"));
+        assert!(written.ends_with("NumberOfEntries=2\nVersion=2\n"));
+
+        let parsed = parse(Format::Pls, &written).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].title, "Art of Minimal Techno");
+        assert_eq!(parsed[1].url, entries()[1].url);
+
+        let parsed = parse(
+            Format::Pls,
+            "[playlist]\nFile2=https://b\nFile1=https://a\nTitle1=a\nLength2=4294968\n",
+        )
+        .unwrap();
+        let urls: Vec<&str> = parsed.iter().map(|e| e.url.as_str()).collect();
+        assert_eq!(urls, vec!["https://a", "https://b"]);
+        assert_eq!(parsed[1].duration_ms, None);
+    }
+
+    #[test]
+    fn test_json() {
+        let written = write(Format::Json, &entries()).unwrap();
+        assert_eq!(parse(Format::Json, &written).unwrap(), entries());
+        assert!(parse(Format::Json, "{").is_err());
+    }
+}
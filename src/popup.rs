@@ -0,0 +1,113 @@
+use tui::buffer::Buffer;
+use tui::layout::Rect;
+use tui::style::{Modifier, Style};
+use tui::widgets::{Block, Borders, Widget};
+
+use crate::theme::Theme;
+
+/// A bordered box of text, drawn centered over whatever is underneath it.
+pub struct Popup {
+    pub title: String,
+    pub lines: Vec<String>,
+    pub theme: Theme,
+}
+
+impl Default for Popup {
+    fn default() -> Popup {
+        Popup {
+            title: String::new(),
+            lines: Vec::new(),
+            theme: Theme::default(),
+        }
+    }
+}
+
+impl Popup {
+    pub fn title(&mut self, title: &str) -> &mut Popup {
+        self.title = title.to_string();
+        self
+    }
+
+    pub fn theme(&mut self, theme: Theme) -> &mut Popup {
+        self.theme = theme;
+        self
+    }
+
+    pub fn lines(&mut self, lines: Vec<String>) -> &mut Popup {
+        self.lines = lines;
+        self
+    }
+
+    /// Lines made of two aligned columns, e.g. key and description.
+    pub fn columns(&mut self, rows: &[(String, String)]) -> &mut Popup {
+        let width = rows
+            .iter()
+            .map(|row| row.0.chars().count())
+            .max()
+            .unwrap_or(0);
+        self.lines = rows
+            .iter()
+            .map(|row| format!("{:<width$}  {}", row.0, row.1, width = width))
+            .collect();
+        self
+    }
+
+    /// The area the popup takes up, centered in `area` and clipped to fit.
+    pub fn area(&self, area: Rect) -> Rect {
+        let content = self
+            .lines
+            .iter()
+            .map(|line| line.chars().count())
+            .chain(Some(self.title.chars().count()))
+            .max()
+            .unwrap_or(0) as u16;
+
+        // border and one column of padding on each side
+        let width = (content + 4).min(area.width);
+        let height = (self.lines.len() as u16 + 2).min(area.height);
+        Rect::new(
+            area.left() + (area.width - width) / 2,
+            area.top() + (area.height - height) / 2,
+            width,
+            height,
+        )
+    }
+}
+
+impl Widget for Popup {
+    fn draw(&mut self, area: Rect, buf: &mut Buffer) {
+        let area = self.area(area);
+        if area.width < 2 || area.height < 2 {
+            return;
+        }
+
+        // clear out whatever was drawn underneath
+        for y in area.top()..area.bottom() {
+            for x in area.left()..area.right() {
+                buf.get_mut(x, y).reset();
+            }
+        }
+
+        let mut block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(self.theme.accent()))
+            .title(&self.title)
+            .title_style(Style::default().modifier(Modifier::BOLD));
+        block.draw(area, buf);
+
+        let inner = block.inner(area);
+        if inner.width < 2 {
+            return;
+        }
+
+        for (i, line) in self.lines.iter().take(inner.height as usize).enumerate() {
+            buf.set_stringn(
+                inner.left() + 1,
+                inner.top() + i as u16,
+                line,
+                inner.width as usize - 1,
+                Style::default(),
+            );
+        }
+    }
+}
@@ -0,0 +1,282 @@
+// Play queue with shuffle and repeat, kept apart from audio output
+// so the order tracks play in can be tested on its own
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Repeat {
+    Off,
+    All,
+    One,
+}
+
+impl Default for Repeat {
+    fn default() -> Repeat {
+        Repeat::Off
+    }
+}
+
+impl Repeat {
+    pub fn next(self) -> Repeat {
+        match self {
+            Repeat::Off => Repeat::All,
+            Repeat::All => Repeat::One,
+            Repeat::One => Repeat::Off,
+        }
+    }
+}
+
+pub struct Queue<T> {
+    items: Vec<T>,
+    // play order, as indexes into `items`
+    order: Vec<usize>,
+    // index into `order` of the current item
+    position: usize,
+    shuffle: bool,
+    repeat: Repeat,
+    rng: StdRng,
+}
+
+impl<T> Queue<T> {
+    pub fn new(items: Vec<T>) -> Queue<T> {
+        Queue {
+            order: (0..items.len()).collect(),
+            items,
+            position: 0,
+            shuffle: false,
+            repeat: Repeat::Off,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// Seed the shuffle, so the same seed always gives the same order.
+    pub fn seed(&mut self, seed: u64) -> &mut Queue<T> {
+        self.rng = StdRng::seed_from_u64(seed);
+        self
+    }
+
+    pub fn current(&self) -> Option<&T> {
+        self.order.get(self.position).map(|&i| &self.items[i])
+    }
+
+    /// Every item, in the order they'll play.
+    pub fn items(&self) -> Vec<&T> {
+        self.order.iter().map(|&i| &self.items[i]).collect()
+    }
+
+    /// Move on once the current item has finished, None when
+    /// there's nothing left to play.
+    pub fn advance(&mut self) -> Option<&T> {
+        self.step(self.repeat)
+    }
+
+    /// Move past the current item, e.g. one that can't be played, even
+    /// when it's on repeat.
+    pub fn skip(&mut self) -> Option<&T> {
+        match self.repeat {
+            Repeat::One => self.step(Repeat::All),
+            repeat => self.step(repeat),
+        }
+    }
+
+    fn step(&mut self, repeat: Repeat) -> Option<&T> {
+        if self.items.is_empty() {
+            return None;
+        }
+
+        match repeat {
+            Repeat::One => {}
+            _ if self.position + 1 < self.order.len() => self.position += 1,
+            Repeat::All => {
+                // a new pass through a shuffled queue gets a new order
+                if self.shuffle {
+                    fisher_yates(&mut self.order, &mut self.rng);
+                }
+                self.position = 0;
+            }
+            Repeat::Off => return None,
+        }
+
+        self.current()
+    }
+
+    /// Add an item to the end of the queue.
+    pub fn push(&mut self, item: T) {
+        self.order.push(self.items.len());
+        self.items.push(item);
+    }
+
+    /// Play an item straight away, carrying on with the queue after it.
+    pub fn play(&mut self, item: T) {
+        let at = if self.order.is_empty() {
+            0
+        } else {
+            self.position + 1
+        };
+        self.order.insert(at, self.items.len());
+        self.items.push(item);
+        self.position = at;
+    }
+
+    pub fn shuffle(&self) -> bool {
+        self.shuffle
+    }
+
+    /// Turning shuffle on keeps the current item playing, followed by
+    /// everything else in random order. Turning it off picks the original
+    /// order back up from the current item.
+    pub fn set_shuffle(&mut self, shuffle: bool) {
+        self.shuffle = shuffle;
+        let current = match self.order.get(self.position) {
+            Some(&i) => i,
+            None => return,
+        };
+
+        if shuffle {
+            let mut rest: Vec<usize> = (0..self.items.len()).filter(|&i| i != current).collect();
+            fisher_yates(&mut rest, &mut self.rng);
+            self.order = vec![current];
+            self.order.extend(rest);
+            self.position = 0;
+        } else {
+            self.order = (0..self.items.len()).collect();
+            self.position = current;
+        }
+    }
+
+    pub fn repeat(&self) -> Repeat {
+        self.repeat
+    }
+
+    pub fn set_repeat(&mut self, repeat: Repeat) {
+        self.repeat = repeat;
+    }
+}
+
+fn fisher_yates<T, R: Rng>(items: &mut [T], rng: &mut R) {
+    for i in (1..items.len()).rev() {
+        let j = rng.gen_range(0, i + 1);
+        items.swap(i, j);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue(len: u32) -> Queue<u32> {
+        let mut queue = Queue::new((0..len).collect());
+        queue.seed(42);
+        queue
+    }
+
+    // every item, in play order, starting from the current one
+    fn pass(queue: &mut Queue<u32>) -> Vec<u32> {
+        let mut items = vec![*queue.current().unwrap()];
+        for _ in 1..queue.items.len() {
+            items.push(*queue.advance().unwrap());
+        }
+        items
+    }
+
+    #[test]
+    fn test_in_order() {
+        let mut q = queue(3);
+        assert_eq!(pass(&mut q), vec![0, 1, 2]);
+        assert_eq!(q.advance(), None);
+
+        // autoplay adds to a finished queue
+        q.push(3);
+        assert_eq!(q.advance(), Some(&3));
+
+        assert_eq!(Queue::<u32>::new(Vec::new()).advance(), None);
+    }
+
+    #[test]
+    fn test_play() {
+        let mut q = queue(3);
+        q.advance();
+        q.play(10);
+        assert_eq!(q.current(), Some(&10));
+        assert_eq!(q.advance(), Some(&2));
+        assert_eq!(q.advance(), None);
+
+        assert_eq!(q.items(), vec![&0, &1, &10, &2]);
+
+        let mut empty = Queue::new(Vec::new());
+        empty.play(10);
+        assert_eq!(empty.current(), Some(&10));
+    }
+
+    #[test]
+    fn test_repeat() {
+        let mut q = queue(3);
+        q.set_repeat(Repeat::One);
+        assert_eq!(q.advance(), Some(&0));
+        assert_eq!(q.advance(), Some(&0));
+
+        q.set_repeat(Repeat::All);
+        assert_eq!(pass(&mut q), vec![0, 1, 2]);
+        assert_eq!(q.advance(), Some(&0));
+    }
+
+    #[test]
+    fn test_skip() {
+        // on past a repeated item, round to the start again
+        let mut q = queue(3);
+        q.set_repeat(Repeat::One);
+        assert_eq!(q.skip(), Some(&1));
+        assert_eq!(q.skip(), Some(&2));
+        assert_eq!(q.skip(), Some(&0));
+        assert_eq!(q.advance(), Some(&0));
+
+        // or to the end, when not repeating
+        q.set_repeat(Repeat::Off);
+        q.skip();
+        q.skip();
+        assert_eq!(q.skip(), None);
+    }
+
+    #[test]
+    fn test_shuffle() {
+        let mut q = queue(10);
+        q.advance();
+        q.set_shuffle(true);
+
+        // current stays put, everything else plays once in a new order
+        let shuffled = pass(&mut q);
+        assert_eq!(shuffled[0], 1);
+        assert_ne!(shuffled, (0..10).collect::<Vec<u32>>());
+        let mut sorted = shuffled.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..10).collect::<Vec<u32>>());
+
+        // same seed, same order
+        let mut again = queue(10);
+        again.advance();
+        again.set_shuffle(true);
+        assert_eq!(pass(&mut again), shuffled);
+
+        // back in order from wherever shuffle left off
+        q.set_shuffle(false);
+        let last = *q.current().unwrap();
+        assert_eq!(q.advance().cloned(), (last + 1..10).next());
+    }
+
+    #[test]
+    fn test_shuffle_repeat_all() {
+        let mut q = queue(10);
+        q.set_shuffle(true);
+        q.set_repeat(Repeat::All);
+
+        let first = pass(&mut q);
+        q.advance();
+        let second = pass(&mut q);
+        assert_ne!(first, second);
+
+        let mut sorted = second.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..10).collect::<Vec<u32>>());
+    }
+}
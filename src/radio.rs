@@ -0,0 +1,92 @@
+// Autoplay radio: once the queue runs out, keep playing tracks related
+// to the last one, skipping anything played recently
+use std::collections::VecDeque;
+
+use crate::soundcloud;
+
+// how many track ids are remembered to avoid repeats
+const RECENT: usize = 200;
+
+/// Ids of the most recently played tracks, oldest first.
+pub struct Recent {
+    ids: VecDeque<u64>,
+    capacity: usize,
+}
+
+impl Default for Recent {
+    fn default() -> Recent {
+        Recent::with_capacity(RECENT)
+    }
+}
+
+impl Recent {
+    pub fn with_capacity(capacity: usize) -> Recent {
+        Recent {
+            ids: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, id: u64) {
+        // a replayed track moves back to the newest end
+        self.ids.retain(|&i| i != id);
+        if self.ids.len() == self.capacity {
+            self.ids.pop_front();
+        }
+        self.ids.push_back(id);
+    }
+
+    pub fn contains(&self, id: u64) -> bool {
+        self.ids.contains(&id)
+    }
+}
+
+/// First of the related tracks that can be streamed and hasn't been
+/// played recently, in the order SoundCloud ranks them.
+pub fn pick(related: Vec<soundcloud::Track>, recent: &Recent) -> Option<soundcloud::Track> {
+    related
+        .into_iter()
+        .find(|track| !track.stream_url.is_empty() && !recent.contains(track.id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(id: u64) -> soundcloud::Track {
+        soundcloud::Track {
+            id,
+            stream_url: format!("https://api.soundcloud.com/tracks/{}/stream", id),
+            ..soundcloud::Track::default()
+        }
+    }
+
+    #[test]
+    fn test_recent() {
+        let mut recent = Recent::with_capacity(2);
+        recent.push(1);
+        recent.push(2);
+        recent.push(1);
+        recent.push(3);
+
+        // 2 was the oldest once 1 was played again
+        assert!(recent.contains(1));
+        assert!(!recent.contains(2));
+        assert!(recent.contains(3));
+    }
+
+    #[test]
+    fn test_pick() {
+        let mut recent = Recent::default();
+        recent.push(1);
+        recent.push(2);
+
+        let unstreamable = soundcloud::Track {
+            id: 3,
+            ..soundcloud::Track::default()
+        };
+        let related = vec![track(1), unstreamable, track(2), track(4), track(5)];
+        assert_eq!(pick(related, &recent).map(|t| t.id), Some(4));
+        assert!(pick(vec![track(1), track(2)], &recent).is_none());
+    }
+}
@@ -0,0 +1,571 @@
+// Keeping a copy of a track: the decoded audio as WAV or FLAC, recorded
+// from the player as it plays or by `scli record`, or the stream itself
+// as MP3 with its details filled in
+use rodio::source::Source;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+use crate::decoder;
+use crate::flac;
+use crate::soundcloud;
+
+// number of samples collected on the audio thread before handing them
+// to the thread writing them out
+const CHUNK: usize = 512;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Wav,
+    Flac,
+    Mp3,
+}
+
+impl Format {
+    /// Format for a file, going by its extension.
+    pub fn from_path(path: &Path) -> Option<Format> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "wav" => Some(Format::Wav),
+            "flac" => Some(Format::Flac),
+            "mp3" => Some(Format::Mp3),
+            _ => None,
+        }
+    }
+}
+
+/// Decoded audio going into a file, which is only created once the
+/// first samples say what format they're in.
+pub struct Recording {
+    path: PathBuf,
+    format: Format,
+    tags: Vec<(&'static str, String)>,
+    writer: Option<Writer>,
+    // the first write that failed, anything after it is dropped
+    error: Option<io::Error>,
+}
+
+enum Writer {
+    Wav(hound::WavWriter<BufWriter<File>>),
+    Flac(flac::Encoder<BufWriter<File>>),
+}
+
+impl Recording {
+    /// Record `track` to a .wav or .flac file.
+    pub fn create(path: &Path, track: &soundcloud::Track) -> Result<Recording, failure::Error> {
+        let format = match Format::from_path(path) {
+            Some(Format::Mp3) | None => {
+                failure::bail!("can't record to {}, use .wav or .flac", path.display())
+            }
+            Some(format) => format,
+        };
+        // a path that can't be written to should fail now, not a few samples in
+        File::create(path)
+            .map_err(|e| failure::format_err!("can't write to {}: {}", path.display(), e))?;
+
+        Ok(Recording {
+            path: path.to_path_buf(),
+            format,
+            tags: tags(track),
+            writer: None,
+            error: None,
+        })
+    }
+
+    /// Add interleaved samples.
+    pub fn write(&mut self, samples: &[i16], channels: u16, rate: u32) {
+        if self.error.is_none() {
+            self.error = self.append(samples, channels, rate).err();
+        }
+    }
+
+    fn append(&mut self, samples: &[i16], channels: u16, rate: u32) -> io::Result<()> {
+        if self.writer.is_none() {
+            let file = BufWriter::new(File::create(&self.path)?);
+            self.writer = Some(match self.format {
+                Format::Flac => {
+                    let tags: Vec<(&str, &str)> = self
+                        .tags
+                        .iter()
+                        .map(|(name, value)| (*name, value.as_str()))
+                        .collect();
+                    Writer::Flac(flac::Encoder::new(file, channels, rate, &tags)?)
+                }
+                _ => {
+                    let spec = hound::WavSpec {
+                        channels,
+                        sample_rate: rate,
+                        bits_per_sample: 16,
+                        sample_format: hound::SampleFormat::Int,
+                    };
+                    Writer::Wav(hound::WavWriter::new(file, spec).map_err(wav_error)?)
+                }
+            });
+        }
+
+        match self.writer.as_mut().unwrap() {
+            Writer::Wav(writer) => {
+                for &sample in samples {
+                    writer.write_sample(sample).map_err(wav_error)?;
+                }
+                Ok(())
+            }
+            Writer::Flac(encoder) => encoder.write(samples),
+        }
+    }
+
+    /// Finish the file, returning where it is.
+    pub fn finish(self) -> Result<PathBuf, failure::Error> {
+        if let Some(e) = self.error {
+            failure::bail!("can't write to {}: {}", self.path.display(), e);
+        }
+        match self.writer {
+            Some(Writer::Wav(writer)) => writer.finalize()?,
+            Some(Writer::Flac(encoder)) => {
+                encoder.finish()?;
+            }
+            None => {
+                // created up front, but there's nothing to put in it
+                let _ = fs::remove_file(&self.path);
+                failure::bail!("nothing was recorded")
+            }
+        }
+        Ok(self.path)
+    }
+}
+
+fn wav_error(e: hound::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, e.to_string())
+}
+
+// Vorbis comments for a track
+fn tags(track: &soundcloud::Track) -> Vec<(&'static str, String)> {
+    let mut tags = vec![
+        ("TITLE", track.title.clone()),
+        ("ARTIST", track.user.username.clone()),
+    ];
+    if !track.genre.is_empty() {
+        tags.push(("GENRE", track.genre.clone()));
+    }
+    if let Some(year) = year(track) {
+        tags.push(("DATE", year.to_string()));
+    }
+    if !track.permalink_url.is_empty() {
+        tags.push(("COMMENT", track.permalink_url.clone()));
+    }
+    tags
+}
+
+// e.g. "2019/11/02 21:04:51 +0000" or "2019-11-02T21:04:51Z"
+fn year(track: &soundcloud::Track) -> Option<i32> {
+    track.created_at.get(..4)?.parse().ok()
+}
+
+enum Message {
+    Start(Box<Recording>),
+    Write(Vec<i16>, u16, u32),
+    Stop(mpsc::Sender<Option<Result<PathBuf, failure::Error>>>),
+}
+
+/// Shared between the player and whatever starts and stops recording,
+/// recording nothing until it's started. Encoding and writing happen on a
+/// thread of their own, so they never hold up playback.
+#[derive(Clone)]
+pub struct Recorder {
+    recording: Arc<AtomicBool>,
+    // bumped for each recording, so samples from before it aren't kept
+    generation: Arc<AtomicUsize>,
+    writer: mpsc::Sender<Message>,
+}
+
+impl Default for Recorder {
+    fn default() -> Recorder {
+        let (tx, rx) = mpsc::channel();
+        // stops once every copy of the recorder is dropped
+        thread::spawn(move || {
+            let mut recording: Option<Recording> = None;
+            for message in rx {
+                match message {
+                    Message::Start(started) => recording = Some(*started),
+                    Message::Write(samples, channels, rate) => {
+                        if let Some(ref mut recording) = recording {
+                            recording.write(&samples, channels, rate);
+                        }
+                    }
+                    Message::Stop(reply) => {
+                        let _ = reply.send(recording.take().map(Recording::finish));
+                    }
+                }
+            }
+        });
+
+        Recorder {
+            recording: Arc::new(AtomicBool::new(false)),
+            generation: Arc::new(AtomicUsize::new(0)),
+            writer: tx,
+        }
+    }
+}
+
+impl Recorder {
+    pub fn start(&self, recording: Recording) {
+        // the recording's there before any samples are sent for it
+        let _ = self.writer.send(Message::Start(Box::new(recording)));
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        self.recording.store(true, Ordering::SeqCst);
+    }
+
+    /// Stop and finish the file, if there's one being recorded, once
+    /// everything sent so far is written.
+    pub fn stop(&self) -> Option<Result<PathBuf, failure::Error>> {
+        self.recording.store(false, Ordering::SeqCst);
+        let (tx, rx) = mpsc::channel();
+        self.writer.send(Message::Stop(tx)).ok()?;
+        rx.recv().ok()?
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.load(Ordering::SeqCst)
+    }
+
+    fn generation(&self) -> usize {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    fn write(&self, samples: Vec<i16>, channels: u16, rate: u32) {
+        let _ = self.writer.send(Message::Write(samples, channels, rate));
+    }
+}
+
+/// A pass-through source filter that copies samples to a `Recorder`.
+pub struct Tee<S>
+where
+    S: Source<Item = i16>,
+{
+    input: S,
+    recorder: Recorder,
+    pending: Vec<i16>,
+    // the recorder's generation the pending samples belong to
+    generation: usize,
+}
+
+impl<S> Tee<S>
+where
+    S: Source<Item = i16>,
+{
+    pub fn new(input: S, recorder: Recorder) -> Tee<S> {
+        let generation = recorder.generation();
+        Tee {
+            input,
+            recorder,
+            pending: Vec::with_capacity(CHUNK),
+            generation,
+        }
+    }
+
+    fn flush(&mut self) {
+        let channels = self.input.channels();
+        let sample_rate = self.input.sample_rate();
+        if self.recorder.is_recording() {
+            let samples = mem::replace(&mut self.pending, Vec::with_capacity(CHUNK));
+            self.recorder.write(samples, channels, sample_rate);
+        } else {
+            self.pending.clear();
+        }
+    }
+}
+
+impl<S> Iterator for Tee<S>
+where
+    S: Source<Item = i16>,
+{
+    type Item = i16;
+
+    #[inline]
+    fn next(&mut self) -> Option<i16> {
+        let sample = self.input.next();
+        match sample {
+            Some(v) => {
+                // a recording's started since, what came before isn't part of it
+                let generation = self.recorder.generation();
+                if generation != self.generation {
+                    self.pending.clear();
+                    self.generation = generation;
+                }
+                self.pending.push(v);
+                if self.pending.len() >= CHUNK {
+                    self.flush();
+                }
+            }
+            None => {
+                if !self.pending.is_empty() {
+                    self.flush();
+                }
+            }
+        }
+
+        sample
+    }
+}
+
+impl<S> Source for Tee<S>
+where
+    S: Source<Item = i16>,
+{
+    #[inline]
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    #[inline]
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
+
+/// Save a whole track to `path`, as fast as it downloads, calling
+/// `progress` with how much is done in ms.
+pub fn track<F>(
+    client: &soundcloud::Client,
+    track: &soundcloud::Track,
+    path: &Path,
+    mut progress: F,
+) -> Result<(), failure::Error>
+where
+    F: FnMut(u32),
+{
+    let format = Format::from_path(path).ok_or_else(|| {
+        failure::format_err!(
+            "can't record to {}, use .wav, .flac or .mp3",
+            path.display()
+        )
+    })?;
+    let mut stream = client.stream(&track.stream_url, 0)?;
+
+    // the stream as it is, after a tag
+    if format == Format::Mp3 {
+        let mut file = BufWriter::new(File::create(path)?);
+        id3(client, track).write_to(&mut file, id3::Version::Id3v24)?;
+        io::copy(&mut stream, &mut file)?;
+        return Ok(());
+    }
+
+    let mut source = decoder::Mp3Decoder::new(BufReader::new(stream))
+        .map_err(|_| failure::format_err!("can't decode {}", track.title))?;
+    let mut recording = Recording::create(path, track)?;
+    let mut samples = Vec::new();
+    let mut done = 0u64;
+    loop {
+        // a second at a time
+        let channels = source.channels();
+        let rate = source.sample_rate();
+        samples.clear();
+        samples.extend(source.by_ref().take(rate as usize * channels as usize));
+        if samples.is_empty() {
+            break;
+        }
+
+        recording.write(&samples, channels, rate);
+        done += (samples.len() / channels as usize) as u64 * 1000 / rate as u64;
+        progress(done as u32);
+    }
+
+    recording.finish()?;
+    Ok(())
+}
+
+/// `user - title.<extension>`, without anything that can't go in a file name.
+pub fn filename(track: &soundcloud::Track, extension: &str) -> String {
+    let name = format!("{} - {}", track.user.username, track.title);
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+
+    format!("{}.{}", name.trim(), extension)
+}
+
+// ID3 tag for a track, artwork and all
+fn id3(client: &soundcloud::Client, track: &soundcloud::Track) -> id3::Tag {
+    use id3::frame::{Content, Picture, PictureType};
+    use id3::{Frame, TagLike};
+
+    let mut tag = id3::Tag::new();
+    tag.set_title(track.title.as_str());
+    tag.set_artist(track.user.username.as_str());
+    if !track.genre.is_empty() {
+        tag.set_genre(track.genre.as_str());
+    }
+    if let Some(year) = year(track) {
+        tag.set_year(year);
+    }
+    tag.set_duration(track.duration);
+    if !track.permalink_url.is_empty() {
+        tag.add_frame(Frame::link("WOAF", track.permalink_url.as_str()));
+    }
+    // the tag's still worth having without it
+    if let Ok(Some(artwork)) = client.artwork(track) {
+        tag.add_frame(Frame::with_content(
+            "APIC",
+            Content::Picture(Picture {
+                mime_type: String::from("image/jpeg"),
+                picture_type: PictureType::CoverFront,
+                description: String::new(),
+                data: artwork,
+            }),
+        ));
+    }
+    tag
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    fn track() -> soundcloud::Track {
+        soundcloud::Track {
+            title: String::from("Art of Minimal Techno"),
+            user: soundcloud::User {
+                username: String::from("trippycode"),
+                ..soundcloud::User::default()
+            },
+            genre: String::from("Techno"),
+            created_at: String::from("2019/11/02 21:04:51 +0000"),
+            permalink_url: String::from("https://soundcloud.com/trippycode/art-of-minimal"),
+            ..soundcloud::Track::default()
+        }
+    }
+
+    // Some stereo samples at 44.1kHz
+    struct Tone {
+        samples: std::vec::IntoIter<i16>,
+    }
+
+    impl Iterator for Tone {
+        type Item = i16;
+
+        fn next(&mut self) -> Option<i16> {
+            self.samples.next()
+        }
+    }
+
+    impl Source for Tone {
+        fn current_frame_len(&self) -> Option<usize> {
+            None
+        }
+
+        fn channels(&self) -> u16 {
+            2
+        }
+
+        fn sample_rate(&self) -> u32 {
+            44100
+        }
+
+        fn total_duration(&self) -> Option<Duration> {
+            None
+        }
+    }
+
+    fn path(extension: &str) -> PathBuf {
+        env::temp_dir().join(format!("scli-record-{}.{}", process::id(), extension))
+    }
+
+    #[test]
+    fn test_filename() {
+        let track = soundcloud::Track {
+            title: String::from("Art of Minimal: Part 1/2"),
+            ..track()
+        };
+        assert_eq!(
+            filename(&track, "mp3"),
+            "trippycode - Art of Minimal_ Part 1_2.mp3"
+        );
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(Format::from_path(Path::new("mix.FLAC")), Some(Format::Flac));
+        assert_eq!(Format::from_path(Path::new("mix.mp3")), Some(Format::Mp3));
+        assert_eq!(Format::from_path(Path::new("mix.ogg")), None);
+        assert!(Recording::create(Path::new("mix.mp3"), &track()).is_err());
+    }
+
+    #[test]
+    fn test_tags() {
+        let tags = tags(&track());
+        assert_eq!(tags[0], ("TITLE", String::from("Art of Minimal Techno")));
+        assert!(tags.contains(&("DATE", String::from("2019"))));
+        assert!(tags.contains(&("GENRE", String::from("Techno"))));
+    }
+
+    #[test]
+    fn test_tee() {
+        let path = path("flac");
+        let recorder = Recorder::default();
+        let samples: Vec<i16> = (0..20000).map(|i| (i % 300) as i16).collect();
+        let mut tee = Tee::new(
+            Tone {
+                samples: samples.clone().into_iter(),
+            },
+            recorder.clone(),
+        );
+
+        // nothing's kept until recording starts
+        assert_eq!(tee.by_ref().take(1000).count(), 1000);
+        recorder.start(Recording::create(&path, &track()).unwrap());
+        assert!(recorder.is_recording());
+        assert_eq!(tee.count(), 19000);
+        assert_eq!(recorder.stop().unwrap().unwrap(), path);
+        assert!(!recorder.is_recording());
+        assert!(recorder.stop().is_none());
+
+        let mut reader = claxon::FlacReader::open(&path).unwrap();
+        assert_eq!(reader.get_tag("ARTIST").next(), Some("trippycode"));
+        let recorded: Vec<i16> = reader.samples().map(|s| s.unwrap() as i16).collect();
+        assert_eq!(recorded, &samples[1000..]);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_wav() {
+        let path = path("wav");
+        let mut recording = Recording::create(&path, &track()).unwrap();
+        recording.write(&[1, 2, 3, 4], 2, 48000);
+        recording.write(&[5, 6], 2, 48000);
+        recording.finish().unwrap();
+
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.spec().sample_rate, 48000);
+        let recorded: Vec<i16> = reader.samples().map(|s| s.unwrap()).collect();
+        assert_eq!(recorded, vec![1, 2, 3, 4, 5, 6]);
+
+        // nothing written, nothing worth keeping
+        let recording = Recording::create(&path, &track()).unwrap();
+        assert!(recording.finish().is_err());
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,523 @@
+// Opt-in HTTP remote control, e.g. from a phone on the same network:
+// JSON state and playback controls, plus a server-sent events stream of
+// state changes, for whoever has the token from the config
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use termion::event::Key;
+
+use crate::control::{Command, Reply, Request, Status};
+use crate::event::Event;
+use crate::status::Clock;
+
+// actions that only touch playback, anything touching the account or
+// writing files stays with whoever is in front of the player
+const ACTIONS: &[&str] = &[
+    "play_pause",
+    "volume_up",
+    "volume_down",
+    "slower",
+    "faster",
+    "autoplay",
+    "shuffle",
+    "repeat",
+];
+
+// the most of a request that's read, request line and headers together,
+// and the most headers, anything past either is turned away
+const MAX_HEAD: u64 = 8192;
+const MAX_HEADERS: usize = 64;
+// how long a client has to send its request, or take what's sent to it
+const TIMEOUT: Duration = Duration::from_secs(10);
+// connections answered at once, event streams included, any more are closed
+const MAX_CONNECTIONS: usize = 16;
+
+// open event streams, and the last state sent to them
+#[derive(Default)]
+struct Subscribers {
+    streams: Vec<mpsc::Sender<String>>,
+    last: Option<String>,
+}
+
+pub struct Remote {
+    subscribers: Arc<Mutex<Subscribers>>,
+}
+
+// What a request is asking for
+#[derive(Debug, PartialEq)]
+enum Route {
+    Command(Command),
+    Events,
+    BadRequest(String),
+    Forbidden(String),
+    NotFound,
+}
+
+/// The address being listened on, and the token every request needs.
+pub struct Listener {
+    listener: TcpListener,
+    token: Arc<String>,
+}
+
+/// Bind the address early, so a port in use is reported before playing.
+/// A bare port only listens on this machine, a host has to be given to be
+/// reachable from anywhere else.
+pub fn bind(address: &str, token: String) -> Result<Listener, failure::Error> {
+    if token.is_empty() {
+        failure::bail!("the remote's token can't be empty");
+    }
+    let address = if address.contains(':') {
+        address.to_string()
+    } else {
+        format!("127.0.0.1:{}", address)
+    };
+    let listener = TcpListener::bind(&address)
+        .map_err(|e| failure::format_err!("can't listen on {}: {}", address, e))?;
+    Ok(Listener {
+        listener,
+        token: Arc::new(token),
+    })
+}
+
+impl Remote {
+    /// Answer requests, passing commands on as events next to key presses.
+    pub fn serve(listener: Listener, events: mpsc::Sender<Event<Key>>) -> Remote {
+        let subscribers = Arc::new(Mutex::new(Subscribers::default()));
+        let remote = Remote {
+            subscribers: Arc::clone(&subscribers),
+        };
+
+        let Listener { listener, token } = listener;
+        let connections = Arc::new(AtomicUsize::new(0));
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if let Ok(stream) = stream {
+                    if connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                        connections.fetch_sub(1, Ordering::SeqCst);
+                        continue;
+                    }
+                    let events = events.clone();
+                    let subscribers = Arc::clone(&subscribers);
+                    let token = Arc::clone(&token);
+                    let connections = Arc::clone(&connections);
+                    thread::spawn(move || {
+                        let _ = handle(stream, &token, events, subscribers);
+                        connections.fetch_sub(1, Ordering::SeqCst);
+                    });
+                }
+            }
+        });
+
+        remote
+    }
+
+    /// Send the state to event streams, when it's changed.
+    pub fn publish(&self, mut status: Status) {
+        // the position only counts once a second, rather than every tick
+        status.position_ms -= status.position_ms % 1000;
+        let json = match serde_json::to_string(&status) {
+            Ok(json) => json,
+            Err(_) => return,
+        };
+
+        let mut subscribers = self.subscribers.lock().unwrap();
+        if subscribers.last.as_ref() == Some(&json) {
+            return;
+        }
+        subscribers
+            .streams
+            .retain(|stream| stream.send(json.clone()).is_ok());
+        subscribers.last = Some(json);
+    }
+}
+
+fn handle(
+    stream: TcpStream,
+    token: &str,
+    events: mpsc::Sender<Event<Key>>,
+    subscribers: Arc<Mutex<Subscribers>>,
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let head = head(BufReader::new(stream.try_clone()?), token)?;
+
+    let mut stream = stream;
+    let (request, authorized) = match head {
+        Some(head) => head,
+        None => {
+            return respond(
+                &mut stream,
+                "400 Bad Request",
+                &Reply::error("request cut short or too large".to_string()),
+            )
+        }
+    };
+    if !authorized {
+        return respond(
+            &mut stream,
+            "401 Unauthorized",
+            &Reply::error("send the token as Authorization: Bearer <token>".to_string()),
+        );
+    }
+
+    let mut parts = request.split_whitespace();
+    let method = parts.next().unwrap_or("");
+    let target = parts.next().unwrap_or("");
+    match route(method, target) {
+        Route::Command(command) => {
+            let (tx, rx) = mpsc::channel();
+            let reply = match events.send(Event::Control(Request { command, reply: tx })) {
+                Ok(_) => rx
+                    .recv()
+                    .unwrap_or_else(|_| Reply::error("player stopped".to_string())),
+                Err(_) => Reply::error("player stopped".to_string()),
+            };
+            let status = if reply.ok { "200 OK" } else { "409 Conflict" };
+            respond(&mut stream, status, &reply)
+        }
+        Route::Events => {
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\n\
+                 Content-Type: text/event-stream\r\n\
+                 Cache-Control: no-cache\r\n\r\n"
+            )?;
+
+            let (tx, rx) = mpsc::channel();
+            {
+                let mut subscribers = subscribers.lock().unwrap();
+                if let Some(ref last) = subscribers.last {
+                    let _ = tx.send(last.clone());
+                }
+                subscribers.streams.push(tx);
+            }
+
+            // until the client goes away, the next publish drops the sender
+            for json in rx {
+                write!(stream, "data: {}\n\n", json)?;
+                stream.flush()?;
+            }
+            Ok(())
+        }
+        Route::BadRequest(error) => respond(&mut stream, "400 Bad Request", &Reply::error(error)),
+        Route::Forbidden(error) => respond(&mut stream, "403 Forbidden", &Reply::error(error)),
+        Route::NotFound => respond(
+            &mut stream,
+            "404 Not Found",
+            &Reply::error(format!("no such endpoint {} {}", method, target)),
+        ),
+    }
+}
+
+// The request line, and whether the headers carry the token. None when
+// the head ends early or runs past the limits. Only the token is needed
+// from the headers, nothing from the body.
+fn head<R: BufRead>(reader: R, token: &str) -> io::Result<Option<(String, bool)>> {
+    let mut reader = reader.take(MAX_HEAD);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+
+    let mut authorized = false;
+    for _ in 0..MAX_HEADERS {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        if header.trim().is_empty() {
+            return Ok(Some((request, authorized)));
+        }
+        if let Some(bearer) = bearer(&header) {
+            authorized = same(bearer.as_bytes(), token.as_bytes());
+        }
+    }
+    Ok(None)
+}
+
+fn respond(stream: &mut TcpStream, status: &str, reply: &Reply) -> io::Result<()> {
+    let body = serde_json::to_string(reply).unwrap_or_default();
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+fn route(method: &str, target: &str) -> Route {
+    let (path, query) = match target.find('?') {
+        Some(i) => (&target[..i], &target[i + 1..]),
+        None => (target, ""),
+    };
+    let param = |name: &str| {
+        query
+            .split('&')
+            .filter_map(|pair| {
+                let mut pair = pair.splitn(2, '=');
+                match (pair.next(), pair.next()) {
+                    (Some(key), Some(value)) if key == name => Some(decode(value)),
+                    _ => None,
+                }
+            })
+            .next()
+    };
+
+    let command = match (method, path) {
+        ("GET", "/status") => Command::Status,
+        ("GET", "/events") => return Route::Events,
+        ("POST", "/play") => Command::Play,
+        ("POST", "/pause") => Command::Pause,
+        ("POST", "/toggle") => Command::Toggle,
+        ("POST", "/next") => Command::Next,
+        ("POST", "/seek") => match param("position").as_ref().and_then(|p| Clock::parse(p)) {
+            Some(position) => Command::Seek { position },
+            None => return Route::BadRequest("position must be a time, e.g. 43:10".to_string()),
+        },
+        ("POST", "/volume") => match param("volume").and_then(|v| v.parse::<u8>().ok()) {
+            Some(volume) if volume <= 100 => Command::Volume { volume },
+            _ => return Route::BadRequest("volume must be between 0 and 100".to_string()),
+        },
+        ("POST", "/enqueue") => match param("url") {
+            Some(url) => Command::Enqueue { url },
+            None => return Route::BadRequest("url is missing".to_string()),
+        },
+        ("POST", path) if path.starts_with("/actions/") => {
+            let action = &path["/actions/".len()..];
+            if !ACTIONS.contains(&action) {
+                return Route::Forbidden(format!("'{}' can't be done remotely", action));
+            }
+            Command::Action {
+                action: action.to_string(),
+            }
+        }
+        _ => return Route::NotFound,
+    };
+
+    Route::Command(command)
+}
+
+// The token in an `Authorization: Bearer <token>` header
+fn bearer(header: &str) -> Option<&str> {
+    let mut parts = header.splitn(2, ':');
+    let name = parts.next()?.trim();
+    let value = parts.next()?.trim();
+    if !name.eq_ignore_ascii_case("authorization") {
+        return None;
+    }
+    let mut value = value.splitn(2, ' ');
+    match (value.next(), value.next()) {
+        (Some(scheme), Some(token)) if scheme.eq_ignore_ascii_case("bearer") => Some(token.trim()),
+        _ => None,
+    }
+}
+
+// Compare tokens in the same time however much of them matches
+fn same(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+// Undo percent encoding in a query value, e.g. a url to enqueue
+fn decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+                match u8::from_str_radix(hex, 16) {
+                    Ok(byte) => {
+                        decoded.push(byte);
+                        i += 2;
+                    }
+                    Err(_) => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_route() {
+        assert_eq!(route("GET", "/status"), Route::Command(Command::Status));
+        assert_eq!(route("GET", "/events"), Route::Events);
+        assert_eq!(route("POST", "/toggle"), Route::Command(Command::Toggle));
+        assert_eq!(
+            route("POST", "/seek?position=1:00"),
+            Route::Command(Command::Seek { position: 60000 })
+        );
+        assert_eq!(
+            route("POST", "/volume?x=1&volume=80"),
+            Route::Command(Command::Volume { volume: 80 })
+        );
+        assert_eq!(
+            route(
+                "POST",
+                "/enqueue?url=https%3A%2F%2Fsoundcloud.com%2Ftrippycode%2Fart-of-minimal"
+            ),
+            Route::Command(Command::Enqueue {
+                url: "https://soundcloud.com/trippycode/art-of-minimal".to_string()
+            })
+        );
+        assert_eq!(
+            route("POST", "/actions/shuffle"),
+            Route::Command(Command::Action {
+                action: "shuffle".to_string()
+            })
+        );
+        for action in &["like", "repost", "record", "export", "quit"] {
+            match route("POST", &format!("/actions/{}", action)) {
+                Route::Forbidden(_) => {}
+                other => panic!("{}: {:?}", action, other),
+            }
+        }
+
+        match route("POST", "/volume?volume=101") {
+            Route::BadRequest(_) => {}
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(route("GET", "/toggle"), Route::NotFound);
+        assert_eq!(route("GET", "/"), Route::NotFound);
+    }
+
+    #[test]
+    fn test_bearer() {
+        assert_eq!(bearer("Authorization: Bearer s3cret\r\n"), Some("s3cret"));
+        assert_eq!(bearer("authorization: bearer s3cret"), Some("s3cret"));
+        assert_eq!(bearer("Authorization: Basic czNjcmV0"), None);
+        assert_eq!(bearer("X-Token: Bearer s3cret"), None);
+        assert!(same(b"s3cret", b"s3cret"));
+        assert!(!same(b"s3cret", b"s3cre"));
+        assert!(!same(b"s3cret", b"s3creT"));
+    }
+
+    #[test]
+    fn test_unauthorized() {
+        let listener = bind("0", "s3cret".to_string()).unwrap();
+        let address = listener.listener.local_addr().unwrap();
+        assert!(address.ip().is_loopback());
+        let (events, received) = mpsc::channel();
+        let _remote = Remote::serve(listener, events);
+
+        let send = |headers: &str| {
+            let mut stream = TcpStream::connect(address).unwrap();
+            write!(stream, "POST /toggle HTTP/1.1\r\n{}\r\n", headers).unwrap();
+            let mut response = String::new();
+            BufReader::new(stream).read_line(&mut response).unwrap();
+            response
+        };
+
+        // turned away before the player hears about it
+        assert!(send("").starts_with("HTTP/1.1 401"));
+        assert!(send("Authorization: Bearer wrong\r\n").starts_with("HTTP/1.1 401"));
+        assert!(received.try_recv().is_err());
+
+        // answered by a stand-in for the event loop
+        thread::spawn(move || {
+            if let Ok(Event::Control(request)) = received.recv() {
+                let _ = request.reply.send(Reply::ok());
+            }
+        });
+        assert!(send("Authorization: Bearer s3cret\r\n").starts_with("HTTP/1.1 200"));
+    }
+
+    #[test]
+    fn test_head() {
+        let head = |text: String| head(io::Cursor::new(text), "s3cret").unwrap();
+        assert_eq!(
+            head("GET /status HTTP/1.1\r\nAuthorization: Bearer s3cret\r\n\r\n".to_string()),
+            Some(("GET /status HTTP/1.1\r\n".to_string(), true))
+        );
+        assert_eq!(
+            head("GET /status HTTP/1.1\r\nHost: x\r\n\r\n".to_string()),
+            Some(("GET /status HTTP/1.1\r\n".to_string(), false))
+        );
+
+        // cut short, too long, or too many headers
+        assert_eq!(
+            head("GET /status HTTP/1.1\r\nHost: x\r\n".to_string()),
+            None
+        );
+        let long = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_HEAD as usize));
+        assert_eq!(head(long), None);
+        let many = format!(
+            "GET / HTTP/1.1\r\n{}\r\n",
+            "X: y\r\n".repeat(MAX_HEADERS + 1)
+        );
+        assert_eq!(head(many), None);
+    }
+
+    #[test]
+    fn test_connections() {
+        let listener = bind("0", "s3cret".to_string()).unwrap();
+        let address = listener.listener.local_addr().unwrap();
+        let (events, _received) = mpsc::channel();
+        let _remote = Remote::serve(listener, events);
+
+        // clients that never send anything, held open until they time out
+        let _idle: Vec<TcpStream> = (0..MAX_CONNECTIONS)
+            .map(|_| TcpStream::connect(address).unwrap())
+            .collect();
+
+        // and one too many, closed straight away
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut response = Vec::new();
+        assert_eq!(stream.read_to_end(&mut response).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode("a+b%20c"), "a b c");
+        assert_eq!(decode("100%"), "100%");
+        assert_eq!(decode("%zz"), "%zz");
+    }
+
+    #[test]
+    fn test_publish() {
+        let remote = Remote {
+            subscribers: Arc::new(Mutex::new(Subscribers::default())),
+        };
+        let (tx, rx) = mpsc::channel();
+        remote.subscribers.lock().unwrap().streams.push(tx);
+
+        let status = Status {
+            position_ms: 1200,
+            ..Status::default()
+        };
+        remote.publish(status.clone());
+        // same second, nothing new to say
+        remote.publish(Status {
+            position_ms: 1900,
+            ..status.clone()
+        });
+        remote.publish(Status {
+            position_ms: 2000,
+            ..status
+        });
+
+        let sent: Vec<String> = rx.try_iter().collect();
+        assert_eq!(sent.len(), 2);
+        assert!(sent[0].contains("\"position_ms\":1000"));
+    }
+}
@@ -0,0 +1,122 @@
+// Playback positions saved between sessions, so a long mix
+// can pick up where it was left, in $XDG_DATA_HOME/scli/positions.json
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::soundcloud;
+
+// positions this close to either end of a track aren't worth resuming
+const MIN_POSITION: u32 = 30 * 1000;
+const MIN_REMAINING: u32 = 30 * 1000;
+
+pub struct Positions {
+    path: Option<PathBuf>,
+    positions: HashMap<u64, u32>,
+}
+
+impl Positions {
+    /// Load saved positions, starting afresh if there aren't any (or
+    /// they can't be read, losing them isn't worth failing to play over).
+    pub fn load() -> Positions {
+        match path() {
+            Some(path) => Positions::from_file(path),
+            None => Positions {
+                path: None,
+                positions: HashMap::new(),
+            },
+        }
+    }
+
+    pub fn from_file(path: PathBuf) -> Positions {
+        let positions = fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+
+        Positions {
+            path: Some(path),
+            positions,
+        }
+    }
+
+    pub fn get(&self, track_id: u64) -> Option<u32> {
+        self.positions.get(&track_id).cloned()
+    }
+
+    /// Remember how far into a track playback got, forgetting
+    /// it when the track was barely started or played to the end.
+    pub fn set(&mut self, track: &soundcloud::Track, position: u32) {
+        if position < MIN_POSITION || position + MIN_REMAINING > track.duration {
+            self.positions.remove(&track.id);
+        } else {
+            self.positions.insert(track.id, position);
+        }
+    }
+
+    pub fn save(&self) -> Result<(), failure::Error> {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        // write then rename, so quitting mid-write can't lose everything
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string(&self.positions)?)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+fn path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("scli").join("positions.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    fn track(id: u64) -> soundcloud::Track {
+        soundcloud::Track {
+            id,
+            duration: 60 * 60 * 1000,
+            ..soundcloud::Track::default()
+        }
+    }
+
+    #[test]
+    fn test_set() {
+        let mut positions = Positions::from_file(PathBuf::new());
+        positions.set(&track(1), 43 * 60 * 1000);
+        assert_eq!(positions.get(1), Some(43 * 60 * 1000));
+
+        // barely started, or finished
+        positions.set(&track(1), 10 * 1000);
+        assert_eq!(positions.get(1), None);
+        positions.set(&track(2), 60 * 60 * 1000 - 5000);
+        assert_eq!(positions.get(2), None);
+    }
+
+    #[test]
+    fn test_save_load() {
+        let dir = env::temp_dir().join(format!("scli-resume-{}", process::id()));
+        let path = dir.join("positions.json");
+
+        let mut positions = Positions::from_file(path.clone());
+        positions.set(&track(1), 90 * 1000);
+        positions.set(&track(2), 120 * 1000);
+        positions.save().unwrap();
+
+        let loaded = Positions::from_file(path);
+        assert_eq!(loaded.get(1), Some(90 * 1000));
+        assert_eq!(loaded.get(2), Some(120 * 1000));
+        assert_eq!(loaded.get(3), None);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
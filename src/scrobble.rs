@@ -0,0 +1,705 @@
+// Scrobbling to Last.fm and ListenBrainz. Scrobbles wait in
+// $XDG_DATA_HOME/scli/scrobbles.json until they're accepted, so
+// none are lost while offline, and any a service turns down are
+// set aside there so they don't hold up the rest
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+use chrono::{DateTime, Local};
+use reqwest::{header, StatusCode};
+
+use crate::config;
+use crate::soundcloud;
+
+const LASTFM: &str = "https://ws.audioscrobbler.com/2.0/";
+const LISTENBRAINZ: &str = "https://api.listenbrainz.org";
+
+// a track counts once half of it, or this much, has been played
+const MIN_LISTENED: u32 = 4 * 60 * 1000;
+// anything shorter never counts
+const MIN_DURATION: u32 = 30 * 1000;
+// the most Last.fm takes at once
+const BATCH: usize = 50;
+// Last.fm errors worth trying again: service offline, temporarily
+// unavailable, rate limited
+const LASTFM_RETRY: [i64; 3] = [11, 16, 29];
+// Last.fm errors down to the account or keys rather than a scrobble:
+// authentication failed, invalid session key, invalid API key, invalid
+// signature, suspended API key
+const LASTFM_REFUSED: [i64; 5] = [4, 9, 10, 13, 26];
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Scrobble {
+    pub artist: String,
+    pub title: String,
+    pub url: String,
+    pub duration_ms: u32,
+    /// When playback started, in seconds since the epoch
+    pub timestamp: i64,
+}
+
+impl Scrobble {
+    pub fn new(track: &soundcloud::Track, started_at: DateTime<Local>) -> Scrobble {
+        let (artist, title) = split(&track.title, &track.user.username);
+        Scrobble {
+            artist,
+            title,
+            url: track.permalink_url.clone(),
+            duration_ms: track.duration,
+            timestamp: started_at.timestamp(),
+        }
+    }
+}
+
+/// Whether enough of a track was played for it to be scrobbled.
+pub fn counts(duration_ms: u32, listened_ms: u32) -> bool {
+    duration_ms > MIN_DURATION && listened_ms >= (duration_ms / 2).min(MIN_LISTENED)
+}
+
+/// Guess the artist and title from a track's title, which is often
+/// "Artist - Title", otherwise the uploader is as good a guess as any.
+pub fn split(title: &str, username: &str) -> (String, String) {
+    let separator = [" - ", " – ", " — "]
+        .iter()
+        .filter_map(|separator| title.find(separator).map(|i| (i, separator.len())))
+        .min();
+    if let Some((i, len)) = separator {
+        let (artist, rest) = (title[..i].trim(), title[i + len..].trim());
+        if !artist.is_empty() && !rest.is_empty() {
+            return (artist.to_string(), rest.to_string());
+        }
+    }
+
+    (username.to_string(), title.trim().to_string())
+}
+
+// Scrobbles not yet accepted, for each service
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+struct Pending {
+    lastfm: Vec<Scrobble>,
+    listenbrainz: Vec<Scrobble>,
+    rejected: Rejected,
+}
+
+// Scrobbles a service won't ever take, kept rather than thrown away
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+struct Rejected {
+    lastfm: Vec<Scrobble>,
+    listenbrainz: Vec<Scrobble>,
+}
+
+// Why a request didn't go through
+#[derive(Debug, PartialEq)]
+enum Failure {
+    // offline, or the service is having trouble, so it's worth trying later
+    Retry,
+    // the credentials were turned down, so nothing will go through until
+    // they're fixed, but the scrobbles themselves are fine
+    Refused,
+    // this scrobble was turned down, and would be again
+    Rejected,
+}
+
+impl From<reqwest::Error> for Failure {
+    fn from(e: reqwest::Error) -> Failure {
+        match e.status() {
+            Some(status) => Failure::from(status),
+            None => Failure::Retry,
+        }
+    }
+}
+
+impl From<StatusCode> for Failure {
+    fn from(status: StatusCode) -> Failure {
+        if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
+            Failure::Refused
+        } else if status.is_client_error() && status != StatusCode::TOO_MANY_REQUESTS {
+            Failure::Rejected
+        } else {
+            Failure::Retry
+        }
+    }
+}
+
+enum Service {
+    LastFm(config::LastFm),
+    ListenBrainz(config::ListenBrainz),
+}
+
+impl Service {
+    fn pending<'a>(&self, pending: &'a mut Pending) -> &'a mut Vec<Scrobble> {
+        match self {
+            Service::LastFm(_) => &mut pending.lastfm,
+            Service::ListenBrainz(_) => &mut pending.listenbrainz,
+        }
+    }
+
+    fn rejected<'a>(&self, pending: &'a mut Pending) -> &'a mut Vec<Scrobble> {
+        match self {
+            Service::LastFm(_) => &mut pending.rejected.lastfm,
+            Service::ListenBrainz(_) => &mut pending.rejected.listenbrainz,
+        }
+    }
+
+    fn now_playing(&self, client: &reqwest::Client, scrobble: &Scrobble) -> Result<(), Failure> {
+        match self {
+            Service::LastFm(config) => {
+                let params = vec![
+                    ("method".to_string(), "track.updateNowPlaying".to_string()),
+                    ("artist".to_string(), scrobble.artist.clone()),
+                    ("track".to_string(), scrobble.title.clone()),
+                    (
+                        "duration".to_string(),
+                        (scrobble.duration_ms / 1000).to_string(),
+                    ),
+                ];
+                lastfm(client, config, params)
+            }
+            Service::ListenBrainz(config) => {
+                listenbrainz(client, config, "playing_now", &[scrobble.clone()])
+            }
+        }
+    }
+
+    fn submit(&self, client: &reqwest::Client, scrobbles: &[Scrobble]) -> Result<(), Failure> {
+        match self {
+            Service::LastFm(config) => {
+                let mut params = vec![("method".to_string(), "track.scrobble".to_string())];
+                for (i, scrobble) in scrobbles.iter().enumerate() {
+                    params.push((format!("artist[{}]", i), scrobble.artist.clone()));
+                    params.push((format!("track[{}]", i), scrobble.title.clone()));
+                    params.push((format!("timestamp[{}]", i), scrobble.timestamp.to_string()));
+                    params.push((
+                        format!("duration[{}]", i),
+                        (scrobble.duration_ms / 1000).to_string(),
+                    ));
+                }
+                lastfm(client, config, params)
+            }
+            Service::ListenBrainz(config) => {
+                let listen_type = if scrobbles.len() == 1 {
+                    "single"
+                } else {
+                    "import"
+                };
+                listenbrainz(client, config, listen_type, scrobbles)
+            }
+        }
+    }
+}
+
+enum Work {
+    NowPlaying(Scrobble),
+    Submit,
+}
+
+/// Sends scrobbles in the background, keeping them on disk until they're sent.
+pub struct Scrobbler {
+    path: Option<PathBuf>,
+    pending: Arc<Mutex<Pending>>,
+    services: Arc<Vec<Service>>,
+    worker: Option<mpsc::Sender<Work>>,
+}
+
+impl Scrobbler {
+    pub fn start(config: config::Scrobble) -> Scrobbler {
+        Scrobbler::from_file(path(), config)
+    }
+
+    pub fn from_file(path: Option<PathBuf>, config: config::Scrobble) -> Scrobbler {
+        let pending: Pending = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        let mut services = Vec::new();
+        services.extend(config.lastfm.map(Service::LastFm));
+        services.extend(config.listenbrainz.map(Service::ListenBrainz));
+        let mut scrobbler = Scrobbler {
+            path,
+            pending: Arc::new(Mutex::new(pending)),
+            services: Arc::new(services),
+            worker: None,
+        };
+        if scrobbler.services.is_empty() {
+            return scrobbler;
+        }
+
+        let (tx, rx) = mpsc::channel();
+        let path = scrobbler.path.clone();
+        let pending = Arc::clone(&scrobbler.pending);
+        let services = Arc::clone(&scrobbler.services);
+        thread::spawn(move || {
+            let client = reqwest::Client::new();
+            for work in rx {
+                match work {
+                    Work::NowPlaying(scrobble) => {
+                        // only of the moment, not worth retrying
+                        for service in services.iter() {
+                            let _ = service.now_playing(&client, &scrobble);
+                        }
+                    }
+                    Work::Submit => submit(&client, &services, &pending, path.as_ref()),
+                }
+            }
+        });
+
+        // anything left over from last time
+        let _ = tx.send(Work::Submit);
+        scrobbler.worker = Some(tx);
+        scrobbler
+    }
+
+    /// Tell the services what's started playing.
+    pub fn now_playing(&self, track: &soundcloud::Track) {
+        if let Some(ref worker) = self.worker {
+            let _ = worker.send(Work::NowPlaying(Scrobble::new(track, Local::now())));
+        }
+    }
+
+    /// Queue a scrobble, saved straight away in case scli quits before it's sent.
+    pub fn scrobble(&self, scrobble: Scrobble) {
+        let worker = match self.worker {
+            Some(ref worker) => worker,
+            None => return,
+        };
+
+        {
+            let mut pending = self.pending.lock().unwrap();
+            for service in self.services.iter() {
+                service.pending(&mut pending).push(scrobble.clone());
+            }
+            let _ = save(self.path.as_ref(), &pending);
+        }
+        let _ = worker.send(Work::Submit);
+    }
+}
+
+// Submit whatever's pending, in batches, until a service can't be reached
+fn submit(
+    client: &reqwest::Client,
+    services: &[Service],
+    pending: &Mutex<Pending>,
+    path: Option<&PathBuf>,
+) {
+    for service in services {
+        let mut size = BATCH;
+        loop {
+            let batch: Vec<Scrobble> = {
+                let mut pending = pending.lock().unwrap();
+                service
+                    .pending(&mut pending)
+                    .iter()
+                    .take(size)
+                    .cloned()
+                    .collect()
+            };
+            if batch.is_empty() {
+                break;
+            }
+
+            let rejected = match service.submit(client, &batch) {
+                Ok(()) => false,
+                Err(Failure::Retry) | Err(Failure::Refused) => break,
+                // one at a time from here, to find which it was
+                Err(Failure::Rejected) if batch.len() > 1 => {
+                    size = 1;
+                    continue;
+                }
+                Err(Failure::Rejected) => true,
+            };
+
+            // new scrobbles only ever go on the end
+            let mut pending = pending.lock().unwrap();
+            let sent: Vec<Scrobble> = service.pending(&mut pending).drain(..batch.len()).collect();
+            if rejected {
+                service.rejected(&mut pending).extend(sent);
+            }
+            let _ = save(path, &pending);
+        }
+    }
+}
+
+fn lastfm(
+    client: &reqwest::Client,
+    config: &config::LastFm,
+    mut params: Vec<(String, String)>,
+) -> Result<(), Failure> {
+    params.push(("api_key".to_string(), config.api_key.clone()));
+    params.push(("sk".to_string(), config.session_key.clone()));
+    let signature = signature(&params, &config.secret);
+    params.push(("api_sig".to_string(), signature));
+    params.push(("format".to_string(), "json".to_string()));
+
+    let endpoint = config.endpoint.as_ref().map(String::as_str);
+    let mut resp = client
+        .post(endpoint.unwrap_or(LASTFM))
+        .header(header::USER_AGENT, "scli")
+        .form(&params)
+        .send()?;
+
+    // errors come back as JSON, whatever the status
+    let body: serde_json::Value = resp.json().unwrap_or_default();
+    if let Some(error) = body.get("error") {
+        return Err(match error.as_i64() {
+            Some(code) if LASTFM_RETRY.contains(&code) => Failure::Retry,
+            Some(code) if LASTFM_REFUSED.contains(&code) => Failure::Refused,
+            _ => Failure::Rejected,
+        });
+    }
+    resp.error_for_status()?;
+    Ok(())
+}
+
+// Last.fm's request signature: every parameter and its value, sorted, then the secret
+fn signature(params: &[(String, String)], secret: &str) -> String {
+    let mut params = params.to_vec();
+    params.sort();
+    let mut text: String = params.iter().map(|(k, v)| format!("{}{}", k, v)).collect();
+    text.push_str(secret);
+    format!("{:x}", md5::compute(text))
+}
+
+fn listenbrainz(
+    client: &reqwest::Client,
+    config: &config::ListenBrainz,
+    listen_type: &str,
+    scrobbles: &[Scrobble],
+) -> Result<(), Failure> {
+    let payload: Vec<serde_json::Value> = scrobbles
+        .iter()
+        .map(|scrobble| {
+            let mut listen = serde_json::json!({
+                "track_metadata": {
+                    "artist_name": scrobble.artist,
+                    "track_name": scrobble.title,
+                    "additional_info": {
+                        "origin_url": scrobble.url,
+                        "duration_ms": scrobble.duration_ms,
+                        "submission_client": "scli",
+                    },
+                },
+            });
+            if listen_type != "playing_now" {
+                listen["listened_at"] = scrobble.timestamp.into();
+            }
+            listen
+        })
+        .collect();
+
+    let endpoint = config.endpoint.as_ref().map(String::as_str);
+    let url = format!(
+        "{}/1/submit-listens",
+        endpoint.unwrap_or(LISTENBRAINZ).trim_end_matches('/')
+    );
+    client
+        .post(&url)
+        .header(header::AUTHORIZATION, format!("Token {}", config.token))
+        .json(&serde_json::json!({ "listen_type": listen_type, "payload": payload }))
+        .send()?
+        .error_for_status()?;
+    Ok(())
+}
+
+fn save(path: Option<&PathBuf>, pending: &Pending) -> Result<(), failure::Error> {
+    let path = match path {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_string(pending)?)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("scli").join("scrobbles.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::process;
+    use std::time::Duration;
+
+    fn scrobble(title: &str) -> Scrobble {
+        Scrobble {
+            artist: String::from("trippycode"),
+            title: title.to_string(),
+            url: String::from("https://soundcloud.com/trippycode/art-of-minimal"),
+            duration_ms: 4112000,
+            timestamp: 1577836800,
+        }
+    }
+
+    // Stands in for a service, answering with whatever `status` is at the
+    // time and passing each request back to the test
+    fn server(status: Arc<Mutex<&'static str>>) -> (String, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request = String::new();
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.to_lowercase().starts_with("content-length:") {
+                        length = line[15..].trim().parse().unwrap();
+                    }
+                    request.push_str(&line);
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                request.push_str(&String::from_utf8(body).unwrap());
+                tx.send(request).unwrap();
+
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{{}}",
+                    status.lock().unwrap()
+                )
+                .unwrap();
+            }
+        });
+
+        (endpoint, rx)
+    }
+
+    // Wait for the worker to catch up
+    fn saved<F>(path: &PathBuf, check: F) -> bool
+    where
+        F: Fn(&Pending) -> bool,
+    {
+        (0..50).any(|_| {
+            thread::sleep(Duration::from_millis(20));
+            fs::read_to_string(path)
+                .ok()
+                .and_then(|contents| serde_json::from_str::<Pending>(&contents).ok())
+                .map_or(false, |pending| check(&pending))
+        })
+    }
+
+    fn pending(path: &PathBuf, count: usize) -> bool {
+        saved(path, |pending| pending.lastfm.len() == count)
+    }
+
+    #[test]
+    fn test_split() {
+        let split = |title| split(title, "trippycode");
+        assert_eq!(
+            split("Mathew Jonson - Marionette"),
+            (String::from("Mathew Jonson"), String::from("Marionette"))
+        );
+        assert_eq!(
+            split("Ben Klock – Subzero - Original Mix"),
+            (
+                String::from("Ben Klock"),
+                String::from("Subzero - Original Mix")
+            )
+        );
+        assert_eq!(
+            split("Art of Minimal Techno"),
+            (
+                String::from("trippycode"),
+                String::from("Art of Minimal Techno")
+            )
+        );
+        assert_eq!(
+            split(" - Untitled"),
+            (String::from("trippycode"), String::from("- Untitled"))
+        );
+    }
+
+    #[test]
+    fn test_counts() {
+        assert!(counts(3 * 60 * 1000, 90 * 1000));
+        assert!(!counts(3 * 60 * 1000, 89 * 1000));
+        // long mixes count after 4 minutes
+        assert!(counts(60 * 60 * 1000, 4 * 60 * 1000));
+        assert!(!counts(20 * 1000, 20 * 1000));
+    }
+
+    #[test]
+    fn test_signature() {
+        let params: Vec<(String, String)> = [
+            ("method", "track.updateNowPlaying"),
+            ("track", "Voodoo Ray"),
+            ("artist", "A Guy Called Gerald"),
+            ("api_key", "xx"),
+            ("sk", "yy"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        assert_eq!(
+            signature(&params, "secret"),
+            "46f6b6933f59da79308bd48f39b9445b"
+        );
+    }
+
+    #[test]
+    fn test_lastfm_retry() {
+        let path = env::temp_dir().join(format!("scli-scrobbles-{}.json", process::id()));
+        let status = Arc::new(Mutex::new("503 Service Unavailable"));
+        let (endpoint, requests) = server(Arc::clone(&status));
+        let config = config::Scrobble {
+            lastfm: Some(config::LastFm {
+                api_key: String::from("xx"),
+                secret: String::from("secret"),
+                session_key: String::from("yy"),
+                endpoint: Some(endpoint),
+            }),
+            listenbrainz: None,
+        };
+
+        // offline, so it's kept
+        let scrobbler = Scrobbler::from_file(Some(path.clone()), config.clone());
+        scrobbler.scrobble(scrobble("Art of Minimal Techno"));
+        let request = requests.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(request.contains("method=track.scrobble"));
+        assert!(request.contains("api_sig="));
+        assert!(pending(&path, 1));
+        drop(scrobbler);
+
+        // picked up again next time, along with the next one
+        *status.lock().unwrap() = "200 OK";
+        let scrobbler = Scrobbler::from_file(Some(path.clone()), config);
+        scrobbler.scrobble(scrobble("Deep Minimal"));
+        let request = requests.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(request.contains("track%5B0%5D=Art+of+Minimal+Techno"));
+        assert!(pending(&path, 0));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_rejected() {
+        assert_eq!(Failure::from(StatusCode::BAD_REQUEST), Failure::Rejected);
+        assert_eq!(Failure::from(StatusCode::TOO_MANY_REQUESTS), Failure::Retry);
+        assert_eq!(Failure::from(StatusCode::BAD_GATEWAY), Failure::Retry);
+        assert_eq!(Failure::from(StatusCode::UNAUTHORIZED), Failure::Refused);
+        assert_eq!(Failure::from(StatusCode::FORBIDDEN), Failure::Refused);
+
+        let path = env::temp_dir().join(format!("scli-rejected-{}.json", process::id()));
+        let status = Arc::new(Mutex::new("400 Bad Request"));
+        let (endpoint, requests) = server(Arc::clone(&status));
+        let config = config::Scrobble {
+            lastfm: None,
+            listenbrainz: Some(config::ListenBrainz {
+                token: String::from("token"),
+                endpoint: Some(endpoint),
+            }),
+        };
+
+        // turned down, so set aside rather than sent again and again
+        let scrobbler = Scrobbler::from_file(Some(path.clone()), config);
+        scrobbler.scrobble(scrobble("Art of Minimal Techno"));
+        requests.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(saved(&path, |pending| pending.listenbrainz.is_empty()
+            && pending.rejected.listenbrainz.len() == 1));
+
+        // and the next one goes through on its own
+        *status.lock().unwrap() = "200 OK";
+        scrobbler.scrobble(scrobble("Deep Minimal"));
+        let request = requests.recv_timeout(Duration::from_secs(5)).unwrap();
+        let body = &request[request.find("\r\n\r\n").unwrap() + 4..];
+        let json: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(json["listen_type"], "single");
+        assert_eq!(
+            json["payload"][0]["track_metadata"]["track_name"],
+            "Deep Minimal"
+        );
+        assert!(saved(&path, |pending| pending.listenbrainz.is_empty()));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_refused() {
+        let path = env::temp_dir().join(format!("scli-refused-{}.json", process::id()));
+        let status = Arc::new(Mutex::new("401 Unauthorized"));
+        let (endpoint, requests) = server(Arc::clone(&status));
+        let config = config::Scrobble {
+            lastfm: None,
+            listenbrainz: Some(config::ListenBrainz {
+                token: String::from("expired"),
+                endpoint: Some(endpoint),
+            }),
+        };
+
+        // a bad token says nothing about the scrobbles, so they're all kept
+        let scrobbler = Scrobbler::from_file(Some(path.clone()), config);
+        scrobbler.scrobble(scrobble("Art of Minimal Techno"));
+        requests.recv_timeout(Duration::from_secs(5)).unwrap();
+        scrobbler.scrobble(scrobble("Deep Minimal"));
+        requests.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(saved(&path, |pending| pending.listenbrainz.len() == 2
+            && pending.rejected.listenbrainz.is_empty()));
+
+        // and go through once it's sorted
+        *status.lock().unwrap() = "200 OK";
+        scrobbler.scrobble(scrobble("Minimal Mix"));
+        assert!(saved(&path, |pending| pending.listenbrainz.is_empty()
+            && pending.rejected.listenbrainz.is_empty()));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_listenbrainz() {
+        let (endpoint, requests) = server(Arc::new(Mutex::new("200 OK")));
+        let config = config::Scrobble {
+            lastfm: None,
+            listenbrainz: Some(config::ListenBrainz {
+                token: String::from("token"),
+                endpoint: Some(endpoint),
+            }),
+        };
+        let scrobbler = Scrobbler::from_file(None, config);
+        let track = soundcloud::Track {
+            title: String::from("Mathew Jonson - Marionette"),
+            duration: 600000,
+            ..soundcloud::Track::default()
+        };
+
+        scrobbler.now_playing(&track);
+        let request = requests.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(request.starts_with("POST /1/submit-listens"));
+        assert!(request
+            .to_lowercase()
+            .contains("authorization: token token"));
+        let body = &request[request.find("\r\n\r\n").unwrap() + 4..];
+        let json: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(json["listen_type"], "playing_now");
+        assert_eq!(
+            json["payload"][0]["track_metadata"]["artist_name"],
+            "Mathew Jonson"
+        );
+        assert!(json["payload"][0].get("listened_at").is_none());
+
+        scrobbler.scrobble(scrobble("Art of Minimal Techno"));
+        let request = requests.recv_timeout(Duration::from_secs(5)).unwrap();
+        let body = &request[request.find("\r\n\r\n").unwrap() + 4..];
+        let json: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(json["listen_type"], "single");
+        assert_eq!(json["payload"][0]["listened_at"], 1577836800);
+    }
+}
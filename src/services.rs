@@ -0,0 +1,73 @@
+// Everything outside the terminal that follows the player: the control
+// socket, the HTTP remote, MPRIS, status bars and notifications, started
+// next to the event loop
+use crate::app::App;
+use crate::config;
+use crate::control;
+use crate::event::Events;
+#[cfg(all(target_os = "linux", feature = "mpris"))]
+use crate::mpris::Mpris;
+#[cfg(all(target_os = "linux", feature = "notifications"))]
+use crate::notify::Notifier;
+use crate::nowplaying::NowPlaying;
+use crate::remote::{self, Remote};
+
+/// What to start, decided before there's an event loop.
+pub struct Setup {
+    /// The HTTP remote's address and token, when it's on
+    pub remote: Option<remote::Listener>,
+    pub now_playing: config::NowPlaying,
+    #[cfg_attr(not(feature = "notifications"), allow(dead_code))]
+    pub notifications: bool,
+}
+
+pub struct Services {
+    _control: Option<control::Server>,
+    remote: Option<Remote>,
+    #[cfg(all(target_os = "linux", feature = "mpris"))]
+    mpris: Option<Mpris>,
+    now_playing: NowPlaying,
+    #[cfg(all(target_os = "linux", feature = "notifications"))]
+    notifier: Option<Notifier>,
+}
+
+impl Services {
+    pub fn start(events: &Events, setup: Setup) -> Services {
+        Services {
+            _control: control::start(events),
+            remote: setup
+                .remote
+                .map(|listener| Remote::serve(listener, events.sender())),
+            #[cfg(all(target_os = "linux", feature = "mpris"))]
+            mpris: Mpris::start(events.sender()),
+            now_playing: NowPlaying::new(setup.now_playing),
+            #[cfg(all(target_os = "linux", feature = "notifications"))]
+            notifier: if setup.notifications {
+                Notifier::start()
+            } else {
+                None
+            },
+        }
+    }
+
+    /// Pass the player's state on, after each event.
+    pub fn publish(&mut self, app: &App) {
+        let status = app.status();
+        self.now_playing.publish(&status);
+        #[cfg(all(target_os = "linux", feature = "notifications"))]
+        {
+            if let Some(ref mut notifier) = self.notifier {
+                notifier.publish(&status, app.artwork());
+            }
+        }
+        if let Some(ref remote) = self.remote {
+            remote.publish(status.clone());
+        }
+        #[cfg(all(target_os = "linux", feature = "mpris"))]
+        {
+            if let Some(ref mpris) = self.mpris {
+                mpris.publish(status);
+            }
+        }
+    }
+}
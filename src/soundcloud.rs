@@ -1,11 +1,17 @@
-use reqwest::{header, Response};
+use reqwest::{header, Method, RequestBuilder, Response, StatusCode};
 
 // scli soundcloud app client id
 const CLIENT_ID: &str = "nWYlHdW5jX1OyNQ9pipPhlUK9xDX8XFF";
 
+// streams are 128 kbps constant bit rate mp3, so a position in the
+// track maps to a byte offset in the stream, near enough to start at
+const STREAM_BYTES_PER_MS: u64 = 128 * 1000 / 8 / 1000;
+
+#[derive(Clone)]
 pub struct Client {
     client: reqwest::Client,
     client_id: String,
+    token: Option<String>,
     url: String,
 }
 
@@ -14,14 +20,95 @@ pub struct Resource {
     pub location: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Clone, Default, Deserialize)]
 pub struct Track {
+    pub id: u64,
     pub duration: u32,
     pub genre: String,
     pub waveform_url: String,
+    // missing for tracks that can't be streamed
+    #[serde(default)]
     pub stream_url: String,
     pub title: String,
     pub user: User,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tag_list: String,
+    #[serde(default)]
+    pub created_at: String,
+    #[serde(default)]
+    pub playback_count: Option<u32>,
+    #[serde(default, alias = "favoritings_count")]
+    pub likes_count: Option<u32>,
+    #[serde(default)]
+    pub reposts_count: Option<u32>,
+    #[serde(default)]
+    pub comment_count: Option<u32>,
+    #[serde(default)]
+    pub license: String,
+    #[serde(default)]
+    pub permalink_url: String,
+    #[serde(default)]
+    pub artwork_url: Option<String>,
+}
+
+impl Track {
+    /// Tags are space separated, with multi-word tags in quotes,
+    /// e.g. `techno "minimal techno"`
+    pub fn tags(&self) -> Vec<String> {
+        let mut tags = Vec::new();
+        let mut quoted = false;
+        let mut current = String::new();
+        for c in self.tag_list.chars() {
+            match c {
+                '"' => quoted = !quoted,
+                ' ' if !quoted => {
+                    if !current.is_empty() {
+                        tags.push(current.clone());
+                        current.clear();
+                    }
+                }
+                _ => current.push(c),
+            }
+        }
+        if !current.is_empty() {
+            tags.push(current);
+        }
+
+        tags
+    }
+}
+
+// What a url resolves to, tracks, playlists and users' tracks can be played
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum Resolved {
+    Track(Track),
+    Playlist(Playlist),
+    User(User),
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+struct Playlist {
+    tracks: Vec<Track>,
+}
+
+#[derive(Deserialize)]
+pub struct Comment {
+    pub body: String,
+    /// Position in the track the comment is pinned to, in ms
+    pub timestamp: Option<u32>,
+    pub user: User,
+}
+
+// A page of a paginated collection, see `linked_partitioning`
+#[derive(Deserialize)]
+struct Page<T> {
+    collection: Vec<T>,
+    next_href: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -31,8 +118,10 @@ pub struct Wave {
     pub samples: Vec<u16>,
 }
 
-#[derive(Deserialize)]
+#[derive(Clone, Default, Deserialize)]
 pub struct User {
+    #[serde(default)]
+    pub id: u64,
     pub username: String,
 }
 
@@ -46,24 +135,45 @@ impl Client {
         Client {
             client: rc,
             client_id: CLIENT_ID.to_string(),
+            token: None,
             url: "https://api.soundcloud.com".to_string(),
         }
     }
 
-    pub fn track(&self, url: String) -> Result<Track, reqwest::Error> {
-        self.resolve(url).and_then(|location: String| {
-            let mut resp = self
-                .client
-                .get(&location)
-                .header(header::USER_AGENT, "scli")
-                .query(&[("client_id", &self.client_id)])
-                .send()?;
+    /// Authenticate requests with an OAuth access token, required for
+    /// anything acting on behalf of a user (likes, reposts, comments)
+    pub fn token(mut self, token: Option<String>) -> Client {
+        self.token = token;
+        self
+    }
+
+    pub fn is_authenticated(&self) -> bool {
+        self.token.is_some()
+    }
+
+    /// Tracks to play for a url: a single track, all of a playlist's,
+    /// or a user's latest.
+    pub fn tracks(&self, url: String) -> Result<Vec<Track>, failure::Error> {
+        let location = self.resolve(url.clone())?;
+        let mut resp = self
+            .client
+            .get(&location)
+            .header(header::USER_AGENT, "scli")
+            .query(&[("client_id", &self.client_id)])
+            .send()?
+            .error_for_status()?;
 
-            Ok(resp.json()?)
-        })
+        match resp.json()? {
+            Resolved::Track(track) => Ok(vec![track]),
+            Resolved::Playlist(playlist) => Ok(playlist.tracks),
+            Resolved::User(user) => Ok(self.user_tracks(user.id)?),
+            Resolved::Other => failure::bail!("{} is not a track, playlist or user", url),
+        }
     }
 
-    pub fn stream(&self, stream_url: &String) -> Result<Response, reqwest::Error> {
+    /// Raw audio for a track, starting around `start_ms` into it when the
+    /// server takes the range, with a 206 status, otherwise from the start.
+    pub fn stream(&self, stream_url: &String, start_ms: u32) -> Result<Response, reqwest::Error> {
         // resolve stream url
         let mut resolve_resp = self
             .client
@@ -74,7 +184,12 @@ impl Client {
 
         // get raw audio from resolved resource
         let resource: Resource = resolve_resp.json()?;
-        let resp = self.client.get(&resource.location).send().unwrap();
+        let mut req = self.client.get(&resource.location);
+        if start_ms > 0 {
+            let offset = start_ms as u64 * STREAM_BYTES_PER_MS;
+            req = req.header(header::RANGE, format!("bytes={}-", offset));
+        }
+        let resp = req.send()?.error_for_status()?;
 
         Ok(resp)
     }
@@ -91,17 +206,193 @@ impl Client {
         Ok(resp.json()?)
     }
 
-    fn resolve(&self, url: String) -> Result<String, reqwest::Error> {
-        let endpoint = format!("{}{}", self.url, "/resolve");
+    /// All comments on a track, following pages until there are no more.
+    pub fn comments(&self, track_id: u64) -> Result<Vec<Comment>, reqwest::Error> {
+        let mut comments = Vec::new();
+        let mut next = Some(format!("{}/tracks/{}/comments", self.url, track_id));
+        while let Some(url) = next {
+            let mut req = self.client.get(&url).header(header::USER_AGENT, "scli");
+
+            // next_href carries the paging parameters, but not the client id
+            if !url.contains("client_id=") {
+                req = req.query(&[("client_id", &self.client_id)]);
+            }
+            if !url.contains("linked_partitioning=") {
+                req = req.query(&[("limit", "200"), ("linked_partitioning", "1")]);
+            }
+
+            let mut resp = req.send()?.error_for_status()?;
+
+            let page: Page<Comment> = resp.json()?;
+            comments.extend(page.collection);
+            next = page.next_href;
+        }
+
+        Ok(comments)
+    }
+
+    /// Tracks matching a search query, best match first.
+    pub fn search(&self, query: &str, limit: u32) -> Result<Vec<Track>, reqwest::Error> {
+        let url = format!("{}/tracks", self.url);
+        let mut resp = self
+            .client
+            .get(&url)
+            .header(header::USER_AGENT, "scli")
+            .query(&[("client_id", &self.client_id)])
+            .query(&[("q", query.to_string()), ("limit", limit.to_string())])
+            .send()?
+            .error_for_status()?;
+
+        Ok(resp.json()?)
+    }
+
+    /// A user's tracks, newest first.
+    pub fn user_tracks(&self, user_id: u64) -> Result<Vec<Track>, reqwest::Error> {
+        let url = format!("{}/users/{}/tracks", self.url, user_id);
+        let mut resp = self
+            .client
+            .get(&url)
+            .header(header::USER_AGENT, "scli")
+            .query(&[("client_id", &self.client_id)])
+            .query(&[("limit", "50")])
+            .send()?
+            .error_for_status()?;
+
+        Ok(resp.json()?)
+    }
+
+    /// Tracks similar to the given one, most related first.
+    pub fn related(&self, track_id: u64) -> Result<Vec<Track>, reqwest::Error> {
+        let url = format!("{}/tracks/{}/related", self.url, track_id);
+        let mut resp = self
+            .client
+            .get(&url)
+            .header(header::USER_AGENT, "scli")
+            .query(&[("client_id", &self.client_id)])
+            .query(&[("limit", "50")])
+            .send()?
+            .error_for_status()?;
+
+        Ok(resp.json()?)
+    }
+
+    pub fn artwork(&self, track: &Track) -> Result<Option<Vec<u8>>, reqwest::Error> {
+        let url = match track.artwork_url {
+            // the default artwork is tiny (100x100), ask for something bigger
+            Some(ref url) => url.replace("-large.", "-t300x300."),
+            None => return Ok(None),
+        };
+
+        let mut resp = self
+            .client
+            .get(&url)
+            .header(header::USER_AGENT, "scli")
+            .send()?
+            .error_for_status()?;
+
+        let mut bytes = Vec::new();
+        resp.copy_to(&mut bytes)?;
+        Ok(Some(bytes))
+    }
+
+    pub fn is_liked(&self, track_id: u64) -> Result<bool, failure::Error> {
+        let path = format!("/me/favorites/{}", track_id);
+        let resp = self.authorized(Method::GET, &path)?.send()?;
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+
+        resp.error_for_status()?;
+        Ok(true)
+    }
+
+    pub fn like(&self, track_id: u64) -> Result<(), failure::Error> {
+        let path = format!("/me/favorites/{}", track_id);
+        self.authorized(Method::PUT, &path)?
+            .send()?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    pub fn unlike(&self, track_id: u64) -> Result<(), failure::Error> {
+        let path = format!("/me/favorites/{}", track_id);
+        self.authorized(Method::DELETE, &path)?
+            .send()?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    pub fn repost(&self, track_id: u64) -> Result<(), failure::Error> {
+        let path = format!("/e1/me/track_reposts/{}", track_id);
+        self.authorized(Method::PUT, &path)?
+            .send()?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Post a comment pinned to `timestamp` (ms) in the track.
+    pub fn comment(
+        &self,
+        track_id: u64,
+        body: &str,
+        timestamp: u32,
+    ) -> Result<Comment, failure::Error> {
+        let path = format!("/tracks/{}/comments", track_id);
         let mut resp = self
+            .authorized(Method::POST, &path)?
+            .form(&[
+                ("comment[body]", body.to_string()),
+                ("comment[timestamp]", timestamp.to_string()),
+            ])
+            .send()?
+            .error_for_status()?;
+
+        Ok(resp.json()?)
+    }
+
+    fn authorized(&self, method: Method, path: &str) -> Result<RequestBuilder, failure::Error> {
+        let token = self
+            .token
+            .as_ref()
+            .ok_or_else(|| failure::format_err!("not logged in, set a token in the config file"))?;
+
+        Ok(self
+            .client
+            .request(method, &format!("{}{}", self.url, path))
+            .header(header::USER_AGENT, "scli")
+            .header(header::AUTHORIZATION, format!("OAuth {}", token)))
+    }
+
+    fn resolve(&self, url: String) -> Result<String, failure::Error> {
+        let endpoint = format!("{}{}", self.url, "/resolve");
+        let resp = self
             .client
             .get(&endpoint)
             .header(header::USER_AGENT, "scli")
             .query(&[("client_id", &self.client_id)])
-            .query(&[("url", url)])
+            .query(&[("url", &url)])
             .send()?;
+        if resp.status() == StatusCode::NOT_FOUND {
+            failure::bail!("nothing found on SoundCloud at {}", url);
+        }
+        let mut resp = resp.error_for_status()?;
 
         let resource: Resource = resp.json()?;
         Ok(resource.location)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tags() {
+        let track = Track {
+            tag_list: String::from("techno \"minimal techno\"  mix"),
+            ..Track::default()
+        };
+        assert_eq!(track.tags(), vec!["techno", "minimal techno", "mix"]);
+        assert!(Track::default().tags().is_empty());
+    }
+}
@@ -0,0 +1,298 @@
+use std::f32::consts::PI;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use tui::buffer::Buffer;
+use tui::layout::Rect;
+use tui::style::{Modifier, Style};
+use tui::widgets::Widget;
+
+use crate::tap;
+use crate::theme::Theme;
+
+// number of frames (per channel) run through the fft, must be a power of two
+const FFT_SIZE: usize = 2048;
+
+// number of frequency bands displayed
+const BANDS: usize = 32;
+
+// frequency range covered by the bands, in Hz
+const MIN_FREQ: f32 = 50.0;
+const MAX_FREQ: f32 = 16000.0;
+
+// levels below this (in dBFS) are not displayed
+const FLOOR_DB: f32 = -60.0;
+
+// how much a band can fall per analysis step, so bars decay smoothly
+const FALL: f32 = 0.04;
+
+const RATE: Duration = Duration::from_millis(33);
+
+/// Runs an fft over the latest tapped samples on a background thread,
+/// keeping a set of frequency band levels (0.0 to 1.0) up to date.
+pub struct Analyzer {
+    bands: Arc<Mutex<Vec<f32>>>,
+    // the thread stops once this is dropped along with the analyzer
+    _stop: mpsc::Sender<()>,
+}
+
+impl Analyzer {
+    pub fn new(samples: tap::Buffer) -> Analyzer {
+        let bands = Arc::new(Mutex::new(vec![0.0; BANDS]));
+        let (stop, stopped) = mpsc::channel();
+
+        {
+            let bands = Arc::clone(&bands);
+            thread::spawn(move || {
+                let window = hann(FFT_SIZE);
+                let mut position = samples.position();
+
+                loop {
+                    // only analyze when new samples are flowing, otherwise
+                    // (paused, stopped) let the bars fall back down
+                    let current = samples.position();
+                    let levels = if current != position {
+                        let channels = samples.channels().max(1) as usize;
+                        let latest = samples.latest(FFT_SIZE * channels);
+                        analyze(&latest, channels, samples.sample_rate(), &window)
+                    } else {
+                        vec![0.0; BANDS]
+                    };
+                    position = current;
+
+                    {
+                        let mut bands = bands.lock().unwrap();
+                        for (band, level) in bands.iter_mut().zip(levels) {
+                            *band = level.max(*band - FALL).max(0.0);
+                        }
+                    }
+
+                    if let Err(mpsc::RecvTimeoutError::Disconnected) = stopped.recv_timeout(RATE) {
+                        break;
+                    }
+                }
+            });
+        }
+
+        Analyzer { bands, _stop: stop }
+    }
+
+    pub fn bands(&self) -> Vec<f32> {
+        self.bands.lock().unwrap().clone()
+    }
+}
+
+// Downmix interleaved samples to mono, and compute the level of each band
+fn analyze(samples: &[i16], channels: usize, sample_rate: u32, window: &[f32]) -> Vec<f32> {
+    let mut buf = vec![Complex::default(); FFT_SIZE];
+    let frames = samples.len() / channels;
+    let offset = FFT_SIZE.saturating_sub(frames);
+    for (i, frame) in samples.chunks(channels).take(FFT_SIZE).enumerate() {
+        let sum: f32 = frame.iter().map(|&s| s as f32 / 32768.0).sum();
+        let mono = sum / channels as f32;
+        buf[offset + i].re = mono * window[offset + i];
+    }
+
+    fft(&mut buf);
+
+    // scale so that a full-scale sine reads as 0 dBFS
+    let gain: f32 = 2.0 / window.iter().sum::<f32>();
+    let resolution = sample_rate as f32 / FFT_SIZE as f32;
+    let max_freq = MAX_FREQ.min(sample_rate as f32 / 2.0);
+
+    (0..BANDS)
+        .map(|b| {
+            let lo = band_edge(b, max_freq) / resolution;
+            let hi = band_edge(b + 1, max_freq) / resolution;
+
+            // always cover at least one bin, low bands are narrower than a bin
+            let start = lo as usize;
+            let end = (hi.ceil() as usize).max(start + 1).min(FFT_SIZE / 2);
+
+            let peak = buf[start..end]
+                .iter()
+                .map(|c| c.norm() * gain)
+                .fold(0.0, f32::max);
+
+            let db = 20.0 * peak.max(1e-9).log10();
+            ((db - FLOOR_DB) / -FLOOR_DB).max(0.0).min(1.0)
+        })
+        .collect()
+}
+
+// Logarithmically spaced band edges between MIN_FREQ and max_freq
+fn band_edge(band: usize, max_freq: f32) -> f32 {
+    MIN_FREQ * (max_freq / MIN_FREQ).powf(band as f32 / BANDS as f32)
+}
+
+fn hann(n: usize) -> Vec<f32> {
+    (0..n)
+        .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / (n - 1) as f32).cos())
+        .collect()
+}
+
+#[derive(Clone, Copy, Default)]
+struct Complex {
+    re: f32,
+    im: f32,
+}
+
+impl Complex {
+    fn norm(&self) -> f32 {
+        (self.re * self.re + self.im * self.im).sqrt()
+    }
+}
+
+// In-place iterative radix-2 fft, buf.len() must be a power of two
+fn fft(buf: &mut [Complex]) {
+    let n = buf.len();
+
+    // bit reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            buf.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let a = buf[start + k];
+                let b = buf[start + k + len / 2];
+                let t = Complex {
+                    re: b.re * cos - b.im * sin,
+                    im: b.re * sin + b.im * cos,
+                };
+                buf[start + k] = Complex {
+                    re: a.re + t.re,
+                    im: a.im + t.im,
+                };
+                buf[start + k + len / 2] = Complex {
+                    re: a.re - t.re,
+                    im: a.im - t.im,
+                };
+            }
+        }
+        len <<= 1;
+    }
+}
+
+pub struct Spectrum {
+    pub bands: Vec<f32>,
+    pub theme: Theme,
+}
+
+impl Default for Spectrum {
+    fn default() -> Spectrum {
+        Spectrum {
+            bands: Vec::new(),
+            theme: Theme::default(),
+        }
+    }
+}
+
+impl Spectrum {
+    pub fn bands(&mut self, bands: Vec<f32>) -> &mut Spectrum {
+        self.bands = bands;
+        self
+    }
+
+    pub fn theme(&mut self, theme: Theme) -> &mut Spectrum {
+        self.theme = theme;
+        self
+    }
+}
+
+// partial blocks, in eighths of a cell
+const BLOCKS: [&str; 9] = [" ", "▁", "▂", "▃", "▄", "▅", "▆", "▇", "█"];
+
+impl Widget for Spectrum {
+    fn draw(&mut self, area: Rect, buf: &mut Buffer) {
+        if area.width == 0 || area.height == 0 || self.bands.is_empty() {
+            return;
+        }
+
+        let style = Style::default()
+            .fg(self.theme.accent())
+            .modifier(Modifier::BOLD);
+        for x in 0..area.width {
+            // spread the bands evenly across the available width
+            let band = x as usize * self.bands.len() / area.width as usize;
+            let level = self.bands[band].max(0.0).min(1.0);
+
+            // bar height in eighths of a cell, drawn from the bottom up
+            let mut eighths = (level * area.height as f32 * 8.0) as u16;
+            for y in (0..area.height).rev() {
+                let fill = eighths.min(8);
+                eighths -= fill;
+                buf.set_string(
+                    area.left() + x,
+                    area.top() + y,
+                    BLOCKS[fill as usize],
+                    style,
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fft_peak() {
+        // sine wave landing exactly on bin 8
+        let n = 64;
+        let mut buf: Vec<Complex> = (0..n)
+            .map(|i| Complex {
+                re: (2.0 * PI * 8.0 * i as f32 / n as f32).sin(),
+                im: 0.0,
+            })
+            .collect();
+
+        fft(&mut buf);
+
+        let peak = (0..n / 2)
+            .max_by(|&a, &b| buf[a].norm().partial_cmp(&buf[b].norm()).unwrap())
+            .unwrap();
+        assert_eq!(peak, 8);
+        assert!((buf[8].norm() - n as f32 / 2.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_analyze_silence() {
+        let window = hann(FFT_SIZE);
+        let bands = analyze(&vec![0; FFT_SIZE * 2], 2, 44100, &window);
+        assert_eq!(bands.len(), BANDS);
+        assert!(bands.iter().all(|&b| b == 0.0));
+    }
+
+    #[test]
+    fn test_analyze_tone() {
+        // full scale 1kHz tone should peak in the band containing 1kHz
+        let window = hann(FFT_SIZE);
+        let samples: Vec<i16> = (0..FFT_SIZE)
+            .map(|i| ((2.0 * PI * 1000.0 * i as f32 / 44100.0).sin() * 32767.0) as i16)
+            .collect();
+        let bands = analyze(&samples, 1, 44100, &window);
+
+        let loudest = (0..BANDS)
+            .max_by(|&a, &b| bands[a].partial_cmp(&bands[b]).unwrap())
+            .unwrap();
+        assert!(band_edge(loudest, MAX_FREQ) <= 1000.0);
+        assert!(band_edge(loudest + 1, MAX_FREQ) >= 1000.0);
+        assert!(bands[loudest] > 0.9);
+    }
+}
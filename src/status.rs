@@ -3,6 +3,9 @@ use tui::layout::Rect;
 use tui::style::Style;
 use tui::widgets::Widget;
 
+use crate::level;
+use crate::queue;
+
 pub struct Clock {
     pub elapsed_ms: u32,
     pub total_ms: u32,
@@ -18,7 +21,7 @@ impl Default for Clock {
 }
 
 impl Clock {
-    fn format(value_ms: u32) -> String {
+    pub fn format(value_ms: u32) -> String {
         let hours = value_ms / (3600 * 1000); // hours in ms
         let minutes = value_ms / (60 * 1000) % 60; // minutes in ms
         let seconds = value_ms % (60 * 1000) / 1000; // seconds in ms
@@ -29,12 +32,39 @@ impl Clock {
             format!("{:02}:{:02}", minutes, seconds)
         }
     }
+
+    /// Parse a time as written by `format`, e.g. "43:10" or "1:02:03",
+    /// or a plain number of seconds. None past what fits in a u32 of ms.
+    pub fn parse(value: &str) -> Option<u32> {
+        let mut parts = Vec::new();
+        for part in value.split(':') {
+            parts.push(part.parse::<u32>().ok()?);
+        }
+        if parts.len() > 3 || parts[1..].iter().any(|&p| p >= 60) {
+            return None;
+        }
+
+        let seconds = parts.iter().try_fold(0u32, |total, &p| {
+            total.checked_mul(60).and_then(|total| total.checked_add(p))
+        })?;
+        seconds.checked_mul(1000)
+    }
 }
 
+// width of each channel's bar in the level meter
+const METER_BAR: u16 = 8;
+
 pub struct Status {
     pub is_playing: bool,
     pub volume: u8,
+    pub speed: f32,
     pub clock: Clock,
+    pub levels: Option<level::Reading>,
+    pub liked: Option<bool>,
+    pub autoplay: bool,
+    pub shuffle: bool,
+    pub repeat: queue::Repeat,
+    pub recording: bool,
 }
 
 impl Default for Status {
@@ -42,7 +72,14 @@ impl Default for Status {
         Status {
             is_playing: false,
             volume: 0,
+            speed: 1.0,
             clock: Clock::default(),
+            levels: None,
+            liked: None,
+            autoplay: false,
+            shuffle: false,
+            repeat: queue::Repeat::Off,
+            recording: false,
         }
     }
 }
@@ -58,37 +95,136 @@ impl Status {
         self
     }
 
+    pub fn speed(&mut self, speed: f32) -> &mut Status {
+        self.speed = speed;
+        self
+    }
+
     pub fn clock(&mut self, clock: Clock) -> &mut Status {
         self.clock = clock;
         self
     }
+
+    pub fn levels(&mut self, levels: level::Reading) -> &mut Status {
+        self.levels = Some(levels);
+        self
+    }
+
+    /// Whether the track is liked, None when that isn't known (logged out).
+    pub fn liked(&mut self, liked: Option<bool>) -> &mut Status {
+        self.liked = liked;
+        self
+    }
+
+    pub fn autoplay(&mut self, autoplay: bool) -> &mut Status {
+        self.autoplay = autoplay;
+        self
+    }
+
+    pub fn shuffle(&mut self, shuffle: bool) -> &mut Status {
+        self.shuffle = shuffle;
+        self
+    }
+
+    pub fn repeat(&mut self, repeat: queue::Repeat) -> &mut Status {
+        self.repeat = repeat;
+        self
+    }
+
+    pub fn recording(&mut self, recording: bool) -> &mut Status {
+        self.recording = recording;
+        self
+    }
 }
 
 impl Widget for Status {
     fn draw(&mut self, area: Rect, buf: &mut Buffer) {
-        // show elapsed time
+        if area.width == 0 || area.height == 0 {
+            return;
+        }
+
+        // show elapsed time, always, or as much of it as fits
         let elapsed = Clock::format(self.clock.elapsed_ms);
         let elapsed_x = area.left();
-        buf.set_string(elapsed_x, area.top(), &elapsed, Style::default());
+        buf.set_stringn(
+            elapsed_x,
+            area.top(),
+            &elapsed,
+            area.width as usize,
+            Style::default(),
+        );
 
-        // show state
-        let state = if self.is_playing { "Playing" } else { "Paused" };
-        let state_x = elapsed_x + elapsed.len() as u16 + 2;
-        buf.set_string(state_x, area.top(), state, Style::default());
+        // show total time, if it fits without touching the elapsed time
+        let total = Clock::format(self.clock.total_ms);
+        let total_len = total.len() as u16;
+        let end = if area.width >= elapsed.len() as u16 + 2 + total_len {
+            let total_x = area.right() - total_len;
+            buf.set_string(total_x, area.top(), total, Style::default());
+            total_x
+        } else {
+            area.right()
+        };
 
-        // show volume
+        // show state, recording, like, play modes, speed, then volume, for as long as there's room before the total
+        let state = if self.is_playing { "Playing" } else { "Paused" };
+        let recording = if self.recording { "Recording" } else { "" };
+        let liked = match self.liked {
+            Some(true) => "♥ Liked",
+            Some(false) => "♡",
+            None => "",
+        };
+        let autoplay = if self.autoplay { "Autoplay" } else { "" };
+        let shuffle = if self.shuffle { "Shuffle" } else { "" };
+        let repeat = match self.repeat {
+            queue::Repeat::Off => "",
+            queue::Repeat::All => "Repeat all",
+            queue::Repeat::One => "Repeat one",
+        };
+        // only worth mentioning when it isn't normal
+        let speed = if (self.speed - 1.0).abs() > std::f32::EPSILON {
+            format!("{}x", self.speed)
+        } else {
+            String::new()
+        };
         let volume = format!("Volume: {}%", self.volume);
-        let volume_x = state_x + state.len() as u16 + 2;
-        buf.set_string(volume_x, area.top(), volume, Style::default());
 
-        // show total time
-        let total = Clock::format(self.clock.total_ms);
-        buf.set_string(
-            area.right() - total.len() as u16,
-            area.top(),
-            total,
-            Style::default(),
-        );
+        let mut x = elapsed_x + elapsed.len() as u16;
+        for piece in [
+            state,
+            recording,
+            liked,
+            autoplay,
+            shuffle,
+            repeat,
+            speed.as_str(),
+            volume.as_str(),
+        ]
+        .iter()
+        {
+            if piece.is_empty() {
+                continue;
+            }
+
+            let piece_x = x + 2;
+            let piece_len = piece.chars().count() as u16;
+            if piece_x + piece_len + 2 > end {
+                return;
+            }
+
+            buf.set_string(piece_x, area.top(), piece, Style::default());
+            x = piece_x + piece_len;
+        }
+
+        // show level meter next to the volume, if there's room for it
+        if let Some(reading) = self.levels {
+            let meter_x = x + 2;
+            let meter_width = level::Vu::width(METER_BAR);
+            if meter_x + meter_width + 2 <= end {
+                level::Vu::default()
+                    .reading(reading)
+                    .draw(Rect::new(meter_x, area.top(), meter_width, 1), buf);
+            }
+        }
     }
 }
 
@@ -108,4 +244,24 @@ mod tests {
         assert_eq!(Clock::format(4112738), "01:08:32");
         assert_eq!(Clock::format(8688931), "02:24:48");
     }
+
+    #[test]
+    fn test_clock_parse() {
+        assert_eq!(Clock::parse("00:00"), Some(0));
+        assert_eq!(Clock::parse("90"), Some(90 * 1000));
+        assert_eq!(Clock::parse("43:10"), Some((43 * 60 + 10) * 1000));
+        assert_eq!(Clock::parse("1:02:03"), Some((3600 + 2 * 60 + 3) * 1000));
+        assert_eq!(Clock::parse(&Clock::format(4112000)), Some(4112000));
+
+        assert_eq!(Clock::parse(""), None);
+        assert_eq!(Clock::parse("1:60"), None);
+        assert_eq!(Clock::parse("1:2:3:4"), None);
+        assert_eq!(Clock::parse("ten"), None);
+
+        // too long to count in ms
+        assert_eq!(Clock::parse("4294967"), Some(4294967000));
+        assert_eq!(Clock::parse("4294968"), None);
+        assert_eq!(Clock::parse("99999999:00"), None);
+        assert_eq!(Clock::parse("4294967295:59:59"), None);
+    }
 }
@@ -0,0 +1,347 @@
+// Playing faster or slower without changing pitch, by WSOLA (waveform
+// similarity overlap-add): overlapping windows of the track are added
+// back together closer together or further apart than they were taken,
+// each one nudged to where it lines up best with the one before
+use rodio::source::Source;
+use std::f32::consts::PI;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+pub const MIN_SPEED: f32 = 0.5;
+pub const MAX_SPEED: f32 = 3.0;
+
+pub struct Stretch<S>
+where
+    S: Source<Item = i16>,
+{
+    input: S,
+    speed: Arc<Mutex<f32>>,
+    channels: usize,
+    sample_rate: u32,
+    // frames between windows on the way out, half a window
+    hop: usize,
+    // how far a window can be nudged either way, in frames
+    tolerance: usize,
+    weights: Vec<f32>,
+    // interleaved input, from the oldest frame that's still needed
+    buffer: Vec<f32>,
+    // frames in the buffer when the input ran out
+    end: Option<usize>,
+    // where the next window should start going by the speed, and where
+    // it would start to carry straight on from the last one
+    position: f64,
+    next: Option<usize>,
+    // second half of the last window, to add to the first half of the next
+    overlap: Vec<f32>,
+    output: Vec<i16>,
+    emitted: usize,
+}
+
+impl<S> Stretch<S>
+where
+    S: Source<Item = i16>,
+{
+    /// Play `input` at `speed`, which can change as it plays.
+    pub fn new(input: S, speed: Arc<Mutex<f32>>) -> Stretch<S> {
+        let channels = input.channels().max(1) as usize;
+        let sample_rate = input.sample_rate();
+        // 25ms windows, short enough not to smear beats, long enough for low notes
+        let hop = (sample_rate as usize / 80).max(1);
+        let window = hop * 2;
+        // a periodic Hann window, so overlapping halves add up to exactly one
+        let weights = (0..window)
+            .map(|n| 0.5 - 0.5 * (2.0 * PI * n as f32 / window as f32).cos())
+            .collect();
+
+        Stretch {
+            input,
+            speed,
+            channels,
+            sample_rate,
+            hop,
+            tolerance: hop / 2,
+            weights,
+            // the first window starts half a window early, on silence, so the
+            // track doesn't fade in
+            buffer: vec![0.0; hop * channels],
+            end: None,
+            position: 0.0,
+            next: None,
+            overlap: vec![0.0; hop * channels],
+            output: Vec::with_capacity(hop * channels),
+            emitted: 0,
+        }
+    }
+
+    // Add the next window, returns false once the input has run out
+    fn step(&mut self) -> bool {
+        let speed = self.speed.lock().unwrap().max(MIN_SPEED).min(MAX_SPEED) as f64;
+        let nominal = self.position.round() as usize;
+        if self.end.map_or(false, |end| nominal >= end) {
+            return false;
+        }
+
+        // only what's still needed is kept
+        let oldest = nominal
+            .saturating_sub(self.tolerance)
+            .min(self.next.unwrap_or(nominal));
+        self.buffer.drain(..oldest * self.channels);
+        self.position -= oldest as f64;
+        self.next = self.next.map(|next| next - oldest);
+        self.end = self.end.map(|end| end - oldest);
+        let nominal = nominal - oldest;
+
+        let furthest = (nominal + self.tolerance).max(self.next.unwrap_or(0));
+        self.fill(furthest + self.hop * 2);
+
+        // at normal speed every window carries straight on, giving back the input
+        let start = match self.next {
+            Some(next) if speed == 1.0 => {
+                self.position = next as f64;
+                next
+            }
+            Some(next) => self.search(next, nominal),
+            None => nominal,
+        };
+
+        let channels = self.channels;
+        let half = self.hop * channels;
+        let window = &self.buffer[start * channels..start * channels + half * 2];
+        self.output.clear();
+        self.emitted = 0;
+        for i in 0..half {
+            let sample = self.overlap[i] + window[i] * self.weights[i / channels];
+            self.output
+                .push(sample.round().max(-32768.0).min(32767.0) as i16);
+            self.overlap[i] = window[half + i] * self.weights[self.hop + i / channels];
+        }
+        // the first half of the first window was the silence before the track,
+        // and nothing after the end of it is kept
+        if self.next.is_none() {
+            self.output.clear();
+        }
+        if let Some(end) = self.end {
+            self.output
+                .truncate(end.saturating_sub(start).min(self.hop) * channels);
+        }
+
+        self.next = Some(start + self.hop);
+        self.position += self.hop as f64 * speed;
+        true
+    }
+
+    // Read input until there are `frames` frames, then silence once it's run out
+    fn fill(&mut self, frames: usize) {
+        while self.buffer.len() < frames * self.channels {
+            match self.input.next() {
+                Some(sample) => self.buffer.push(sample as f32),
+                None => {
+                    if self.end.is_none() {
+                        self.end = Some(self.buffer.len() / self.channels);
+                    }
+                    self.buffer.resize(frames * self.channels, 0.0);
+                }
+            }
+        }
+    }
+
+    // Where near `nominal` a window starts most like the one at `next`,
+    // what would have carried straight on from the last
+    fn search(&self, next: usize, nominal: usize) -> usize {
+        let target = self.mono(next);
+        let score = |start: usize| {
+            let candidate = self.mono(start);
+            let (dot, energy) = target
+                .iter()
+                .zip(candidate.iter())
+                .fold((0.0, 0.0), |(dot, energy), (t, c)| {
+                    (dot + t * c, energy + c * c)
+                });
+            dot / (energy + 1.0f32).sqrt()
+        };
+        let best = |starts: &mut dyn Iterator<Item = usize>| {
+            starts
+                .map(|start| (start, score(start)))
+                .fold((nominal, std::f32::MIN), |best, (start, score)| {
+                    if score > best.1 {
+                        (start, score)
+                    } else {
+                        best
+                    }
+                })
+                .0
+        };
+
+        // roughly, then to the frame
+        let low = nominal.saturating_sub(self.tolerance);
+        let high = nominal + self.tolerance;
+        let rough = best(&mut (low..=high).step_by(2));
+        best(&mut (rough.saturating_sub(1).max(low)..=(rough + 1).min(high)))
+    }
+
+    // Every other frame of the half window at `start`, with the channels
+    // mixed, which is plenty to line windows up by
+    fn mono(&self, start: usize) -> Vec<f32> {
+        let channels = self.channels;
+        (start..start + self.hop)
+            .step_by(2)
+            .map(|frame| {
+                self.buffer[frame * channels..(frame + 1) * channels]
+                    .iter()
+                    .sum()
+            })
+            .collect()
+    }
+}
+
+impl<S> Iterator for Stretch<S>
+where
+    S: Source<Item = i16>,
+{
+    type Item = i16;
+
+    #[inline]
+    fn next(&mut self) -> Option<i16> {
+        while self.emitted == self.output.len() {
+            if !self.step() {
+                return None;
+            }
+        }
+
+        let sample = self.output[self.emitted];
+        self.emitted += 1;
+        Some(sample)
+    }
+}
+
+impl<S> Source for Stretch<S>
+where
+    S: Source<Item = i16>,
+{
+    #[inline]
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    #[inline]
+    fn channels(&self) -> u16 {
+        self.channels as u16
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A stereo sine at 44.1kHz
+    struct Sine {
+        frequency: f32,
+        frames: usize,
+        sample: usize,
+    }
+
+    impl Sine {
+        fn new(frequency: f32, frames: usize) -> Sine {
+            Sine {
+                frequency,
+                frames,
+                sample: 0,
+            }
+        }
+    }
+
+    impl Iterator for Sine {
+        type Item = i16;
+
+        fn next(&mut self) -> Option<i16> {
+            if self.sample == self.frames * 2 {
+                return None;
+            }
+            let t = (self.sample / 2) as f32 / 44100.0;
+            self.sample += 1;
+            Some(((2.0 * PI * self.frequency * t).sin() * 10000.0) as i16)
+        }
+    }
+
+    impl Source for Sine {
+        fn current_frame_len(&self) -> Option<usize> {
+            None
+        }
+
+        fn channels(&self) -> u16 {
+            2
+        }
+
+        fn sample_rate(&self) -> u32 {
+            44100
+        }
+
+        fn total_duration(&self) -> Option<Duration> {
+            None
+        }
+    }
+
+    fn stretch(speed: f32, frames: usize) -> Vec<i16> {
+        Stretch::new(Sine::new(441.0, frames), Arc::new(Mutex::new(speed))).collect()
+    }
+
+    // Frequency of the left channel, by counting where it crosses zero going up
+    fn frequency(samples: &[i16]) -> f32 {
+        let left: Vec<i16> = samples.iter().step_by(2).cloned().collect();
+        let crossings = left.windows(2).filter(|w| w[0] < 0 && w[1] >= 0).count();
+        crossings as f32 * 44100.0 / left.len() as f32
+    }
+
+    #[test]
+    fn test_normal_speed() {
+        // the same samples back, no more and no fewer
+        let samples: Vec<i16> = Sine::new(441.0, 10000).collect();
+        assert_eq!(stretch(1.0, 10000), samples);
+    }
+
+    #[test]
+    fn test_length() {
+        // a second, give or take a window
+        let frames = |samples: Vec<i16>| samples.len() as i64 / 2;
+        assert!((frames(stretch(2.0, 88200)) - 44100).abs() < 1200);
+        assert!((frames(stretch(0.5, 22050)) - 44100).abs() < 1200);
+        assert!((frames(stretch(3.0, 132300)) - 44100).abs() < 1200);
+    }
+
+    #[test]
+    fn test_pitch() {
+        for &speed in &[0.5, 1.5, 3.0] {
+            let samples = stretch(speed, 44100);
+            let frequency = frequency(&samples);
+            assert!(
+                (frequency - 441.0).abs() < 441.0 * 0.02,
+                "{}Hz at {}x",
+                frequency,
+                speed
+            );
+        }
+    }
+
+    #[test]
+    fn test_change_speed() {
+        let speed = Arc::new(Mutex::new(1.0));
+        let mut stretch = Stretch::new(Sine::new(441.0, 88200), Arc::clone(&speed));
+        let start: Vec<i16> = stretch.by_ref().take(44100).collect();
+        *speed.lock().unwrap() = 2.0;
+        let rest = stretch.count() as i64 / 2;
+
+        // the first quarter second as it was, then the rest twice as fast
+        assert_eq!(start, Sine::new(441.0, 22050).collect::<Vec<_>>());
+        assert!((rest - 33075).abs() < 1200);
+    }
+}
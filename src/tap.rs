@@ -0,0 +1,152 @@
+// A pass-through source filter that copies decoded samples into a
+// shared buffer, so they can be analyzed outside of the audio thread
+use rodio::source::Source;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// number of samples (across all channels) kept for analysis
+const CAPACITY: usize = 16384;
+
+// number of samples collected on the audio thread before taking the lock
+const CHUNK: usize = 512;
+
+pub struct Tap<S>
+where
+    S: Source<Item = i16>,
+{
+    input: S,
+    buffer: Buffer,
+    pending: Vec<i16>,
+}
+
+impl<S> Tap<S>
+where
+    S: Source<Item = i16>,
+{
+    pub fn new(input: S, buffer: Buffer) -> Tap<S> {
+        Tap {
+            input,
+            buffer,
+            pending: Vec::with_capacity(CHUNK),
+        }
+    }
+
+    fn flush(&mut self) {
+        let channels = self.input.channels();
+        let sample_rate = self.input.sample_rate();
+        self.buffer.push(&self.pending, channels, sample_rate);
+        self.pending.clear();
+    }
+}
+
+impl<S> Iterator for Tap<S>
+where
+    S: Source<Item = i16>,
+{
+    type Item = i16;
+
+    #[inline]
+    fn next(&mut self) -> Option<i16> {
+        let sample = self.input.next();
+        match sample {
+            Some(v) => {
+                self.pending.push(v);
+                if self.pending.len() >= CHUNK {
+                    self.flush();
+                }
+            }
+            None => {
+                if !self.pending.is_empty() {
+                    self.flush();
+                }
+            }
+        }
+
+        sample
+    }
+}
+
+impl<S> Source for Tap<S>
+where
+    S: Source<Item = i16>,
+{
+    #[inline]
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    #[inline]
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
+
+/// Shared handle to the most recently played samples, interleaved by channel.
+#[derive(Clone)]
+pub struct Buffer {
+    inner: Arc<Mutex<Inner>>,
+}
+
+struct Inner {
+    samples: VecDeque<i16>,
+    channels: u16,
+    sample_rate: u32,
+    pushed: u64,
+}
+
+impl Buffer {
+    pub fn new() -> Buffer {
+        Buffer {
+            inner: Arc::new(Mutex::new(Inner {
+                samples: VecDeque::with_capacity(CAPACITY),
+                channels: 2,
+                sample_rate: 44100,
+                pushed: 0,
+            })),
+        }
+    }
+
+    fn push(&self, samples: &[i16], channels: u16, sample_rate: u32) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.channels = channels;
+        inner.sample_rate = sample_rate;
+        inner.samples.extend(samples);
+        inner.pushed += samples.len() as u64;
+
+        let len = inner.samples.len();
+        if len > CAPACITY {
+            inner.samples.drain(..len - CAPACITY);
+        }
+    }
+
+    /// Returns up to `n` of the latest samples, oldest first.
+    pub fn latest(&self, n: usize) -> Vec<i16> {
+        let inner = self.inner.lock().unwrap();
+        let skip = inner.samples.len().saturating_sub(n);
+        inner.samples.iter().skip(skip).cloned().collect()
+    }
+
+    /// Total number of samples that have flowed through the tap.
+    pub fn position(&self) -> u64 {
+        self.inner.lock().unwrap().pushed
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.inner.lock().unwrap().channels
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.inner.lock().unwrap().sample_rate
+    }
+}
@@ -0,0 +1,71 @@
+// Accent colour used for the waveform, spectrum and borders
+use tui::style::Color;
+
+// name and accent colour of each theme, the first is the default
+const THEMES: [(&str, (u8, u8, u8)); 5] = [
+    ("soundcloud", (237, 97, 43)),
+    ("blue", (66, 135, 245)),
+    ("green", (80, 200, 120)),
+    ("purple", (160, 100, 230)),
+    ("mono", (220, 220, 220)),
+];
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Theme {
+    accent: (u8, u8, u8),
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme {
+            accent: THEMES[0].1,
+        }
+    }
+}
+
+impl Theme {
+    pub fn named(name: &str) -> Option<Theme> {
+        THEMES
+            .iter()
+            .find(|theme| theme.0 == name)
+            .map(|theme| Theme { accent: theme.1 })
+    }
+
+    pub fn names() -> Vec<&'static str> {
+        THEMES.iter().map(|theme| theme.0).collect()
+    }
+
+    pub fn accent(&self) -> Color {
+        let (r, g, b) = self.accent;
+        Color::Rgb(r, g, b)
+    }
+
+    /// Somewhere between white (0.0) and the accent colour (1.0).
+    pub fn blend(&self, amount: f32) -> Color {
+        let amount = amount.max(0.0).min(1.0);
+        let mix = |c: u8| (255.0 - (255.0 - c as f32) * amount).round() as u8;
+        let (r, g, b) = self.accent;
+        Color::Rgb(mix(r), mix(g), mix(b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme() {
+        let theme = Theme::default();
+        assert_eq!(theme.accent(), Color::Rgb(237, 97, 43));
+        assert_eq!(theme.blend(0.0), Color::Rgb(255, 255, 255));
+        assert_eq!(theme.blend(1.0), theme.accent());
+        assert_eq!(Theme::named("soundcloud"), Some(theme));
+
+        assert_eq!(
+            Theme::named("blue").map(|t| t.accent()),
+            Some(Color::Rgb(66, 135, 245))
+        );
+        assert_eq!(Theme::named("nope"), None);
+        assert!(Theme::names().contains(&"mono"));
+    }
+}
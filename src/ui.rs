@@ -0,0 +1,481 @@
+use tui::backend::Backend;
+use tui::layout::{Alignment, Rect};
+use tui::style::{Modifier, Style};
+use tui::widgets::{Block, Borders, Paragraph, SelectableList, Text, Widget};
+use tui::Frame;
+
+use crate::artwork;
+use crate::comments;
+use crate::info;
+use crate::layout;
+use crate::level;
+use crate::popup;
+use crate::queue;
+use crate::soundcloud;
+use crate::spectrum;
+use crate::status;
+use crate::theme;
+use crate::wave;
+
+// What to show in place of the waveform
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum View {
+    Wave,
+    Spectrum,
+    Both,
+}
+
+impl View {
+    pub fn next(self) -> View {
+        match self {
+            View::Wave => View::Spectrum,
+            View::Spectrum => View::Both,
+            View::Both => View::Wave,
+        }
+    }
+}
+
+/// A list to pick from, shown in place of the header and waveform.
+pub struct Pane {
+    pub title: String,
+    pub items: Vec<String>,
+    pub selected: usize,
+}
+
+/// Everything needed to draw a single frame of the player.
+pub struct Screen<'a> {
+    pub track: &'a soundcloud::Track,
+    pub wave: &'a soundcloud::Wave,
+    pub view: View,
+    pub progress: f32,
+    pub markers: Vec<f32>,
+    pub comment: Option<&'a soundcloud::Comment>,
+    pub bands: Vec<f32>,
+    pub is_playing: bool,
+    pub volume: u8,
+    pub speed: f32,
+    pub elapsed_ms: u32,
+    pub levels: level::Reading,
+    pub liked: Option<bool>,
+    pub autoplay: bool,
+    pub shuffle: bool,
+    pub repeat: queue::Repeat,
+    pub recording: bool,
+    pub message: Option<&'a str>,
+    pub info: bool,
+    pub artwork: Option<&'a artwork::Artwork>,
+    pub help: Option<Vec<(String, String)>>,
+    pub prompt: Option<(String, String)>,
+    pub resume: Option<u32>,
+    pub pane: Option<Pane>,
+    pub theme: theme::Theme,
+}
+
+pub fn draw<B: Backend>(f: &mut Frame<B>, screen: &Screen) {
+    let areas = layout::split(f.size());
+
+    // panes take the place of the header and waveform
+    let body = Rect::new(
+        areas.header.left(),
+        areas.header.top(),
+        areas.header.width,
+        areas.wave.bottom().max(areas.header.bottom()) - areas.header.top(),
+    );
+    let covered = screen.info || screen.pane.is_some();
+
+    if let Some(ref pane) = screen.pane {
+        let items = if pane.items.is_empty() {
+            vec!["Nothing here yet".to_string()]
+        } else {
+            pane.items.clone()
+        };
+        SelectableList::default()
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(screen.theme.accent()))
+                    .title(&pane.title)
+                    .title_style(Style::default().modifier(Modifier::BOLD)),
+            )
+            .items(&items)
+            .select(if pane.items.is_empty() {
+                None
+            } else {
+                Some(pane.selected)
+            })
+            .highlight_style(Style::default().modifier(Modifier::BOLD))
+            .highlight_symbol(">")
+            .render(f, body);
+    } else if screen.info {
+        // track details
+        info::Info::new(screen.track)
+            .artwork(screen.artwork.cloned())
+            .render(f, body);
+    }
+
+    // track info
+    if areas.header.area() > 0 && !covered {
+        let username = Text::styled(&screen.track.user.username, Style::default());
+        let title = Text::styled(
+            &screen.track.title,
+            Style::default().modifier(Modifier::BOLD),
+        );
+        let separator = match areas.mode {
+            layout::Mode::Compact => Text::raw(" - "),
+            _ => Text::raw("\n"),
+        };
+
+        let header = [username, separator, title];
+        Paragraph::new(header.iter())
+            .alignment(Alignment::Left)
+            .render(f, areas.header);
+    }
+
+    // waveform and/or live spectrum
+    if areas.wave.area() > 0 && !covered {
+        let (wave_area, spectrum_area) = match screen.view {
+            View::Wave => (Some(areas.wave), None),
+            View::Spectrum => (None, Some(areas.wave)),
+            View::Both => {
+                let (first, second) = halves(areas.wave, areas.mode == layout::Mode::Expanded);
+                (Some(first), Some(second))
+            }
+        };
+
+        if let Some(area) = wave_area {
+            wave::Wave::default()
+                .theme(screen.theme)
+                .width(screen.wave.width)
+                .height(screen.wave.height)
+                .samples(screen.wave.samples.clone())
+                .progress(screen.progress)
+                .markers(screen.markers.clone())
+                .render(f, area);
+        }
+
+        if let Some(area) = spectrum_area {
+            spectrum::Spectrum::default()
+                .theme(screen.theme)
+                .bands(screen.bands.clone())
+                .render(f, area);
+        }
+    }
+
+    // player status
+    if areas.status.area() > 0 {
+        status::Status::default()
+            .is_playing(screen.is_playing)
+            .volume(screen.volume)
+            .speed(screen.speed)
+            .clock(status::Clock {
+                elapsed_ms: screen.elapsed_ms,
+                total_ms: screen.track.duration,
+            })
+            .levels(screen.levels)
+            .liked(screen.liked)
+            .autoplay(screen.autoplay)
+            .shuffle(screen.shuffle)
+            .repeat(screen.repeat)
+            .recording(screen.recording)
+            .render(f, areas.status);
+    }
+
+    // feedback from the last action, or the comment nearest the playhead
+    if areas.ticker.area() > 0 {
+        if let Some(message) = screen.message {
+            Paragraph::new([Text::raw(message)].iter()).render(f, areas.ticker);
+        } else if let Some(comment) = screen.comment {
+            comments::Ticker::default()
+                .comment(comment)
+                .render(f, areas.ticker);
+        }
+    }
+
+    // text input, e.g. writing a comment
+    if let Some((ref title, ref text)) = screen.prompt {
+        // keep the box a sensible size while the text is short
+        let size = f.size();
+        popup::Popup::default()
+            .theme(screen.theme)
+            .title(title)
+            .lines(vec![format!("> {:<40}", format!("{}_", text))])
+            .render(f, size);
+    }
+
+    // offer to pick up from a saved position
+    if let Some(position) = screen.resume {
+        let size = f.size();
+        popup::Popup::default()
+            .theme(screen.theme)
+            .title("Resume")
+            .lines(vec![
+                format!("Resume from {}?", status::Clock::format(position)),
+                String::new(),
+                "y: resume  n: start over".to_string(),
+            ])
+            .render(f, size);
+    }
+
+    // key bindings, over everything else
+    if let Some(ref help) = screen.help {
+        let size = f.size();
+        popup::Popup::default()
+            .theme(screen.theme)
+            .title("Help")
+            .columns(help)
+            .render(f, size);
+    }
+}
+
+// Split an area in two, stacked when there's height to spare,
+// otherwise side by side
+fn halves(area: Rect, stacked: bool) -> (Rect, Rect) {
+    if stacked {
+        let top = area.height / 2;
+        (
+            Rect::new(area.left(), area.top(), area.width, top),
+            Rect::new(area.left(), area.top() + top, area.width, area.height - top),
+        )
+    } else {
+        let left = area.width / 2;
+        (
+            Rect::new(area.left(), area.top(), left, area.height),
+            Rect::new(
+                area.left() + left,
+                area.top(),
+                area.width - left,
+                area.height,
+            ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tui::backend::TestBackend;
+    use tui::buffer::Buffer;
+    use tui::Terminal;
+
+    use crate::keys;
+
+    fn track() -> soundcloud::Track {
+        soundcloud::Track {
+            duration: 4112738,
+            genre: String::from("Techno"),
+            title: String::from("Art of Minimal Techno"),
+            user: soundcloud::User {
+                username: String::from("trippycode"),
+                ..soundcloud::User::default()
+            },
+            ..soundcloud::Track::default()
+        }
+    }
+
+    fn render(width: u16, height: u16, view: View) -> Buffer {
+        render_with(width, height, |screen| screen.view = view)
+    }
+
+    fn render_with<F>(width: u16, height: u16, setup: F) -> Buffer
+    where
+        F: FnOnce(&mut Screen),
+    {
+        let track = track();
+        let wave = soundcloud::Wave {
+            width: 1800,
+            height: 140,
+            samples: (0..1800).map(|i| (i % 140) as u16).collect(),
+        };
+        let comment = soundcloud::Comment {
+            body: String::from("that\ndrop"),
+            timestamp: Some(1000 * 60 * 29),
+            user: soundcloud::User {
+                username: String::from("listener"),
+                ..soundcloud::User::default()
+            },
+        };
+        let mut screen = Screen {
+            track: &track,
+            wave: &wave,
+            view: View::Wave,
+            progress: 42.5,
+            markers: vec![10.0, 50.0],
+            comment: Some(&comment),
+            bands: vec![0.5; 32],
+            is_playing: true,
+            volume: 100,
+            speed: 1.0,
+            elapsed_ms: 1000 * 60 * 30,
+            levels: level::Reading::default(),
+            liked: Some(true),
+            autoplay: false,
+            shuffle: false,
+            repeat: queue::Repeat::Off,
+            recording: false,
+            message: None,
+            info: false,
+            artwork: None,
+            help: None,
+            prompt: None,
+            resume: None,
+            pane: None,
+            theme: theme::Theme::default(),
+        };
+        setup(&mut screen);
+
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal.draw(|mut f| draw(&mut f, &screen)).unwrap();
+        terminal.backend().buffer().clone()
+    }
+
+    fn line(buf: &Buffer, y: u16) -> String {
+        (0..buf.area.width)
+            .map(|x| buf.get(x, y).symbol.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_draw_sizes() {
+        let views = [View::Wave, View::Spectrum, View::Both];
+        let sizes = [
+            (0, 0),
+            (1, 1),
+            (5, 2),
+            (12, 3),
+            (20, 5),
+            (39, 19),
+            (40, 20),
+            (80, 24),
+            (99, 29),
+            (120, 40),
+            (250, 70),
+        ];
+
+        for &view in views.iter() {
+            for &(width, height) in sizes.iter() {
+                render(width, height, view);
+                render_with(width, height, |screen| {
+                    screen.view = view;
+                    screen.help = Some(keys::Bindings::default().help());
+                });
+                render_with(width, height, |screen| {
+                    screen.view = view;
+                    screen.info = true;
+                });
+                render_with(width, height, |screen| {
+                    screen.message = Some("Liked");
+                    screen.prompt = Some(("Comment at 30:00".to_string(), "nice".to_string()));
+                });
+                render_with(width, height, |screen| screen.resume = Some(1000));
+                render_with(width, height, |screen| screen.pane = Some(pane(40)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_draw_normal() {
+        let buf = render(80, 24, View::Wave);
+        assert!(line(&buf, 2).contains("trippycode"));
+        assert!(line(&buf, 3).contains("Art of Minimal Techno"));
+
+        let status = line(&buf, 15);
+        assert!(status.starts_with("  30:00  Playing  ♥ Liked  Volume: 100%"));
+        assert!(status.trim_end().ends_with("01:08:32"));
+        assert!(line(&buf, 17).starts_with("  [29:00] listener: that drop  "));
+    }
+
+    #[test]
+    fn test_draw_modes() {
+        let buf = render_with(120, 24, |screen| {
+            screen.autoplay = true;
+            screen.shuffle = true;
+            screen.repeat = queue::Repeat::All;
+            screen.recording = true;
+            screen.speed = 1.5;
+        });
+        let status = line(&buf, 15);
+        assert!(status.contains("Playing  Recording  ♥ Liked  Autoplay  Shuffle  Repeat all  1.5x"));
+    }
+
+    #[test]
+    fn test_draw_compact() {
+        let buf = render(30, 6, View::Wave);
+        assert!(line(&buf, 0).starts_with("trippycode - Art of Minimal"));
+        assert!(line(&buf, 4).starts_with("[29:00] listener: that drop"));
+        assert!(line(&buf, 5).starts_with("30:00"));
+        assert!(line(&buf, 5).ends_with("01:08:32"));
+
+        // just the clock on a single line
+        let buf = render(12, 1, View::Wave);
+        assert!(line(&buf, 0).starts_with("30:00"));
+    }
+
+    #[test]
+    fn test_draw_help() {
+        let help = vec![
+            ("space".to_string(), "Play/Pause".to_string()),
+            ("q".to_string(), "Quit".to_string()),
+        ];
+        let buf = render_with(80, 24, |screen| screen.help = Some(help));
+
+        // centered over the player, covering the waveform
+        let lines: Vec<String> = (0..24).map(|y| line(&buf, y)).collect();
+        let top = lines.iter().position(|l| l.contains("Help")).unwrap();
+        assert!(lines[top + 1].contains("│ space  Play/Pause │"));
+        assert!(lines[top + 2].contains("│ q      Quit       │"));
+    }
+
+    #[test]
+    fn test_draw_prompt() {
+        let buf = render_with(80, 24, |screen| {
+            screen.message = Some("Comment posted");
+            screen.prompt = Some(("Comment at 30:00".to_string(), "nice".to_string()));
+        });
+
+        let lines: Vec<String> = (0..24).map(|y| line(&buf, y)).collect();
+        assert!(lines.iter().any(|l| l.contains("Comment at 30:00")));
+        assert!(lines.iter().any(|l| l.contains("│ > nice_ ")));
+        assert!(lines[17].starts_with("  Comment posted"));
+    }
+
+    #[test]
+    fn test_draw_resume() {
+        let buf = render_with(80, 24, |screen| {
+            screen.resume = Some(1000 * 60 * 43 + 10000)
+        });
+        let lines: Vec<String> = (0..24).map(|y| line(&buf, y)).collect();
+        assert!(lines.iter().any(|l| l.contains("│ Resume from 43:10?")));
+    }
+
+    fn pane(len: usize) -> Pane {
+        Pane {
+            title: "History".to_string(),
+            items: (0..len).map(|i| format!("track {}", i)).collect(),
+            selected: len.saturating_sub(1),
+        }
+    }
+
+    #[test]
+    fn test_draw_pane() {
+        let buf = render_with(80, 24, |screen| screen.pane = Some(pane(3)));
+        assert!(line(&buf, 2).contains("History"));
+        assert!(line(&buf, 3).starts_with("  │  track 0"));
+        assert!(line(&buf, 5).starts_with("  │> track 2"));
+        assert!(line(&buf, 15).starts_with("  30:00  Playing"));
+
+        // the selection stays in view
+        let buf = render_with(80, 24, |screen| screen.pane = Some(pane(40)));
+        assert!(line(&buf, 13).starts_with("  │> track 39"));
+
+        let buf = render_with(80, 24, |screen| screen.pane = Some(pane(0)));
+        assert!(line(&buf, 3).starts_with("  │Nothing here yet"));
+    }
+
+    #[test]
+    fn test_draw_info() {
+        let buf = render_with(80, 24, |screen| screen.info = true);
+        assert!(line(&buf, 2).contains("Art of Minimal Techno"));
+        assert!(line(&buf, 5).starts_with("  Genre    Techno"));
+        assert!(line(&buf, 15).starts_with("  30:00  Playing"));
+    }
+}
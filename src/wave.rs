@@ -3,11 +3,15 @@ use tui::layout::Rect;
 use tui::style::{Color, Modifier, Style};
 use tui::widgets::Widget;
 
+use crate::theme::Theme;
+
 pub struct Wave {
     pub width: u16,
     pub height: u16,
     pub progress: f32,
     pub samples: Vec<u16>,
+    pub markers: Vec<f32>,
+    pub theme: Theme,
 }
 
 impl Default for Wave {
@@ -17,6 +21,8 @@ impl Default for Wave {
             height: 0,
             progress: 0.0,
             samples: Vec::new(),
+            markers: Vec::new(),
+            theme: Theme::default(),
         }
     }
 }
@@ -41,44 +47,72 @@ impl Wave {
         self.samples = samples;
         self
     }
+
+    /// Positions (as percentages, like progress) to mark above the waveform.
+    pub fn markers(&mut self, markers: Vec<f32>) -> &mut Wave {
+        self.markers = markers;
+        self
+    }
+
+    pub fn theme(&mut self, theme: Theme) -> &mut Wave {
+        self.theme = theme;
+        self
+    }
 }
 
 impl Widget for Wave {
     fn draw(&mut self, area: Rect, buf: &mut Buffer) {
+        if area.width == 0 || area.height == 0 || self.samples.is_empty() || self.height == 0 {
+            return;
+        }
+
+        // markers take the top line, if there's room to spare for them
+        let area = if !self.markers.is_empty() && area.height >= 3 {
+            let marker_style = Style::default().fg(Color::DarkGray);
+            for marker in self.markers.iter() {
+                let x = (clamp(*marker, 0.0, 100.0) / 100.0 * area.width as f32) as u16;
+                let x = x.min(area.width - 1);
+                buf.set_string(area.left() + x, area.top(), "▾", marker_style);
+            }
+
+            Rect::new(area.left(), area.top() + 1, area.width, area.height - 1)
+        } else {
+            area
+        };
+
         // calculate a "resolution" for the waveform
         // based on the width of the buffer area
-        let width = area.right() - area.left();
-        let steps = self.samples.len() as u16 / width;
+        let width = area.width;
+        let steps = self.samples.len() as f32 / width as f32;
 
         for x in 0..width {
             // given the calculated resolution, grab a sample
             // from the waveform at every step, and calculate
-            // its relative display height (0 to 10)
-            let sample = self.samples[(x * steps) as usize];
-            let height = ((sample as f32 / self.height as f32) * 10.0) as u8;
-
-            // create the line to display, up to height
-            let mut line = vec![" "; (10 - height) as usize];
-            line.resize_with(10, || "|");
+            // its relative display height (0 to area height)
+            let index = ((x as f32 * steps) as usize).min(self.samples.len() - 1);
+            let sample = self.samples[index];
+            let ratio = clamp(sample as f32 / self.height as f32, 0.0, 1.0);
+            let height = (ratio * area.height as f32) as u16;
 
             // stylize line with color based on the progress (as percentage)
             let relative_pos = (x as f32 / width as f32) * 100.0;
             let default_style = Style::default().modifier(Modifier::BOLD);
             let style = if self.progress > relative_pos {
                 // progress is fully past x position
-                default_style.fg(ORANGE)
+                default_style.fg(self.theme.accent())
             } else if self.progress as u8 == relative_pos as u8 {
                 // progress is at or in-between x position
                 let p = clamp(self.progress.fract() * 10.0, 0.0, 9.0) as u8;
-                default_style.fg(COLORS[p as usize])
+                default_style.fg(self.theme.blend(p as f32 / 9.0))
             } else {
                 // progress is less than x position
                 default_style
             };
 
-            // draw line
-            for y in 0..line.len() as u16 {
-                buf.set_string(area.left() + x, area.top() + y, line[y as usize], style);
+            // draw line, up to height, from the bottom
+            for y in 0..area.height {
+                let symbol = if y < area.height - height { " " } else { "|" };
+                buf.set_string(area.left() + x, area.top() + y, symbol, style);
             }
         }
     }
@@ -95,17 +129,3 @@ fn clamp(value: f32, min: f32, max: f32) -> f32 {
 
     value
 }
-
-const ORANGE: Color = Color::Rgb(237, 97, 43);
-const COLORS: [Color; 10] = [
-    Color::Rgb(255, 255, 255),
-    Color::Rgb(253, 237, 231),
-    Color::Rgb(251, 220, 208),
-    Color::Rgb(249, 202, 184),
-    Color::Rgb(247, 185, 161),
-    Color::Rgb(245, 167, 137),
-    Color::Rgb(243, 150, 114),
-    Color::Rgb(241, 132, 90),
-    Color::Rgb(239, 115, 67),
-    Color::Rgb(237, 97, 43),
-];